@@ -0,0 +1,554 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+use redis::RedisResult;
+
+use crate::browser::glob_match;
+use crate::rdb::{RdbError, RdbReader};
+use crate::redis_conn::ConnectionHolder;
+use crate::viewer::edit_arg;
+
+#[derive(Debug)]
+pub enum AofError {
+    Io(io::Error),
+    Rdb(RdbError),
+    Manifest(String),
+    //position and what was wrong there, a cut before it gives a loadable file
+    Corrupt(u64, String),
+}
+
+impl fmt::Display for AofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AofError::Io(err) => write!(f, "{}", err),
+            AofError::Rdb(err) => write!(f, "rdb preamble {}", err),
+            AofError::Manifest(msg) => write!(f, "bad manifest: {}", msg),
+            AofError::Corrupt(pos, msg) => write!(f, "corrupt at {}: {}", pos, msg),
+        }
+    }
+}
+
+impl From<io::Error> for AofError {
+    fn from(err: io::Error) -> Self {
+        AofError::Io(err)
+    }
+}
+
+impl From<RdbError> for AofError {
+    fn from(err: RdbError) -> Self {
+        AofError::Rdb(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AofCommand {
+    //index into Aof.files
+    pub file: usize,
+    //bytes before the command over all files, commands from an rdb part get its start
+    pub pos: u64,
+    //unix seconds of the last #TS annotation
+    pub time: Option<i64>,
+    pub args: Vec<Vec<u8>>,
+}
+
+impl AofCommand {
+    pub fn name(&self) -> String {
+        self.args
+            .first()
+            .map_or(String::new(), |n| String::from_utf8_lossy(n).to_uppercase())
+    }
+
+    /// key arguments, guessed from the command name since there is no server to ask
+    pub fn keys(&self) -> Vec<&[u8]> {
+        command_keys(&self.args)
+    }
+
+    /// as it is written in an aof
+    pub fn to_resp(&self) -> Vec<u8> {
+        let mut out = format!("*{}\r\n", self.args.len()).into_bytes();
+        for arg in &self.args {
+            out.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+            out.extend_from_slice(arg);
+            out.extend_from_slice(b"\r\n");
+        }
+        out
+    }
+}
+
+/// key arguments of a command line by its name, the first argument when the name is not known
+pub(crate) fn command_keys(args: &[Vec<u8>]) -> Vec<&[u8]> {
+    let name = args
+        .first()
+        .map_or(String::new(), |n| String::from_utf8_lossy(n).to_uppercase());
+    let args: Vec<&[u8]> = args.iter().skip(1).map(|a| a.as_slice()).collect();
+    match name.as_str() {
+        "SELECT" | "MULTI" | "EXEC" | "DISCARD" | "FLUSHALL" | "FLUSHDB" | "SWAPDB" | "PING"
+        | "FUNCTION" | "SCRIPT" | "PUBLISH" => vec![],
+        //not in an aof, but seen by MONITOR
+        "INFO" | "CONFIG" | "CLIENT" | "SCAN" | "KEYS" | "DBSIZE" | "AUTH" | "HELLO" | "COMMAND"
+        | "ECHO" | "TIME" | "SLOWLOG" | "CLUSTER" | "SUBSCRIBE" | "PSUBSCRIBE" => vec![],
+        "DEL" | "UNLINK" | "EXISTS" | "TOUCH" | "WATCH" | "MGET" => args,
+        "MSET" | "MSETNX" => args.into_iter().step_by(2).collect(),
+        "RENAME" | "RENAMENX" | "SMOVE" | "RPOPLPUSH" | "LMOVE" | "COPY" => {
+            args.into_iter().take(2).collect()
+        }
+        "OBJECT" | "MEMORY" => args.into_iter().skip(1).take(1).collect(),
+        "EVAL" | "EVALSHA" | "FCALL" => {
+            let count = args
+                .get(1)
+                .and_then(|n| std::str::from_utf8(n).ok())
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(0);
+            args.into_iter().skip(2).take(count).collect()
+        }
+        _ => args.into_iter().take(1).collect(),
+    }
+}
+
+impl fmt::Display for AofCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let args: Vec<String> = self.args.iter().map(|a| edit_arg(a)).collect();
+        write!(f, "{}", args.join(" "))
+    }
+}
+
+/// which commands to keep, an empty list of names keeps all of them
+#[derive(Debug, Clone, Default)]
+pub struct AofFilter {
+    pub pattern: Option<String>,
+    pub commands: Vec<String>,
+}
+
+impl AofFilter {
+    pub fn matches(&self, cmd: &AofCommand) -> bool {
+        let name = cmd.name();
+        //db and transaction context stays, replay would go wrong without it
+        if ["SELECT", "MULTI", "EXEC"].contains(&name.as_str()) {
+            return true;
+        }
+        let named =
+            self.commands.is_empty() || self.commands.iter().any(|c| c.eq_ignore_ascii_case(&name));
+        let keyed = match &self.pattern {
+            None => true,
+            Some(pattern) => cmd.keys().iter().any(|k| glob_match(pattern.as_bytes(), k)),
+        };
+        named && keyed
+    }
+}
+
+/// where to cut, everything from there on is dropped
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cut {
+    Pos(u64),
+    //unix seconds, commands annotated later are dropped
+    Time(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AofPart {
+    Base,
+    Incr,
+}
+
+#[derive(Debug, Clone)]
+pub struct AofFile {
+    pub path: PathBuf,
+    pub part: AofPart,
+}
+
+/// a plain appendonly file, or the base and incr files a multipart manifest lists
+#[derive(Debug, Clone)]
+pub struct Aof {
+    pub files: Vec<AofFile>,
+}
+
+fn parse_manifest(dir: &Path, text: &str) -> Result<Vec<AofFile>, AofError> {
+    let mut base = None;
+    let mut incr = Vec::new();
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let value = |name: &str| {
+            words
+                .chunks(2)
+                .find(|kv| kv[0] == name && kv.len() == 2)
+                .map(|kv| kv[1])
+                .ok_or_else(|| AofError::Manifest(format!("no {} in {}", name, line)))
+        };
+        let file = AofFile {
+            path: dir.join(value("file")?),
+            part: AofPart::Incr,
+        };
+        let seq: u64 = value("seq")?
+            .parse()
+            .map_err(|_| AofError::Manifest(format!("bad seq in {}", line)))?;
+        match value("type")? {
+            "b" => {
+                base = Some(AofFile {
+                    part: AofPart::Base,
+                    ..file
+                })
+            }
+            "i" => incr.push((seq, file)),
+            //history files are left over from a rewrite
+            "h" => {}
+            other => return Err(AofError::Manifest(format!("unknown type {}", other))),
+        }
+    }
+    incr.sort_by_key(|(seq, _)| *seq);
+    Ok(base
+        .into_iter()
+        .chain(incr.into_iter().map(|(_, f)| f))
+        .collect())
+}
+
+impl Aof {
+    /// a file, a manifest, or the appendonlydir holding one
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Aof, AofError> {
+        let path = path.as_ref();
+        let manifest = if path.is_dir() {
+            let found = fs::read_dir(path)?
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .find(|p| p.extension().is_some_and(|e| e == "manifest"));
+            match found {
+                Some(found) => found,
+                None => return Err(AofError::Manifest(format!("none in {}", path.display()))),
+            }
+        } else if path.extension().is_some_and(|e| e == "manifest") {
+            path.to_path_buf()
+        } else {
+            return Ok(Aof {
+                files: vec![AofFile {
+                    path: path.to_path_buf(),
+                    part: AofPart::Incr,
+                }],
+            });
+        };
+        let dir = manifest.parent().unwrap_or_else(|| Path::new("."));
+        let files = parse_manifest(dir, &fs::read_to_string(&manifest)?)?;
+        Ok(Aof { files })
+    }
+
+    /// every command of every file in order
+    pub fn commands(&self) -> Result<Vec<AofCommand>, AofError> {
+        let mut commands = Vec::new();
+        let mut start = 0;
+        for (i, file) in self.files.iter().enumerate() {
+            let input = BufReader::new(File::open(&file.path)?);
+            start += read_commands(input, i, start, &mut commands)?;
+        }
+        Ok(commands)
+    }
+}
+
+fn read_line<R: BufRead>(input: &mut R, pos: &mut u64) -> Result<String, AofError> {
+    let mut line = Vec::new();
+    let n = input.read_until(b'\n', &mut line)?;
+    if !line.ends_with(b"\r\n") {
+        return Err(AofError::Corrupt(*pos, "truncated line".to_string()));
+    }
+    *pos += n as u64;
+    line.truncate(line.len() - 2);
+    String::from_utf8(line).map_err(|_| AofError::Corrupt(*pos, "bad line".to_string()))
+}
+
+fn read_number<R: BufRead>(input: &mut R, pos: &mut u64, prefix: char) -> Result<usize, AofError> {
+    let at = *pos;
+    let line = read_line(input, pos)?;
+    line.strip_prefix(prefix)
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| AofError::Corrupt(at, format!("expected {}<number>, got {}", prefix, line)))
+}
+
+/// commands of one file appended, gives the bytes read.
+/// an rdb preamble or base turns into the commands that make its keys
+pub fn read_commands<R: BufRead>(
+    mut input: R,
+    file: usize,
+    start: u64,
+    commands: &mut Vec<AofCommand>,
+) -> Result<u64, AofError> {
+    let mut pos = 0;
+    let mut time = None;
+    loop {
+        let first = match input.fill_buf()?.first() {
+            None => return Ok(pos),
+            Some(b) => *b,
+        };
+        match first {
+            b'R' if pos == 0 => {
+                let mut reader = RdbReader::new(&mut input)?;
+                let mut db = 0;
+                for entry in reader.by_ref() {
+                    let entry = entry?;
+                    if entry.db != db {
+                        db = entry.db;
+                        let select = vec![b"SELECT".to_vec(), db.to_string().into_bytes()];
+                        commands.push(AofCommand {
+                            file,
+                            pos: start,
+                            time,
+                            args: select,
+                        });
+                    }
+                    for args in entry.commands() {
+                        commands.push(AofCommand {
+                            file,
+                            pos: start,
+                            time,
+                            args,
+                        });
+                    }
+                }
+                pos = reader.position();
+                //the rest of the file goes on in the db the rdb ended with
+                if db != 0 {
+                    let select = vec![b"SELECT".to_vec(), b"0".to_vec()];
+                    commands.push(AofCommand {
+                        file,
+                        pos: start,
+                        time,
+                        args: select,
+                    });
+                }
+            }
+            b'#' => {
+                let at = pos;
+                let line = read_line(&mut input, &mut pos)?;
+                if let Some(ts) = line.strip_prefix("#TS:") {
+                    let ts = ts
+                        .parse()
+                        .map_err(|_| AofError::Corrupt(at, line.clone()))?;
+                    time = Some(ts);
+                }
+            }
+            b'*' => {
+                let at = pos;
+                let count = read_number(&mut input, &mut pos, '*')?;
+                let mut args = Vec::with_capacity(count.min(1024));
+                for _ in 0..count {
+                    let len = read_number(&mut input, &mut pos, '$')?;
+                    let with_crlf = len
+                        .checked_add(2)
+                        .ok_or_else(|| AofError::Corrupt(at, format!("length {}", len)))?;
+                    let mut arg = Vec::new();
+                    (&mut input).take(with_crlf as u64).read_to_end(&mut arg)?;
+                    if arg.len() != with_crlf || !arg.ends_with(b"\r\n") {
+                        return Err(AofError::Corrupt(at, "truncated command".to_string()));
+                    }
+                    pos += arg.len() as u64;
+                    arg.truncate(len);
+                    args.push(arg);
+                }
+                commands.push(AofCommand {
+                    file,
+                    pos: start + at,
+                    time,
+                    args,
+                });
+            }
+            other => {
+                return Err(AofError::Corrupt(
+                    pos,
+                    format!("unexpected byte {:#x}", other),
+                ))
+            }
+        }
+    }
+}
+
+/// commands before the cut, a transaction the cut runs through is dropped whole
+pub fn cut(mut commands: Vec<AofCommand>, at: Cut) -> Vec<AofCommand> {
+    let end = commands
+        .iter()
+        .position(|c| match at {
+            Cut::Pos(pos) => c.pos >= pos,
+            Cut::Time(ts) => c.time.is_some_and(|t| t > ts),
+        })
+        .unwrap_or(commands.len());
+    commands.truncate(end);
+    let open = commands.iter().rposition(|c| c.name() == "MULTI");
+    let closed = commands.iter().rposition(|c| c.name() == "EXEC");
+    if let Some(open) = open.filter(|o| closed.is_none_or(|c| c < *o)) {
+        commands.truncate(open);
+    }
+    commands
+}
+
+pub fn write_aof<W: Write>(commands: &[AofCommand], out: &mut W) -> io::Result<()> {
+    for cmd in commands {
+        out.write_all(&cmd.to_resp())?;
+    }
+    out.flush()
+}
+
+/// run the commands on the current connection, pipelined in batches.
+/// a SELECT ends the batch so the holder knows the db it is left in
+pub fn replay(
+    holder: &mut ConnectionHolder,
+    commands: &[AofCommand],
+    batch: usize,
+) -> RedisResult<u64> {
+    let mut done = 0;
+    let mut pipe = redis::pipe();
+    let mut queued = 0;
+    for cmd in commands {
+        if cmd.name() == "SELECT" {
+            let db = cmd
+                .args
+                .get(1)
+                .and_then(|d| std::str::from_utf8(d).ok())
+                .and_then(|d| d.parse().ok())
+                .ok_or((redis::ErrorKind::ClientError, "bad SELECT"))?;
+            holder.pipeline::<()>(&pipe)?;
+            pipe = redis::pipe();
+            queued = 0;
+            holder.select_db(db)?;
+            done += 1;
+            continue;
+        }
+        let mut args = cmd.args.iter();
+        let mut next = redis::cmd(&cmd.name());
+        args.next();
+        for arg in args {
+            next.arg(arg.as_slice());
+        }
+        pipe.add_command(next).ignore();
+        queued += 1;
+        if queued == batch {
+            holder.pipeline::<()>(&pipe)?;
+            pipe = redis::pipe();
+            queued = 0;
+        }
+        done += 1;
+    }
+    holder.pipeline::<()>(&pipe)?;
+    Ok(done)
+}
+
+#[cfg(test)]
+fn test_aof() -> Vec<u8> {
+    let mut aof = Vec::new();
+    aof.extend_from_slice(b"#TS:1000\r\n*3\r\n$3\r\nSET\r\n$6\r\nuser:1\r\n$1\r\na\r\n");
+    aof.extend_from_slice(
+        b"*1\r\n$5\r\nMULTI\r\n*2\r\n$4\r\nincr\r\n$3\r\nhit\r\n*1\r\n$4\r\nEXEC\r\n",
+    );
+    aof.extend_from_slice(
+        b"#TS:2000\r\n*5\r\n$4\r\nMSET\r\n$6\r\nuser:2\r\n$1\r\nb\r\n$1\r\nc\r\n$1\r\nd\r\n",
+    );
+    aof.extend_from_slice(b"*1\r\n$5\r\nMULTI\r\n*1\r\n$8\r\nFLUSHALL\r\n*1\r\n$4\r\nEXEC\r\n");
+    aof
+}
+
+#[test]
+fn test_read_aof() {
+    let aof = test_aof();
+    let mut commands = Vec::new();
+    let read = read_commands(&aof[..], 0, 0, &mut commands).unwrap();
+    assert_eq!(aof.len() as u64, read);
+    let names: Vec<String> = commands.iter().map(|c| c.name()).collect();
+    assert_eq!(
+        vec!["SET", "MULTI", "INCR", "EXEC", "MSET", "MULTI", "FLUSHALL", "EXEC"],
+        names
+    );
+    assert_eq!((10, Some(1000)), (commands[0].pos, commands[0].time));
+    assert_eq!(Some(2000), commands[4].time);
+    assert_eq!(vec![&b"user:2"[..], b"c"], commands[4].keys());
+    assert_eq!("SET user:1 a", commands[0].to_string());
+    let mut out = Vec::new();
+    write_aof(&commands[..1], &mut out).unwrap();
+    assert_eq!(&aof[10..10 + out.len()], &out[..]);
+
+    let huge = read_commands(&b"*1\r\n$18446744073709551615\r\n"[..], 0, 0, &mut vec![]);
+    assert!(matches!(huge, Err(AofError::Corrupt(0, _))));
+    let mut select = Vec::new();
+    read_commands(&b"*1\r\n$6\r\nSELECT\r\n"[..], 0, 0, &mut select).unwrap();
+    let err = replay(&mut ConnectionHolder::new(), &select, 10).unwrap_err();
+    assert_eq!("bad SELECT", err.to_string());
+
+    let filter = AofFilter {
+        pattern: Some("user:*".to_string()),
+        commands: vec![],
+    };
+    let kept: Vec<String> = commands
+        .iter()
+        .filter(|c| filter.matches(c))
+        .map(|c| c.name())
+        .collect();
+    assert_eq!(vec!["SET", "MULTI", "EXEC", "MSET", "MULTI", "EXEC"], kept);
+
+    //cutting at the FLUSHALL drops the MULTI in front of it too
+    let flush = commands
+        .iter()
+        .find(|c| c.name() == "FLUSHALL")
+        .unwrap()
+        .pos;
+    let names: Vec<String> = cut(commands.clone(), Cut::Pos(flush))
+        .iter()
+        .map(|c| c.name())
+        .collect();
+    assert_eq!(vec!["SET", "MULTI", "INCR", "EXEC", "MSET"], names);
+    assert_eq!(4, cut(commands, Cut::Time(1500)).len());
+
+    let mut truncated = Vec::new();
+    let err = read_commands(&aof[..aof.len() - 3], 0, 0, &mut truncated).unwrap_err();
+    assert!(matches!(err, AofError::Corrupt(pos, _) if pos == flush + 18));
+}
+
+#[test]
+fn test_multipart_aof() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut base = b"REDIS0009".to_vec();
+    base.extend_from_slice(&[0xfe, 2, 0, 1, b'k', 1, b'v', 0xff, 0, 0, 0, 0, 0, 0, 0, 0]);
+    base.extend_from_slice(b"*2\r\n$3\r\nDEL\r\n$1\r\nk\r\n");
+    fs::write(dir.path().join("appendonly.aof.1.base.rdb"), &base).unwrap();
+    fs::write(dir.path().join("appendonly.aof.3.incr.aof"), test_aof()).unwrap();
+    fs::write(
+        dir.path().join("appendonly.aof.2.incr.aof"),
+        b"*1\r\n$4\r\nPING\r\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("appendonly.aof.manifest"),
+        "file appendonly.aof.1.base.rdb seq 1 type b\n\
+         file appendonly.aof.0.incr.aof seq 0 type h\n\
+         file appendonly.aof.3.incr.aof seq 3 type i\n\
+         file appendonly.aof.2.incr.aof seq 2 type i\n",
+    )
+    .unwrap();
+
+    let aof = Aof::open(dir.path()).unwrap();
+    assert_eq!(3, aof.files.len());
+    assert_eq!(AofPart::Base, aof.files[0].part);
+    let commands = aof.commands().unwrap();
+    let shown: Vec<String> = commands[..5].iter().map(|c| c.to_string()).collect();
+    assert_eq!(
+        vec!["SELECT 2", "SET k v", "SELECT 0", "DEL k", "PING"],
+        shown
+    );
+    assert_eq!((1, base.len() as u64), (commands[4].file, commands[4].pos));
+    assert_eq!(2, commands[5].file);
+}
+
+#[test]
+fn test_replay_aof() -> RedisResult<()> {
+    let mut commands = Vec::new();
+    read_commands(&test_aof()[..], 0, 0, &mut commands).unwrap();
+    let flush = commands
+        .iter()
+        .find(|c| c.name() == "FLUSHALL")
+        .unwrap()
+        .pos;
+    let commands = cut(commands, Cut::Pos(flush));
+
+    let mut holder = ConnectionHolder::new();
+    holder.put("redis://127.0.0.1:6379/0")?;
+    holder.execute(&["DEL", "user:1", "user:2", "c", "hit"])?;
+    assert_eq!(5, replay(&mut holder, &commands, 2)?);
+    let val: String = redis::from_redis_value(&holder.execute(&["GET", "user:2"])?)?;
+    assert_eq!("b", val);
+    holder.execute(&["DEL", "user:1", "user:2", "c", "hit"])?;
+    Ok(())
+}
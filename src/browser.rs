@@ -0,0 +1,385 @@
+use redis::RedisResult;
+
+use crate::redis_conn::ConnectionHolder;
+
+/// what SCAN walks, kind is a redis type like hash and needs redis 6 on the server,
+/// older servers get it filtered here instead
+#[derive(Debug, Clone)]
+pub struct ScanFilter {
+    pub pattern: String,
+    //a hint for how much work one SCAN call does, not an exact page size
+    pub count: usize,
+    pub kind: Option<String>,
+}
+
+impl Default for ScanFilter {
+    fn default() -> Self {
+        ScanFilter {
+            pattern: "*".to_string(),
+            count: 100,
+            kind: None,
+        }
+    }
+}
+
+impl ScanFilter {
+    pub fn matching(pattern: &str) -> ScanFilter {
+        ScanFilter {
+            pattern: pattern.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyInfo {
+    pub key: Vec<u8>,
+    pub kind: String,
+    //seconds, -1 without expire and -2 when the key went away meanwhile
+    pub ttl: i64,
+    //strlen, or the element count of a collection
+    pub len: Option<u64>,
+    //MEMORY USAGE, none when the server does not have it
+    pub memory: Option<u64>,
+}
+
+impl KeyInfo {
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.key).into_owned()
+    }
+}
+
+//a key as the reports and the key tree get it, for their tests
+#[cfg(test)]
+pub(crate) fn test_info(key: &str, kind: &str, len: u64, memory: u64, ttl: i64) -> KeyInfo {
+    KeyInfo {
+        key: key.as_bytes().to_vec(),
+        kind: kind.to_string(),
+        ttl,
+        len: Some(len),
+        memory: Some(memory),
+    }
+}
+
+/// pages through a db with SCAN, the cursor can be kept and resumed later
+#[derive(Debug, Clone)]
+pub struct KeyBrowser {
+    pub filter: ScanFilter,
+    cursor: u64,
+    done: bool,
+    //SCAN ... TYPE was refused, so the type is checked per key
+    type_on_server: bool,
+    memory_usage: bool,
+}
+
+fn scan_args(filter: &ScanFilter, cursor: u64, with_type: bool) -> Vec<String> {
+    let mut args = vec![
+        "SCAN".to_string(),
+        cursor.to_string(),
+        "MATCH".to_string(),
+        filter.pattern.clone(),
+        "COUNT".to_string(),
+        filter.count.max(1).to_string(),
+    ];
+    if let (true, Some(kind)) = (with_type, &filter.kind) {
+        args.push("TYPE".to_string());
+        args.push(kind.clone());
+    }
+    args
+}
+
+fn len_command(kind: &str) -> Option<&'static str> {
+    match kind {
+        "string" => Some("STRLEN"),
+        "list" => Some("LLEN"),
+        "hash" => Some("HLEN"),
+        "set" => Some("SCARD"),
+        "zset" => Some("ZCARD"),
+        "stream" => Some("XLEN"),
+        _ => None,
+    }
+}
+
+impl KeyBrowser {
+    pub fn new(filter: ScanFilter) -> KeyBrowser {
+        Self::resume(filter, 0)
+    }
+
+    /// continue a scan from a cursor an earlier page returned
+    pub fn resume(filter: ScanFilter, cursor: u64) -> KeyBrowser {
+        KeyBrowser {
+            filter,
+            cursor,
+            done: false,
+            type_on_server: true,
+            memory_usage: true,
+        }
+    }
+
+    pub fn cursor(&self) -> u64 {
+        self.cursor
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// one SCAN call, may well come back empty while the cursor is not done
+    pub fn scan_once(&mut self, holder: &mut ConnectionHolder) -> RedisResult<Vec<Vec<u8>>> {
+        if self.done {
+            return Ok(vec![]);
+        }
+        let with_type = self.type_on_server && self.filter.kind.is_some();
+        let reply = match holder.execute(&scan_args(&self.filter, self.cursor, with_type)) {
+            Err(err) if with_type && err.kind() == redis::ErrorKind::ResponseError => {
+                self.type_on_server = false;
+                holder.execute(&scan_args(&self.filter, self.cursor, false))?
+            }
+            reply => reply?,
+        };
+        let (cursor, keys): (u64, Vec<Vec<u8>>) = redis::from_redis_value(&reply)?;
+        self.cursor = cursor;
+        self.done = cursor == 0;
+        Ok(keys)
+    }
+
+    /// key names only, SCAN batches are kept whole so a page can run a bit
+    /// over the size, the type filter is checked here on old servers
+    pub fn next_names(
+        &mut self,
+        holder: &mut ConnectionHolder,
+        size: usize,
+    ) -> RedisResult<Vec<Vec<u8>>> {
+        let mut names = Vec::new();
+        while !self.done && names.len() < size {
+            let batch = self.scan_once(holder)?;
+            if self.type_on_server || self.filter.kind.is_none() {
+                names.extend(batch);
+            } else {
+                let kinds = key_types(holder, &batch)?;
+                let kind = self.filter.kind.as_deref();
+                names.extend(
+                    batch
+                        .into_iter()
+                        .zip(kinds)
+                        .filter(|(_, k)| Some(k.as_str()) == kind)
+                        .map(|(key, _)| key),
+                );
+            }
+        }
+        Ok(names)
+    }
+
+    /// the next page with type, ttl and size of every key
+    pub fn next_page(
+        &mut self,
+        holder: &mut ConnectionHolder,
+        size: usize,
+    ) -> RedisResult<Vec<KeyInfo>> {
+        let names = self.next_names(holder, size)?;
+        self.describe(holder, names)
+    }
+
+    /// type, ttl and size in two round trips, plus one for MEMORY USAGE
+    pub fn describe(
+        &mut self,
+        holder: &mut ConnectionHolder,
+        keys: Vec<Vec<u8>>,
+    ) -> RedisResult<Vec<KeyInfo>> {
+        if keys.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut pipe = redis::pipe();
+        for key in &keys {
+            pipe.cmd("TYPE")
+                .arg(key.as_slice())
+                .cmd("TTL")
+                .arg(key.as_slice());
+        }
+        let meta: Vec<(String, i64)> = holder.pipeline(&pipe)?;
+
+        let mut pipe = redis::pipe();
+        for (key, (kind, _)) in keys.iter().zip(&meta) {
+            match len_command(kind) {
+                Some(cmd) => pipe.cmd(cmd).arg(key.as_slice()),
+                //modules types and vanished keys
+                None => pipe.cmd("EXISTS").arg(key.as_slice()),
+            };
+        }
+        let lens: Vec<u64> = holder.pipeline(&pipe)?;
+
+        let mut memory = vec![None; keys.len()];
+        if self.memory_usage {
+            let mut pipe = redis::pipe();
+            for key in &keys {
+                pipe.cmd("MEMORY").arg("USAGE").arg(key.as_slice());
+            }
+            match holder.pipeline::<Vec<Option<u64>>>(&pipe) {
+                Ok(usage) => memory = usage,
+                //before redis 4 or renamed away
+                Err(_) => self.memory_usage = false,
+            }
+        }
+
+        Ok(keys
+            .into_iter()
+            .zip(meta)
+            .zip(lens)
+            .zip(memory)
+            .map(|(((key, (kind, ttl)), len), memory)| KeyInfo {
+                len: len_command(&kind).map(|_| len),
+                key,
+                kind,
+                ttl,
+                memory,
+            })
+            .collect())
+    }
+}
+
+fn key_types(holder: &mut ConnectionHolder, keys: &[Vec<u8>]) -> RedisResult<Vec<String>> {
+    if keys.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut pipe = redis::pipe();
+    for key in keys {
+        pipe.cmd("TYPE").arg(key.as_slice());
+    }
+    holder.pipeline(&pipe)
+}
+
+/// up to limit key names matching the filter, walking the whole keyspace in SCAN steps
+pub fn scan_names(
+    holder: &mut ConnectionHolder,
+    filter: ScanFilter,
+    limit: usize,
+) -> RedisResult<Vec<Vec<u8>>> {
+    let mut browser = KeyBrowser::new(filter);
+    let mut names = browser.next_names(holder, limit)?;
+    names.truncate(limit);
+    Ok(names)
+}
+
+/// redis MATCH rules: * ? [abc] [^a-z] and backslash escapes, for keys already fetched
+pub fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', _)) => {
+            let rest: &[u8] = {
+                let stars = pattern.iter().take_while(|&&c| c == b'*').count();
+                &pattern[stars..]
+            };
+            rest.is_empty() || (0..=text.len()).any(|i| glob_match(rest, &text[i..]))
+        }
+        Some((b'?', rest)) => !text.is_empty() && glob_match(rest, &text[1..]),
+        Some((b'[', class)) => {
+            let c = match text.first() {
+                Some(c) => *c,
+                None => return false,
+            };
+            let negate = class.first() == Some(&b'^');
+            let mut i = if negate { 1 } else { 0 };
+            let mut matched = false;
+            while i < class.len() && class[i] != b']' {
+                if class[i] == b'\\' && i + 1 < class.len() {
+                    matched |= class[i + 1] == c;
+                    i += 2;
+                } else if i + 2 < class.len() && class[i + 1] == b'-' && class[i + 2] != b']' {
+                    let (lo, hi) = (class[i].min(class[i + 2]), class[i].max(class[i + 2]));
+                    matched |= lo <= c && c <= hi;
+                    i += 3;
+                } else {
+                    matched |= class[i] == c;
+                    i += 1;
+                }
+            }
+            //an unclosed class runs to the end of the pattern like in redis
+            let rest = &class[(i + 1).min(class.len())..];
+            matched != negate && glob_match(rest, &text[1..])
+        }
+        Some((b'\\', rest)) if !rest.is_empty() => {
+            text.first() == Some(&rest[0]) && glob_match(&rest[1..], &text[1..])
+        }
+        Some((c, rest)) => text.first() == Some(c) && glob_match(rest, &text[1..]),
+    }
+}
+
+#[test]
+fn test_glob_match() {
+    let cases: &[(&str, &str, bool)] = &[
+        ("*", "", true),
+        ("user:*", "user:1", true),
+        ("user:*", "order:1", false),
+        ("*:name:*", "supcom:name:list", true),
+        ("h?llo", "hello", true),
+        ("h?llo", "hllo", false),
+        ("h[ae]llo", "hallo", true),
+        ("h[^e]llo", "hello", false),
+        ("h[a-b]llo", "hbllo", true),
+        ("h[b-a]llo", "hallo", true),
+        ("a\\*b", "a*b", true),
+        ("a\\*b", "axb", false),
+        ("**x", "abcx", true),
+    ];
+    for (pattern, text, expect) in cases {
+        assert_eq!(
+            *expect,
+            glob_match(pattern.as_bytes(), text.as_bytes()),
+            "{} {}",
+            pattern,
+            text
+        );
+    }
+}
+
+#[test]
+fn test_scan_args() {
+    let mut filter = ScanFilter::matching("user:*");
+    filter.kind = Some("hash".to_string());
+    assert_eq!(
+        vec!["SCAN", "17", "MATCH", "user:*", "COUNT", "100", "TYPE", "hash"],
+        scan_args(&filter, 17, true)
+    );
+    assert_eq!(6, scan_args(&filter, 0, false).len());
+    filter.count = 0;
+    assert_eq!("1", scan_args(&filter, 0, false)[5]);
+    assert_eq!(Some("ZCARD"), len_command("zset"));
+    assert_eq!(None, len_command("ReJSON-RL"));
+}
+
+#[test]
+fn test_browse_pages() -> RedisResult<()> {
+    let mut holder = ConnectionHolder::new();
+    holder.put("redis://127.0.0.1:6379/0")?;
+    for i in 0..250 {
+        holder.execute(&["SET", &format!("browse:str:{}", i), "abc"])?;
+    }
+    holder.execute(&["HSET", "browse:hash", "a", "1", "b", "2"])?;
+    holder.execute(&["EXPIRE", "browse:hash", "100"])?;
+
+    let mut browser = KeyBrowser::new(ScanFilter::matching("browse:*"));
+    let first = browser.next_page(&mut holder, 50)?;
+    assert!(first.len() >= 50);
+    let cursor = browser.cursor();
+    //a new browser picks up where the first one stopped
+    let mut resumed = KeyBrowser::resume(ScanFilter::matching("browse:*"), cursor);
+    let mut seen = first.len();
+    while !resumed.is_done() {
+        seen += resumed.next_page(&mut holder, 50)?.len();
+    }
+    assert!(seen >= 251);
+
+    let mut filter = ScanFilter::matching("browse:*");
+    filter.kind = Some("hash".to_string());
+    let hashes = KeyBrowser::new(filter).next_page(&mut holder, 1000)?;
+    assert_eq!(1, hashes.len());
+    assert_eq!("browse:hash", hashes[0].name());
+    assert_eq!(Some(2), hashes[0].len);
+    assert!(hashes[0].ttl > 0 && hashes[0].ttl <= 100);
+
+    let names = scan_names(&mut holder, ScanFilter::matching("browse:*"), 1000)?;
+    let mut del = vec![b"DEL".to_vec()];
+    del.extend(names);
+    holder.execute(&del)?;
+    Ok(())
+}
@@ -0,0 +1,600 @@
+use nom::bytes::complete::take;
+use nom::error::{make_error, ErrorKind};
+use nom::number::complete::{be_f32, be_f64, be_i32, be_i64, be_u16, be_u32, be_u8};
+use nom::{Err, IResult};
+
+use crate::javaser::decode_modified_utf8;
+
+pub const CLASS_MAGIC: u32 = 0xcafebabe;
+
+pub const ACC_PUBLIC: u16 = 0x0001;
+pub const ACC_PRIVATE: u16 = 0x0002;
+pub const ACC_PROTECTED: u16 = 0x0004;
+pub const ACC_STATIC: u16 = 0x0008;
+pub const ACC_FINAL: u16 = 0x0010;
+//ACC_SUPER for classes
+pub const ACC_SYNCHRONIZED: u16 = 0x0020;
+//ACC_BRIDGE for methods
+pub const ACC_VOLATILE: u16 = 0x0040;
+//ACC_VARARGS for methods
+pub const ACC_TRANSIENT: u16 = 0x0080;
+pub const ACC_NATIVE: u16 = 0x0100;
+pub const ACC_INTERFACE: u16 = 0x0200;
+pub const ACC_ABSTRACT: u16 = 0x0400;
+pub const ACC_STRICT: u16 = 0x0800;
+pub const ACC_SYNTHETIC: u16 = 0x1000;
+pub const ACC_ANNOTATION: u16 = 0x2000;
+pub const ACC_ENUM: u16 = 0x4000;
+
+pub type ParseResult<'a, T> = IResult<&'a [u8], T, (&'a [u8], ErrorKind)>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    //slot 0 and the slot after a long or double
+    Unusable,
+    Utf8(String),
+    Integer(i32),
+    Float(f32),
+    Long(i64),
+    Double(f64),
+    Class(u16),
+    Str(u16),
+    FieldRef(u16, u16),
+    MethodRef(u16, u16),
+    InterfaceMethodRef(u16, u16),
+    NameAndType(u16, u16),
+    MethodHandle(u8, u16),
+    MethodType(u16),
+    Dynamic(u16, u16),
+    InvokeDynamic(u16, u16),
+    Module(u16),
+    Package(u16),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstantPool {
+    pub entries: Vec<Constant>,
+}
+
+impl ConstantPool {
+    pub fn get(&self, idx: u16) -> Option<&Constant> {
+        self.entries.get(idx as usize)
+    }
+
+    pub fn utf8(&self, idx: u16) -> Option<&str> {
+        match self.get(idx)? {
+            Constant::Utf8(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    /// internal class name of a CONSTANT_Class entry, eg java/lang/String
+    pub fn class_name(&self, idx: u16) -> Option<&str> {
+        match self.get(idx)? {
+            Constant::Class(name_idx) => self.utf8(*name_idx),
+            _ => None,
+        }
+    }
+
+    pub fn name_and_type(&self, idx: u16) -> Option<(&str, &str)> {
+        match self.get(idx)? {
+            Constant::NameAndType(name, desc) => Some((self.utf8(*name)?, self.utf8(*desc)?)),
+            _ => None,
+        }
+    }
+
+    /// class, name and descriptor of a field, method or interface method ref
+    pub fn member_ref(&self, idx: u16) -> Option<(&str, &str, &str)> {
+        match self.get(idx)? {
+            Constant::FieldRef(class, nat)
+            | Constant::MethodRef(class, nat)
+            | Constant::InterfaceMethodRef(class, nat) => {
+                let (name, desc) = self.name_and_type(*nat)?;
+                Some((self.class_name(*class)?, name, desc))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExceptionEntry {
+    pub start_pc: u16,
+    pub end_pc: u16,
+    pub handler_pc: u16,
+    //0 for finally blocks
+    pub catch_type: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Code {
+    pub max_stack: u16,
+    pub max_locals: u16,
+    pub code: Vec<u8>,
+    pub exception_table: Vec<ExceptionEntry>,
+    pub attributes: Vec<Attribute>,
+}
+
+impl Code {
+    pub fn line_numbers(&self) -> Vec<LineNumber> {
+        self.attributes
+            .iter()
+            .filter_map(|attr| match attr {
+                Attribute::LineNumberTable(lines) => Some(lines.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineNumber {
+    pub start_pc: u16,
+    pub line: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InnerClass {
+    pub inner_class: u16,
+    //0 for local and anonymous classes
+    pub outer_class: u16,
+    pub inner_name: u16,
+    pub access_flags: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Attribute {
+    Code(Code),
+    LineNumberTable(Vec<LineNumber>),
+    SourceFile(u16),
+    Signature(u16),
+    ConstantValue(u16),
+    Exceptions(Vec<u16>),
+    InnerClasses(Vec<InnerClass>),
+    Other(String, Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemberInfo {
+    pub access_flags: u16,
+    pub name_index: u16,
+    pub descriptor_index: u16,
+    pub attributes: Vec<Attribute>,
+}
+
+impl MemberInfo {
+    pub fn name<'p>(&self, pool: &'p ConstantPool) -> &'p str {
+        pool.utf8(self.name_index).unwrap_or("")
+    }
+
+    pub fn descriptor<'p>(&self, pool: &'p ConstantPool) -> &'p str {
+        pool.utf8(self.descriptor_index).unwrap_or("")
+    }
+
+    pub fn code(&self) -> Option<&Code> {
+        self.attributes.iter().find_map(|attr| match attr {
+            Attribute::Code(code) => Some(code),
+            _ => None,
+        })
+    }
+
+    pub fn signature<'p>(&self, pool: &'p ConstantPool) -> Option<&'p str> {
+        signature_of(&self.attributes, pool)
+    }
+
+    pub fn has_flag(&self, flag: u16) -> bool {
+        self.access_flags & flag == flag
+    }
+}
+
+fn signature_of<'p>(attributes: &[Attribute], pool: &'p ConstantPool) -> Option<&'p str> {
+    attributes.iter().find_map(|attr| match attr {
+        Attribute::Signature(idx) => pool.utf8(*idx),
+        _ => None,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassFile {
+    pub minor_version: u16,
+    pub major_version: u16,
+    pub constant_pool: ConstantPool,
+    pub access_flags: u16,
+    pub this_class: u16,
+    pub super_class: u16,
+    pub interfaces: Vec<u16>,
+    pub fields: Vec<MemberInfo>,
+    pub methods: Vec<MemberInfo>,
+    pub attributes: Vec<Attribute>,
+}
+
+impl ClassFile {
+    pub fn class_name(&self) -> &str {
+        self.constant_pool.class_name(self.this_class).unwrap_or("")
+    }
+
+    /// None for java/lang/Object and module-info
+    pub fn super_class_name(&self) -> Option<&str> {
+        self.constant_pool.class_name(self.super_class)
+    }
+
+    pub fn interface_names(&self) -> Vec<&str> {
+        self.interfaces
+            .iter()
+            .filter_map(|idx| self.constant_pool.class_name(*idx))
+            .collect()
+    }
+
+    pub fn source_file(&self) -> Option<&str> {
+        self.attributes.iter().find_map(|attr| match attr {
+            Attribute::SourceFile(idx) => self.constant_pool.utf8(*idx),
+            _ => None,
+        })
+    }
+
+    pub fn signature(&self) -> Option<&str> {
+        signature_of(&self.attributes, &self.constant_pool)
+    }
+
+    pub fn has_flag(&self, flag: u16) -> bool {
+        self.access_flags & flag == flag
+    }
+
+    /// modifiers as java reflection reports them, nested classes keep
+    /// their private/protected/static flags in the InnerClasses attribute
+    pub fn modifiers(&self) -> u16 {
+        let this_name = self.class_name();
+        self.attributes
+            .iter()
+            .filter_map(|attr| match attr {
+                Attribute::InnerClasses(list) => Some(list),
+                _ => None,
+            })
+            .flatten()
+            .find(|inner| self.constant_pool.class_name(inner.inner_class) == Some(this_name))
+            .map_or(self.access_flags, |inner| inner.access_flags)
+    }
+
+    pub fn field(&self, name: &str) -> Option<&MemberInfo> {
+        self.fields
+            .iter()
+            .find(|f| f.name(&self.constant_pool) == name)
+    }
+
+    pub fn method(&self, name: &str, descriptor: &str) -> Option<&MemberInfo> {
+        self.methods.iter().find(|m| {
+            m.name(&self.constant_pool) == name && m.descriptor(&self.constant_pool) == descriptor
+        })
+    }
+
+    /// java version which emits this major version, 52 is java 8
+    pub fn java_version(&self) -> String {
+        match self.major_version {
+            45 => String::from("1.1"),
+            46..=48 => format!("1.{}", self.major_version - 44),
+            _ => format!("{}", self.major_version.saturating_sub(44)),
+        }
+    }
+}
+
+fn fail<T>(input: &[u8], kind: ErrorKind) -> ParseResult<'_, T> {
+    Err(Err::Error(make_error(input, kind)))
+}
+
+fn parse_constant(input: &[u8]) -> ParseResult<'_, (Constant, bool)> {
+    let (i, tag) = be_u8(input)?;
+    let two = |i| -> ParseResult<'_, (u16, u16)> {
+        let (i, a) = be_u16(i)?;
+        let (i, b) = be_u16(i)?;
+        Ok((i, (a, b)))
+    };
+    let (i, constant) = match tag {
+        1 => {
+            let (i, len) = be_u16(i)?;
+            let (i, bytes) = take(len as usize)(i)?;
+            (i, Constant::Utf8(decode_modified_utf8(bytes)))
+        }
+        3 => be_i32(i).map(|(i, v)| (i, Constant::Integer(v)))?,
+        4 => be_f32(i).map(|(i, v)| (i, Constant::Float(v)))?,
+        5 => be_i64(i).map(|(i, v)| (i, Constant::Long(v)))?,
+        6 => be_f64(i).map(|(i, v)| (i, Constant::Double(v)))?,
+        7 => be_u16(i).map(|(i, v)| (i, Constant::Class(v)))?,
+        8 => be_u16(i).map(|(i, v)| (i, Constant::Str(v)))?,
+        9 => two(i).map(|(i, (a, b))| (i, Constant::FieldRef(a, b)))?,
+        10 => two(i).map(|(i, (a, b))| (i, Constant::MethodRef(a, b)))?,
+        11 => two(i).map(|(i, (a, b))| (i, Constant::InterfaceMethodRef(a, b)))?,
+        12 => two(i).map(|(i, (a, b))| (i, Constant::NameAndType(a, b)))?,
+        15 => {
+            let (i, kind) = be_u8(i)?;
+            let (i, idx) = be_u16(i)?;
+            (i, Constant::MethodHandle(kind, idx))
+        }
+        16 => be_u16(i).map(|(i, v)| (i, Constant::MethodType(v)))?,
+        17 => two(i).map(|(i, (a, b))| (i, Constant::Dynamic(a, b)))?,
+        18 => two(i).map(|(i, (a, b))| (i, Constant::InvokeDynamic(a, b)))?,
+        19 => be_u16(i).map(|(i, v)| (i, Constant::Module(v)))?,
+        20 => be_u16(i).map(|(i, v)| (i, Constant::Package(v)))?,
+        _ => return fail(input, ErrorKind::Tag),
+    };
+    //long and double take two slots
+    Ok((i, (constant, tag == 5 || tag == 6)))
+}
+
+pub fn parse_constant_pool(input: &[u8]) -> ParseResult<'_, ConstantPool> {
+    let (mut i, count) = be_u16(input)?;
+    let mut entries = Vec::with_capacity(count as usize);
+    entries.push(Constant::Unusable);
+    while entries.len() < count as usize {
+        let (rest, (constant, wide)) = parse_constant(i)?;
+        i = rest;
+        entries.push(constant);
+        if wide {
+            entries.push(Constant::Unusable);
+        }
+    }
+    Ok((i, ConstantPool { entries }))
+}
+
+fn parse_u16_list(input: &[u8]) -> ParseResult<'_, Vec<u16>> {
+    let (mut i, count) = be_u16(input)?;
+    let mut list = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let (rest, val) = be_u16(i)?;
+        i = rest;
+        list.push(val);
+    }
+    Ok((i, list))
+}
+
+fn parse_code<'a>(input: &'a [u8], pool: &ConstantPool) -> ParseResult<'a, Code> {
+    let (i, max_stack) = be_u16(input)?;
+    let (i, max_locals) = be_u16(i)?;
+    let (i, code_len) = be_u32(i)?;
+    let (i, code) = take(code_len as usize)(i)?;
+    let (mut i, table_len) = be_u16(i)?;
+    let mut exception_table = Vec::with_capacity(table_len as usize);
+    for _ in 0..table_len {
+        let (rest, start_pc) = be_u16(i)?;
+        let (rest, end_pc) = be_u16(rest)?;
+        let (rest, handler_pc) = be_u16(rest)?;
+        let (rest, catch_type) = be_u16(rest)?;
+        i = rest;
+        exception_table.push(ExceptionEntry {
+            start_pc,
+            end_pc,
+            handler_pc,
+            catch_type,
+        });
+    }
+    let (i, attributes) = parse_attributes(i, pool)?;
+    Ok((
+        i,
+        Code {
+            max_stack,
+            max_locals,
+            code: code.to_vec(),
+            exception_table,
+            attributes,
+        },
+    ))
+}
+
+fn parse_attribute<'a>(input: &'a [u8], pool: &ConstantPool) -> ParseResult<'a, Attribute> {
+    let (i, name_idx) = be_u16(input)?;
+    let (i, len) = be_u32(i)?;
+    let (i, info) = take(len as usize)(i)?;
+    let name = match pool.utf8(name_idx) {
+        Some(name) => name,
+        None => return fail(input, ErrorKind::Verify),
+    };
+    let attr = match name {
+        "Code" => Attribute::Code(parse_code(info, pool)?.1),
+        "LineNumberTable" => {
+            let (mut rest, count) = be_u16(info)?;
+            let mut lines = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let (r, start_pc) = be_u16(rest)?;
+                let (r, line) = be_u16(r)?;
+                rest = r;
+                lines.push(LineNumber { start_pc, line });
+            }
+            Attribute::LineNumberTable(lines)
+        }
+        "SourceFile" => Attribute::SourceFile(be_u16(info)?.1),
+        "Signature" => Attribute::Signature(be_u16(info)?.1),
+        "ConstantValue" => Attribute::ConstantValue(be_u16(info)?.1),
+        "Exceptions" => Attribute::Exceptions(parse_u16_list(info)?.1),
+        "InnerClasses" => {
+            let (mut rest, count) = be_u16(info)?;
+            let mut list = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let (r, inner_class) = be_u16(rest)?;
+                let (r, outer_class) = be_u16(r)?;
+                let (r, inner_name) = be_u16(r)?;
+                let (r, access_flags) = be_u16(r)?;
+                rest = r;
+                list.push(InnerClass {
+                    inner_class,
+                    outer_class,
+                    inner_name,
+                    access_flags,
+                });
+            }
+            Attribute::InnerClasses(list)
+        }
+        _ => Attribute::Other(name.to_string(), info.to_vec()),
+    };
+    Ok((i, attr))
+}
+
+fn parse_attributes<'a>(input: &'a [u8], pool: &ConstantPool) -> ParseResult<'a, Vec<Attribute>> {
+    let (mut i, count) = be_u16(input)?;
+    let mut attributes = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let (rest, attr) = parse_attribute(i, pool)?;
+        i = rest;
+        attributes.push(attr);
+    }
+    Ok((i, attributes))
+}
+
+fn parse_members<'a>(input: &'a [u8], pool: &ConstantPool) -> ParseResult<'a, Vec<MemberInfo>> {
+    let (mut i, count) = be_u16(input)?;
+    let mut members = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let (rest, access_flags) = be_u16(i)?;
+        let (rest, name_index) = be_u16(rest)?;
+        let (rest, descriptor_index) = be_u16(rest)?;
+        let (rest, attributes) = parse_attributes(rest, pool)?;
+        i = rest;
+        members.push(MemberInfo {
+            access_flags,
+            name_index,
+            descriptor_index,
+            attributes,
+        });
+    }
+    Ok((i, members))
+}
+
+pub fn parse_class(input: &[u8]) -> ParseResult<'_, ClassFile> {
+    let (i, magic) = be_u32(input)?;
+    if magic != CLASS_MAGIC {
+        return fail(input, ErrorKind::Tag);
+    }
+    let (i, minor_version) = be_u16(i)?;
+    let (i, major_version) = be_u16(i)?;
+    let (i, constant_pool) = parse_constant_pool(i)?;
+    let (i, access_flags) = be_u16(i)?;
+    let (i, this_class) = be_u16(i)?;
+    let (i, super_class) = be_u16(i)?;
+    let (i, interfaces) = parse_u16_list(i)?;
+    let (i, fields) = parse_members(i, &constant_pool)?;
+    let (i, methods) = parse_members(i, &constant_pool)?;
+    let (i, attributes) = parse_attributes(i, &constant_pool)?;
+    Ok((
+        i,
+        ClassFile {
+            minor_version,
+            major_version,
+            constant_pool,
+            access_flags,
+            this_class,
+            super_class,
+            interfaces,
+            fields,
+            methods,
+            attributes,
+        },
+    ))
+}
+
+/// single field type of a descriptor to its java source form, returns the rest
+fn field_type(desc: &str) -> Option<(String, &str)> {
+    let first = desc.chars().next()?;
+    //the descriptor is modified utf-8 from the pool, it may start with any char
+    let rest = &desc[first.len_utf8()..];
+    let name = match first {
+        'B' => "byte",
+        'C' => "char",
+        'D' => "double",
+        'F' => "float",
+        'I' => "int",
+        'J' => "long",
+        'S' => "short",
+        'Z' => "boolean",
+        'V' => "void",
+        'L' => {
+            let end = rest.find(';')?;
+            return Some((rest[..end].replace('/', "."), &rest[end + 1..]));
+        }
+        '[' => {
+            let (component, rest) = field_type(rest)?;
+            return Some((format!("{}[]", component), rest));
+        }
+        _ => return None,
+    };
+    Some((name.to_string(), rest))
+}
+
+/// Ljava/lang/String; to java.lang.String, [I to int[]
+pub fn descriptor_to_java(desc: &str) -> String {
+    field_type(desc)
+        .map(|(name, _)| name)
+        .unwrap_or_else(|| desc.to_string())
+}
+
+/// (ILjava/lang/String;)V to ([int, java.lang.String], void)
+pub fn method_descriptor(desc: &str) -> Option<(Vec<String>, String)> {
+    let mut rest = desc.strip_prefix('(')?;
+    let mut params = vec![];
+    while !rest.starts_with(')') {
+        let (param, r) = field_type(rest)?;
+        params.push(param);
+        rest = r;
+    }
+    let (ret, _) = field_type(&rest[1..])?;
+    Some((params, ret))
+}
+
+#[test]
+fn test_parse_sample_class() {
+    let buf = std::fs::read("testdata/Sample.class").unwrap();
+    let (rest, class) = parse_class(&buf).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(61, class.major_version);
+    assert_eq!("17", class.java_version());
+    assert_eq!("Sample", class.class_name());
+    assert_eq!(Some("java/lang/Object"), class.super_class_name());
+    assert_eq!(
+        vec!["java/io/Serializable", "java/lang/Cloneable"],
+        class.interface_names()
+    );
+    assert_eq!(Some("Sample.java"), class.source_file());
+    assert_eq!(
+        Some("<T::Ljava/lang/Comparable<TT;>;>Ljava/lang/Object;Ljava/io/Serializable;Ljava/lang/Cloneable;"),
+        class.signature()
+    );
+    assert!(class.has_flag(ACC_PUBLIC));
+    assert_eq!(
+        Some(&Constant::Unusable),
+        class.constant_pool.entries.iter().skip(1).find(|c| **c == Constant::Unusable)
+    );
+
+    let pool = &class.constant_pool;
+    let big = class.field("BIG").unwrap();
+    assert_eq!("J", big.descriptor(pool));
+    assert!(big.has_flag(ACC_STATIC | ACC_FINAL));
+    match big.attributes[0] {
+        Attribute::ConstantValue(idx) => assert_eq!(Some(&Constant::Long(1234567890123)), pool.get(idx)),
+        ref other => panic!("not a constant value {:?}", other),
+    }
+    assert!(class.field("count").unwrap().has_flag(ACC_TRANSIENT));
+    assert_eq!(
+        Some("Ljava/util/List<TT;>;"),
+        class.field("items").unwrap().signature(pool)
+    );
+
+    let classify = class.method("classify", "(I)I").unwrap();
+    let code = classify.code().unwrap();
+    assert!(!code.code.is_empty());
+    assert_eq!(2, code.max_locals);
+    assert!(!code.line_numbers().is_empty());
+    let describe = class.method("describe", "(Ljava/lang/Object;)Ljava/lang/String;").unwrap();
+    assert!(!describe.code().unwrap().exception_table.is_empty());
+    assert!(class.method("ping", "()V").unwrap().code().is_none());
+}
+
+#[test]
+fn test_descriptor_to_java() {
+    assert_eq!("java.lang.String", descriptor_to_java("Ljava/lang/String;"));
+    assert_eq!("int[][]", descriptor_to_java("[[I"));
+    assert_eq!(
+        Some((vec![String::from("int[]"), String::from("long")], String::from("long"))),
+        method_descriptor("([IJ)J")
+    );
+    assert_eq!(None, method_descriptor("(Ljava/lang/String"));
+    assert_eq!("é", descriptor_to_java("é"));
+    assert_eq!(None, method_descriptor("(é)V"));
+}
@@ -1,32 +1,635 @@
 extern crate rustyline;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use redis::{RedisError, Value};
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 
-fn test_readline() {
-    let mut rl = Editor::<()>::new();
-    if rl.load_history("history.txt").is_err() {
-        println!("No previous history.");
+use crate::completion::{self, ConsoleHelper};
+use crate::profile::resolve_target;
+use crate::redis_conn::ConnectionHolder;
+
+/// where per user state such as history lives, ~/.rust_a
+pub fn data_dir() -> PathBuf {
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .unwrap_or_else(|| ".".into());
+    PathBuf::from(home).join(".rust_a")
+}
+
+/// every profile keeps its own history file so commands typed against
+/// one server do not show up when browsing another
+pub fn history_path(profile: &str) -> PathBuf {
+    let name: String = profile
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    data_dir().join("history").join(format!("{}.txt", name))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SplitError {
+    //a quoted argument is still open at the end of the input, more lines may close it
+    UnclosedQuote(char),
+    //a closing quote has to be followed by a space, like "a"b is refused
+    TextAfterQuote(usize),
+}
+
+impl fmt::Display for SplitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SplitError::UnclosedQuote(quote) => write!(f, "unbalanced {} quote", quote),
+            SplitError::TextAfterQuote(pos) => {
+                write!(f, "closing quote must be followed by a space at {}", pos)
+            }
+        }
     }
+}
+
+fn hex_digit(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+/// split a command line into binary safe args with the rules of redis-cli (sdssplitargs):
+/// "double quotes" take \xNN, \n, \r, \t, \b, \a escapes, 'single quotes' only \',
+/// and quotes may start in the middle of an arg, foo"bar baz" is one arg
+pub fn split_args(line: &[u8]) -> Result<Vec<Vec<u8>>, SplitError> {
+    let mut args = vec![];
+    let mut pos = 0;
     loop {
-        let readline = rl.readline("_>");
-        match readline {
-            Ok(line) => {
-                rl.add_history_entry(line.as_str());
-                println!("Line: {}", line);
+        while pos < line.len() && line[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if pos == line.len() {
+            return Ok(args);
+        }
+        let mut arg = vec![];
+        let mut quote = None;
+        loop {
+            let b = match line.get(pos) {
+                Some(b) => *b,
+                None => match quote {
+                    Some(q) => return Err(SplitError::UnclosedQuote(q as char)),
+                    None => break,
+                },
+            };
+            match quote {
+                Some(b'"') if b == b'\\' && line.len() > pos + 3 && line[pos + 1] == b'x' => {
+                    match (hex_digit(line[pos + 2]), hex_digit(line[pos + 3])) {
+                        (Some(hi), Some(lo)) => {
+                            arg.push(hi * 16 + lo);
+                            pos += 3;
+                        }
+                        //a bad \x escape is a literal x like redis-cli
+                        _ => {
+                            arg.push(b'x');
+                            pos += 1;
+                        }
+                    }
+                }
+                Some(b'"') if b == b'\\' && pos + 1 < line.len() => {
+                    pos += 1;
+                    arg.push(match line[pos] {
+                        b'n' => b'\n',
+                        b'r' => b'\r',
+                        b't' => b'\t',
+                        b'b' => 0x08,
+                        b'a' => 0x07,
+                        other => other,
+                    });
+                }
+                Some(b'\'') if b == b'\\' && line.get(pos + 1) == Some(&b'\'') => {
+                    pos += 1;
+                    arg.push(b'\'');
+                }
+                Some(q) if b == q => {
+                    match line.get(pos + 1) {
+                        Some(next) if !next.is_ascii_whitespace() => {
+                            return Err(SplitError::TextAfterQuote(pos + 1))
+                        }
+                        _ => {}
+                    }
+                    pos += 1;
+                    break;
+                }
+                Some(_) => arg.push(b),
+                None if b.is_ascii_whitespace() => break,
+                None if b == b'"' || b == b'\'' => quote = Some(b),
+                None => arg.push(b),
+            }
+            pos += 1;
+        }
+        args.push(arg);
+    }
+}
+
+//config parameters holding passwords, the ones redis-cli keeps out of its history
+const SECRET_CONFIGS: [&str; 5] = [
+    "requirepass",
+    "masterauth",
+    "masteruser",
+    "tls-key-file-pass",
+    "tls-client-key-file-pass",
+];
+
+/// index of the first argument that may carry a password, None for commands without any
+pub(crate) fn secret_from<T: AsRef<[u8]>>(args: &[T]) -> Option<usize> {
+    let is = |arg: &T, name: &str| arg.as_ref().eq_ignore_ascii_case(name.as_bytes());
+    let auth = |from: usize| {
+        let pos = args[from..]
+            .iter()
+            .position(|a| is(a, "auth") || is(a, "auth2"))?;
+        Some(from + pos + 1)
+    };
+    match args {
+        [cmd, ..] if is(cmd, "auth") => Some(1),
+        [cmd, ..] if is(cmd, "hello") => auth(1),
+        [cmd, ..] if is(cmd, "migrate") => auth(1),
+        [cmd, sub, ..] if is(cmd, "acl") && is(sub, "setuser") => Some(3),
+        [cmd, sub, params @ ..] if is(cmd, "config") && is(sub, "set") => params
+            .iter()
+            .any(|p| SECRET_CONFIGS.iter().any(|name| is(p, name)))
+            .then_some(2),
+        _ => None,
+    }
+}
+
+/// split a script into commands, one per line unless a quote spans lines,
+/// blank lines and lines starting with # are skipped. errors carry the 1 based
+/// line the failing command starts on
+pub fn split_script(text: &[u8]) -> Result<Vec<Vec<Vec<u8>>>, (usize, SplitError)> {
+    let mut cmds = vec![];
+    let mut pending: Vec<u8> = vec![];
+    let mut start = 0;
+    for (no, line) in text.split(|b| *b == b'\n').enumerate() {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if pending.is_empty() {
+            if line.trim_ascii_start().starts_with(b"#") {
+                continue;
+            }
+            start = no + 1;
+        } else {
+            pending.push(b'\n');
+        }
+        pending.extend_from_slice(line);
+        match split_args(&pending) {
+            Err(SplitError::UnclosedQuote(_)) => continue,
+            Err(err) => return Err((start, err)),
+            Ok(args) if args.is_empty() => {}
+            Ok(args) => cmds.push(args),
+        }
+        pending.clear();
+    }
+    match split_args(&pending) {
+        Err(err) => Err((start, err)),
+        Ok(_) => Ok(cmds),
+    }
+}
+
+//sdscatrepr of redis-cli, printable ascii kept and the rest escaped
+pub(crate) fn quote_bytes(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len() + 2);
+    out.push('"');
+    for b in data {
+        match b {
+            b'\\' => out.push_str("\\\\"),
+            b'"' => out.push_str("\\\""),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            0x07 => out.push_str("\\a"),
+            0x08 => out.push_str("\\b"),
+            0x20..=0x7e => out.push(*b as char),
+            _ => out.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn format_nested(val: &Value, prefix: &str, out: &mut String) {
+    match val {
+        Value::Nil => out.push_str("(nil)\n"),
+        Value::Int(num) => out.push_str(&format!("(integer) {}\n", num)),
+        Value::Data(data) => {
+            out.push_str(&quote_bytes(data));
+            out.push('\n');
+        }
+        Value::Status(status) => {
+            out.push_str(status);
+            out.push('\n');
+        }
+        Value::Okay => out.push_str("OK\n"),
+        Value::Bulk(items) if items.is_empty() => out.push_str("(empty array)\n"),
+        Value::Bulk(items) => {
+            let width = items.len().to_string().len();
+            //nested items line up under the first one
+            let child_prefix = format!("{}{}", prefix, " ".repeat(width + 2));
+            for (i, item) in items.iter().enumerate() {
+                //the first item goes right after the index the parent printed
+                if i > 0 {
+                    out.push_str(prefix);
+                }
+                out.push_str(&format!("{:>width$}) ", i + 1, width = width));
+                format_nested(item, &child_prefix, out);
+            }
+        }
+    }
+}
+
+/// render a reply the way redis-cli does on a terminal
+pub fn format_reply(val: &Value) -> String {
+    let mut out = String::new();
+    format_nested(val, "", &mut out);
+    out
+}
+
+pub fn format_error(err: &RedisError) -> String {
+    match (err.code(), err.detail()) {
+        (Some(code), Some(detail)) => format!("(error) {} {}", code, detail),
+        _ => format!("(error) {}", err),
+    }
+}
+
+/// run every command of a script and print the replies, stops at the first
+/// line which does not split
+pub fn run_script(holder: &mut ConnectionHolder, text: &[u8]) -> Result<(), String> {
+    let cmds = split_script(text).map_err(|(line, err)| format!("line {}: {}", line, err))?;
+    for args in cmds {
+        match holder.execute(&args) {
+            Ok(val) => print!("{}", format_reply(&val)),
+            Err(err) => println!("{}", format_error(&err)),
+        }
+    }
+    Ok(())
+}
+
+//completion scans keys on a connection of its own
+fn side_connection(info: Option<&redis::ConnectionInfo>) -> Option<redis::Connection> {
+    redis::Client::open(info?.clone()).ok()?.get_connection().ok()
+}
+
+//connections opened twice share a profile, prod#3 keeps the history of prod
+fn profile_of(conn_name: &str) -> &str {
+    conn_name.split('#').next().unwrap_or(conn_name)
+}
+
+const META_HELP: &str = ":conns                    list connections, * marks the current one
+:open <uri|profile> [name] connect and switch to it
+:switch <id|name>         send commands to another connection
+:close [id|name]          close a connection, the current one by default
+:closeall                 close every connection
+:reconnect [id|name]      reopen a connection with its address and db
+:safe [on|off]            show or switch safe mode of the current connection
+:force <command...>       run one command safe mode would refuse";
+
+pub struct Repl {
+    editor: Editor<ConsoleHelper>,
+    holder: ConnectionHolder,
+    //profile whose history the editor holds
+    profile: String,
+    //prompt color of each connection id, from the profile color tag
+    colors: HashMap<u32, &'static str>,
+    //lines typed so far of a command whose quote is still open
+    pending: String,
+}
+
+impl Repl {
+    pub fn new(mut holder: ConnectionHolder) -> Repl {
+        //COMMAND DOCS came with redis 7, older servers get the built-in table
+        let docs = holder
+            .execute(&["COMMAND", "DOCS"])
+            .map(|reply| completion::parse_command_docs(&reply))
+            .ok()
+            .filter(|docs| !docs.is_empty())
+            .unwrap_or_else(completion::builtin_docs);
+        let helper = ConsoleHelper::new(docs, side_connection(holder.cur_info()));
+        let mut editor = Editor::<ConsoleHelper>::new();
+        editor.set_helper(Some(helper));
+        let profile = holder
+            .current()
+            .map_or("default", |c| profile_of(&c.name))
+            .to_string();
+        //no history yet for a fresh profile
+        let _ = editor.load_history(&history_path(&profile));
+        Repl {
+            editor,
+            holder,
+            profile,
+            colors: HashMap::new(),
+            pending: String::new(),
+        }
+    }
+
+    /// name[db]> of the current connection, the db is left out when it is 0.
+    /// connections opened by uri are named after the address like redis-cli
+    pub fn prompt(&self) -> String {
+        if !self.pending.is_empty() {
+            return "...> ".to_string();
+        }
+        match self.holder.current() {
+            Some(conn) if conn.db() == 0 => format!("{}> ", conn.name),
+            Some(conn) => format!("{}[{}]> ", conn.name, conn.db()),
+            None => "not connected> ".to_string(),
+        }
+    }
+
+    pub fn save_history(&mut self) {
+        let path = history_path(&self.profile);
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let Err(err) = self.editor.save_history(&path) {
+            println!("save history to {} failed: {}", path.display(), err);
+        }
+    }
+
+    /// draw the prompt of a connection in a profile's color, an ansi sgr code like 31
+    pub fn set_color(&mut self, id: u32, color: Option<&'static str>) {
+        match color {
+            Some(color) => self.colors.insert(id, color),
+            None => self.colors.remove(&id),
+        };
+        self.connection_changed();
+    }
+
+    //the current connection or its db changed, bring history, completion and color along
+    fn connection_changed(&mut self) {
+        let profile = self
+            .holder
+            .current()
+            .map_or("default", |c| profile_of(&c.name))
+            .to_string();
+        if profile != self.profile {
+            self.save_history();
+            self.editor.clear_history();
+            let _ = self.editor.load_history(&history_path(&profile));
+            self.profile = profile;
+        }
+        let color = self
+            .holder
+            .current()
+            .and_then(|c| self.colors.get(&c.id).copied());
+        let conn = side_connection(self.holder.cur_info());
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.set_prompt_color(color);
+            helper.set_connection(conn);
+        }
+    }
+
+    fn open(&mut self, target: &str, name: Option<&str>) -> Result<u32, String> {
+        let target = resolve_target(target).map_err(|err| err.to_string())?;
+        let name = name.unwrap_or(&target.name);
+        let id = target
+            .open(&mut self.holder, name)
+            .map_err(|err| format_error(&err))?;
+        if let Some(color) = target.color {
+            self.colors.insert(id, color);
+        }
+        Ok(id)
+    }
+
+    /// :conns, :open, :switch and friends, connection management of the console
+    pub fn meta_command(&mut self, args: &[String]) -> String {
+        let target = args.get(1).map(String::as_str);
+        let current = self.holder.current().map(|c| c.id.to_string());
+        let target_or_current = target.or(current.as_deref()).unwrap_or_default();
+        let done = match (args[0].as_str(), target) {
+            (":conns", None) => {
+                let cur = self.holder.current().map(|c| c.id);
+                let lines: Vec<String> = self
+                    .holder
+                    .list()
+                    .iter()
+                    .map(|c| {
+                        let mark = if Some(c.id) == cur { '*' } else { ' ' };
+                        format!("{} {:>3} {} {} db {}", mark, c.id, c.name, c.info().addr, c.db())
+                    })
+                    .collect();
+                return lines.join("\n");
             }
-            Err(ReadlineError::Interrupted) => {
-                println!("CTRL-C");
-                break;
+            (":open", Some(target)) => self
+                .open(target, args.get(2).map(String::as_str))
+                .map(|id| format!("opened {}", id)),
+            (":switch", Some(target)) => self
+                .holder
+                .switch(target)
+                .map(|id| format!("switched to {}", id))
+                .map_err(|err| format_error(&err)),
+            (":close", _) => self
+                .holder
+                .close(target_or_current)
+                .map(|id| {
+                    self.colors.remove(&id);
+                    format!("closed {}", id)
+                })
+                .map_err(|err| format_error(&err)),
+            (":closeall", None) => {
+                self.holder.close_all();
+                self.colors.clear();
+                Ok("closed all".to_string())
             }
-            Err(ReadlineError::Eof) => {
-                println!("CTRL-D");
-                break;
+            (":reconnect", _) => self
+                .holder
+                .reconnect(target_or_current)
+                .map(|id| format!("reconnected {}", id))
+                .map_err(|err| format_error(&err)),
+            (":safe", None) => match self.holder.current() {
+                Some(c) if c.safe_mode().is_some() => Ok("safe mode is on".to_string()),
+                Some(_) => Ok("safe mode is off".to_string()),
+                None => Err("not connected".to_string()),
+            },
+            (":safe", Some(state @ "on")) | (":safe", Some(state @ "off")) => {
+                match self.holder.current().map(|c| c.id) {
+                    Some(id) => self
+                        .holder
+                        .set_safe_mode(id, state == "on")
+                        .map(|_| format!("safe mode is {}", state))
+                        .map_err(|err| format_error(&err)),
+                    None => Err("not connected".to_string()),
+                }
+            }
+            _ => Err(META_HELP.to_string()),
+        };
+        self.connection_changed();
+        done.unwrap_or_else(|err| err)
+    }
+
+    /// feed one typed line to the server and print the reply, false once the user quits
+    pub fn handle_line(&mut self, line: &str) -> bool {
+        let mut text = std::mem::take(&mut self.pending);
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(line);
+        let args = match split_args(text.as_bytes()) {
+            Ok(args) => args,
+            Err(SplitError::UnclosedQuote(_)) => {
+                //keep reading lines until the quote is closed
+                self.pending = text;
+                return true;
             }
             Err(err) => {
-                println!("Error: {:?}", err);
-                break;
+                self.editor.add_history_entry(text.as_str());
+                println!("Invalid argument(s): {}", err);
+                return true;
+            }
+        };
+        if args.is_empty() {
+            return true;
+        }
+        //:force runs the command after it, secrets stay out of the history either way
+        let cmd = match args[0].as_slice() {
+            b":force" => &args[1..],
+            _ => &args[..],
+        };
+        if secret_from(cmd).is_none() {
+            self.editor.add_history_entry(text.as_str());
+        }
+        if args.len() == 1
+            && (args[0].eq_ignore_ascii_case(b"quit") || args[0].eq_ignore_ascii_case(b"exit"))
+        {
+            return false;
+        }
+        //no redis command starts with a colon, :force keeps its arguments as bytes
+        let forced = args[0].as_slice() == b":force" && args.len() > 1;
+        if args[0].starts_with(b":") && !forced {
+            let args: Vec<String> = args
+                .iter()
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect();
+            println!("{}", self.meta_command(&args));
+            return true;
+        }
+        let db = self.holder.cur_info().map(|info| info.db);
+        let replied = if forced {
+            self.holder.execute_forced(&args[1..])
+        } else {
+            self.holder.execute(&args)
+        };
+        match replied {
+            Ok(val) => print!("{}", format_reply(&val)),
+            Err(err) => println!("{}", format_error(&err)),
+        }
+        if db != self.holder.cur_info().map(|info| info.db) {
+            self.connection_changed();
+        }
+        true
+    }
+
+    pub fn run(&mut self) {
+        loop {
+            let prompt = self.prompt();
+            match self.editor.readline(&prompt) {
+                Ok(line) => {
+                    if !self.handle_line(&line) {
+                        break;
+                    }
+                }
+                //ctrl-c drops an unfinished multi-line command first
+                Err(ReadlineError::Interrupted) if !self.pending.is_empty() => {
+                    self.pending.clear()
+                }
+                Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
+                Err(err) => {
+                    println!("Error: {:?}", err);
+                    break;
+                }
             }
         }
+        self.save_history();
     }
-    rl.save_history("history.txt").unwrap();
+}
+
+#[test]
+fn test_format_reply_like_redis_cli() {
+    assert_eq!("(nil)\n", format_reply(&Value::Nil));
+    assert_eq!("(integer) -3\n", format_reply(&Value::Int(-3)));
+    assert_eq!("OK\n", format_reply(&Value::Okay));
+    assert_eq!("PONG\n", format_reply(&Value::Status("PONG".into())));
+    assert_eq!("(empty array)\n", format_reply(&Value::Bulk(vec![])));
+    assert_eq!(
+        "\"a\\\"b\\n\\xe5\\xbc\\xa0\"\n",
+        format_reply(&Value::Data("a\"b\n张".as_bytes().to_vec()))
+    );
+
+    let scan = Value::Bulk(vec![
+        Value::Data(b"17".to_vec()),
+        Value::Bulk(vec![Value::Data(b"k1".to_vec()), Value::Data(b"k2".to_vec())]),
+        Value::Nil,
+    ]);
+    assert_eq!(
+        "1) \"17\"\n2) 1) \"k1\"\n   2) \"k2\"\n3) (nil)\n",
+        format_reply(&scan)
+    );
+
+    let long = Value::Bulk((0..10).map(Value::Int).collect());
+    let text = format_reply(&long);
+    assert!(text.starts_with(" 1) (integer) 0\n 2) "));
+    assert!(text.ends_with("10) (integer) 9\n"));
+}
+
+#[test]
+fn test_format_error() {
+    let err: RedisError = (
+        redis::ErrorKind::ResponseError,
+        "An error was signalled by the server",
+        "unknown command `foo`".to_string(),
+    )
+        .into();
+    assert_eq!("(error) ERR unknown command `foo`", format_error(&err));
+    assert!(history_path("prod 127.0.0.1:6379").ends_with("history/prod_127_0_0_1_6379.txt"));
+}
+
+#[test]
+fn test_split_args() {
+    let split = |line: &str| split_args(line.as_bytes());
+    assert_eq!(
+        Ok(vec![b"set".to_vec(), b"k".to_vec(), b"v".to_vec()]),
+        split("  set k\tv  ")
+    );
+    assert_eq!(
+        Ok(vec![b"hset".to_vec(), b"h".to_vec(), "啧啧啧 x".as_bytes().to_vec()]),
+        split("hset h \"啧啧啧 x\"")
+    );
+    assert_eq!(
+        Ok(vec![vec![0xff, 0x00, b'\n', b'"'], b"x1".to_vec()]),
+        split(r#""\xff\x00\n\"" "\x1""#)
+    );
+    assert_eq!(
+        Ok(vec![br#"it's \n"#.to_vec(), b"foobar baz".to_vec(), vec![]]),
+        split(r#"'it\'s \n' foo"bar baz" """#)
+    );
+    assert_eq!(Err(SplitError::UnclosedQuote('"')), split("set k \"v"));
+    assert_eq!(Err(SplitError::UnclosedQuote('\'')), split("set k 'v\\'"));
+    assert_eq!(Err(SplitError::TextAfterQuote(9)), split("set k \"v\"w"));
+}
+
+#[test]
+fn test_secret_args() {
+    let secret = |line: &str| secret_from(&split_args(line.as_bytes()).unwrap());
+    assert_eq!(Some(1), secret("AUTH hunter2"));
+    assert_eq!(Some(3), secret("hello 3 AUTH default hunter2"));
+    assert_eq!(None, secret("hello 3"));
+    assert_eq!(Some(3), secret("acl SetUser bob on >hunter2"));
+    assert_eq!(Some(2), secret("config set maxmemory 1gb requirepass hunter2"));
+    assert_eq!(None, secret("config set maxmemory 1gb"));
+    assert_eq!(Some(8), secret("migrate h 6379 \"\" 0 5000 KEYS AUTH hunter2 KEYS a"));
+    assert_eq!(None, secret("get auth"));
+}
+
+#[test]
+fn test_split_script() {
+    let script = b"# seed data\r\nset a 1\r\n\nset b \"line1\nline2\"\nget b\n";
+    let cmds = split_script(script).unwrap();
+    assert_eq!(3, cmds.len());
+    assert_eq!(b"line1\nline2".to_vec(), cmds[1][2]);
+    assert_eq!(vec![b"get".to_vec(), b"b".to_vec()], cmds[2]);
+    assert_eq!(
+        Err((2, SplitError::UnclosedQuote('\''))),
+        split_script(b"ping\nset a 'x\n\n")
+    );
 }
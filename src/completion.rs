@@ -0,0 +1,645 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+
+use redis::Value;
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+use crate::cli::split_args;
+
+//SCAN calls made per tab press, keeps completion responsive on big dbs
+const SCAN_ROUNDS: usize = 8;
+const SCAN_COUNT: usize = 500;
+
+/// fallback when the server is older than redis 7 and has no COMMAND DOCS,
+/// subcommands are written as "CONFIG GET"
+const BUILTIN_COMMANDS: &[(&str, &str)] = &[
+    ("APPEND", "key value"),
+    ("AUTH", "[username] password"),
+    ("BITCOUNT", "key [start end [BYTE|BIT]]"),
+    ("BLPOP", "key [key ...] timeout"),
+    ("BRPOP", "key [key ...] timeout"),
+    ("CLIENT GETNAME", ""),
+    ("CLIENT KILL", "ip:port"),
+    ("CLIENT LIST", "[TYPE NORMAL|MASTER|REPLICA|PUBSUB]"),
+    ("CLIENT SETNAME", "connection-name"),
+    ("COMMAND COUNT", ""),
+    ("COMMAND DOCS", "[command-name [command-name ...]]"),
+    ("COMMAND INFO", "[command-name [command-name ...]]"),
+    ("CONFIG GET", "parameter [parameter ...]"),
+    ("CONFIG RESETSTAT", ""),
+    ("CONFIG REWRITE", ""),
+    ("CONFIG SET", "parameter value [parameter value ...]"),
+    ("DBSIZE", ""),
+    ("DECR", "key"),
+    ("DECRBY", "key decrement"),
+    ("DEL", "key [key ...]"),
+    ("DUMP", "key"),
+    ("ECHO", "message"),
+    ("EVAL", "script numkeys [key [key ...]] [arg [arg ...]]"),
+    ("EXISTS", "key [key ...]"),
+    ("EXPIRE", "key seconds [NX|XX|GT|LT]"),
+    ("EXPIREAT", "key unix-time-seconds [NX|XX|GT|LT]"),
+    ("FLUSHALL", "[ASYNC|SYNC]"),
+    ("FLUSHDB", "[ASYNC|SYNC]"),
+    ("GET", "key"),
+    ("GETDEL", "key"),
+    ("GETRANGE", "key start end"),
+    ("HDEL", "key field [field ...]"),
+    ("HEXISTS", "key field"),
+    ("HGET", "key field"),
+    ("HGETALL", "key"),
+    ("HINCRBY", "key field increment"),
+    ("HKEYS", "key"),
+    ("HLEN", "key"),
+    ("HMGET", "key field [field ...]"),
+    ("HSCAN", "key cursor [MATCH pattern] [COUNT count]"),
+    ("HSET", "key field value [field value ...]"),
+    ("HVALS", "key"),
+    ("INCR", "key"),
+    ("INCRBY", "key increment"),
+    ("INFO", "[section [section ...]]"),
+    ("KEYS", "pattern"),
+    ("LINDEX", "key index"),
+    ("LLEN", "key"),
+    ("LPOP", "key [count]"),
+    ("LPUSH", "key element [element ...]"),
+    ("LRANGE", "key start stop"),
+    ("LREM", "key count element"),
+    ("LSET", "key index element"),
+    ("LTRIM", "key start stop"),
+    ("MEMORY USAGE", "key [SAMPLES count]"),
+    ("MGET", "key [key ...]"),
+    ("MONITOR", ""),
+    ("MSET", "key value [key value ...]"),
+    ("OBJECT ENCODING", "key"),
+    ("OBJECT FREQ", "key"),
+    ("OBJECT IDLETIME", "key"),
+    ("PERSIST", "key"),
+    ("PEXPIRE", "key milliseconds [NX|XX|GT|LT]"),
+    ("PING", "[message]"),
+    ("PSUBSCRIBE", "pattern [pattern ...]"),
+    ("PTTL", "key"),
+    ("PUBLISH", "channel message"),
+    ("RENAME", "key newkey"),
+    ("RESTORE", "key ttl serialized-value [REPLACE] [ABSTTL] [IDLETIME seconds] [FREQ frequency]"),
+    ("RPOP", "key [count]"),
+    ("RPUSH", "key element [element ...]"),
+    ("SADD", "key member [member ...]"),
+    ("SCAN", "cursor [MATCH pattern] [COUNT count] [TYPE type]"),
+    ("SCARD", "key"),
+    ("SELECT", "index"),
+    ("SET", "key value [NX|XX] [GET] [EX seconds|PX milliseconds|EXAT unix-time-seconds|PXAT unix-time-milliseconds|KEEPTTL]"),
+    ("SETEX", "key seconds value"),
+    ("SETNX", "key value"),
+    ("SISMEMBER", "key member"),
+    ("SLOWLOG GET", "[count]"),
+    ("SLOWLOG LEN", ""),
+    ("SLOWLOG RESET", ""),
+    ("SMEMBERS", "key"),
+    ("SPOP", "key [count]"),
+    ("SREM", "key member [member ...]"),
+    ("SSCAN", "key cursor [MATCH pattern] [COUNT count]"),
+    ("STRLEN", "key"),
+    ("SUBSCRIBE", "channel [channel ...]"),
+    ("TTL", "key"),
+    ("TYPE", "key"),
+    ("UNLINK", "key [key ...]"),
+    ("XADD", "key [NOMKSTREAM] [MAXLEN|MINID [=|~] threshold] *|id field value [field value ...]"),
+    ("XLEN", "key"),
+    ("XRANGE", "key start end [COUNT count]"),
+    ("ZADD", "key [NX|XX] [GT|LT] [CH] [INCR] score member [score member ...]"),
+    ("ZCARD", "key"),
+    ("ZINCRBY", "key increment member"),
+    ("ZRANGE", "key start stop [BYSCORE|BYLEX] [REV] [LIMIT offset count] [WITHSCORES]"),
+    ("ZREM", "key member [member ...]"),
+    ("ZSCAN", "key cursor [MATCH pattern] [COUNT count]"),
+    ("ZSCORE", "key member"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandDoc {
+    //upper case, subcommands joined with a space like CONFIG GET
+    pub name: String,
+    //argument signature as in the redis docs, key value [NX|XX]
+    pub args: String,
+}
+
+pub fn builtin_docs() -> Vec<CommandDoc> {
+    BUILTIN_COMMANDS
+        .iter()
+        .map(|(name, args)| CommandDoc {
+            name: name.to_string(),
+            args: args.to_string(),
+        })
+        .collect()
+}
+
+fn as_text(val: &Value) -> Option<String> {
+    match val {
+        Value::Data(data) => Some(String::from_utf8_lossy(data).into_owned()),
+        Value::Status(status) => Some(status.clone()),
+        _ => None,
+    }
+}
+
+//resp2 maps come as flat arrays of key value pairs
+fn as_pairs(val: &Value) -> Vec<(String, &Value)> {
+    match val {
+        Value::Bulk(items) => items
+            .chunks(2)
+            .filter(|pair| pair.len() == 2)
+            .filter_map(|pair| Some((as_text(&pair[0])?, &pair[1])))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn render_arg(arg: &Value) -> String {
+    let fields = as_pairs(arg);
+    let get = |key: &str| fields.iter().find(|(k, _)| k == key).map(|(_, v)| *v);
+    let text = |key: &str| get(key).and_then(as_text);
+    let flags: Vec<String> = match get("flags") {
+        Some(Value::Bulk(flags)) => flags.iter().filter_map(as_text).collect(),
+        _ => vec![],
+    };
+    let children = || -> Vec<String> {
+        match get("arguments") {
+            Some(Value::Bulk(args)) => args.iter().map(render_arg).collect(),
+            _ => vec![],
+        }
+    };
+    let mut out = match text("type").as_deref() {
+        Some("pure-token") => text("token").unwrap_or_default(),
+        Some("oneof") => children().join("|"),
+        Some("block") => children().join(" "),
+        _ => {
+            let name = text("display_text")
+                .or_else(|| text("name"))
+                .unwrap_or_default();
+            match text("token") {
+                Some(token) => format!("{} {}", token, name),
+                None => name,
+            }
+        }
+    };
+    if flags.iter().any(|f| f == "multiple") {
+        out = format!("{} [{} ...]", out, out);
+    }
+    if flags.iter().any(|f| f == "optional") {
+        out = format!("[{}]", out);
+    }
+    out
+}
+
+fn collect_docs(reply: &Value, out: &mut Vec<CommandDoc>) {
+    for (name, doc) in as_pairs(reply) {
+        let fields = as_pairs(doc);
+        let args = fields
+            .iter()
+            .find(|(k, _)| k == "arguments")
+            .map(|(_, v)| match v {
+                Value::Bulk(args) => args.iter().map(render_arg).collect::<Vec<_>>().join(" "),
+                _ => String::new(),
+            })
+            .unwrap_or_default();
+        out.push(CommandDoc {
+            name: name.replace('|', " ").to_uppercase(),
+            args,
+        });
+        if let Some((_, subs)) = fields.iter().find(|(k, _)| k == "subcommands") {
+            collect_docs(subs, out);
+        }
+    }
+}
+
+/// command table from a COMMAND DOCS reply
+pub fn parse_command_docs(reply: &Value) -> Vec<CommandDoc> {
+    let mut docs = vec![];
+    collect_docs(reply, &mut docs);
+    docs.sort_by(|a, b| a.name.cmp(&b.name));
+    docs
+}
+
+//split a signature into top level words, [EX seconds|PX milliseconds] stays one word
+fn signature_words(args: &str) -> Vec<&str> {
+    let mut words = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ' ' if depth == 0 => {
+                if i > start {
+                    words.push(&args[start..i]);
+                }
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < args.len() {
+        words.push(&args[start..]);
+    }
+    words
+}
+
+/// whether the argument at index, counted after the command name, is a key
+pub fn is_key_arg(doc: &CommandDoc, index: usize) -> bool {
+    let is_key = |word: &str| word.trim_start_matches('[').starts_with("key");
+    let words = signature_words(&doc.args);
+    match words.get(index) {
+        Some(word) => is_key(word),
+        //key [key ...] repeats for every following arg
+        None => words
+            .last()
+            .is_some_and(|w| w.ends_with("...]") && is_key(w)),
+    }
+}
+
+pub(crate) fn escape_glob(prefix: &str) -> String {
+    let mut out = String::with_capacity(prefix.len());
+    for c in prefix.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// keys collected by SCAN MATCH prefix*, continued on every tab press
+struct KeyCache {
+    conn: Option<redis::Connection>,
+    pattern: String,
+    cursor: u64,
+    done: bool,
+    keys: BTreeSet<String>,
+}
+
+impl KeyCache {
+    fn keys_with_prefix(&mut self, prefix: &str) -> Vec<String> {
+        let pattern = format!("{}*", escape_glob(prefix));
+        //a finished scan of a shorter prefix already holds every match
+        let covered = self.done && prefix.starts_with(&self.pattern[..self.pattern.len() - 1]);
+        if pattern != self.pattern && !covered {
+            self.pattern = pattern;
+            self.cursor = 0;
+            self.done = false;
+            self.keys.clear();
+        }
+        if let Some(conn) = self.conn.as_mut() {
+            for _ in 0..SCAN_ROUNDS {
+                if self.done {
+                    break;
+                }
+                let ret: redis::RedisResult<(u64, Vec<Vec<u8>>)> = redis::cmd("SCAN")
+                    .arg(self.cursor)
+                    .arg("MATCH")
+                    .arg(&self.pattern)
+                    .arg("COUNT")
+                    .arg(SCAN_COUNT)
+                    .query(conn);
+                match ret {
+                    Ok((cursor, keys)) => {
+                        self.keys
+                            .extend(keys.iter().map(|k| String::from_utf8_lossy(k).into_owned()));
+                        self.cursor = cursor;
+                        self.done = cursor == 0;
+                    }
+                    Err(_) => self.done = true,
+                }
+            }
+        }
+        self.keys
+            .iter()
+            .filter(|k| k.starts_with(prefix))
+            .cloned()
+            .collect()
+    }
+}
+
+/// collapse keys to the next `:` level after the prefix, user:1:name and
+/// user:1:age typed as user: complete to user:1:
+pub fn key_prefixes(keys: &[String], prefix: &str, separator: char) -> Vec<String> {
+    let mut out = BTreeSet::new();
+    for key in keys {
+        let rest = &key[prefix.len()..];
+        match rest.find(separator) {
+            Some(idx) => out.insert(key[..prefix.len() + idx + 1].to_string()),
+            None => out.insert(key.clone()),
+        };
+    }
+    out.into_iter().collect()
+}
+
+//keep the case the user typed in, set completes to get, SE to SET
+fn match_case(typed: &str, name: &str) -> String {
+    if typed.chars().any(|c| c.is_ascii_lowercase()) {
+        name.to_lowercase()
+    } else {
+        name.to_string()
+    }
+}
+
+pub struct ConsoleHelper {
+    docs: Vec<CommandDoc>,
+    keys: RefCell<KeyCache>,
+    //ansi sgr code the prompt is drawn in, from the profile color tag
+    prompt_color: Option<&'static str>,
+}
+
+impl ConsoleHelper {
+    /// conn is a dedicated connection for SCAN so completion never interleaves
+    /// with replies of the console connection
+    pub fn new(docs: Vec<CommandDoc>, conn: Option<redis::Connection>) -> ConsoleHelper {
+        ConsoleHelper {
+            docs,
+            keys: RefCell::new(KeyCache {
+                conn,
+                pattern: "*".to_string(),
+                cursor: 0,
+                done: false,
+                keys: BTreeSet::new(),
+            }),
+            prompt_color: None,
+        }
+    }
+
+    pub fn set_prompt_color(&mut self, color: Option<&'static str>) {
+        self.prompt_color = color;
+    }
+
+    /// switch to another server or db, cached keys belong to the old one
+    pub fn set_connection(&mut self, conn: Option<redis::Connection>) {
+        let mut cache = self.keys.borrow_mut();
+        cache.conn = conn;
+        cache.pattern = "*".to_string();
+        cache.cursor = 0;
+        cache.done = false;
+        cache.keys.clear();
+    }
+
+    /// longest command name (with subcommand) matching the typed words,
+    /// and how many of the words it took
+    fn find_doc(&self, words: &[String]) -> Option<(&CommandDoc, usize)> {
+        let mut found = None;
+        for doc in &self.docs {
+            let parts = doc.name.split(' ').count();
+            if parts > words.len() {
+                continue;
+            }
+            let typed = words[..parts].join(" ");
+            if doc.name.eq_ignore_ascii_case(&typed) && found.is_none_or(|(_, n)| parts > n) {
+                found = Some((doc, parts));
+            }
+        }
+        found
+    }
+}
+
+//the words before the cursor and the start of the one being typed,
+//None while a quote is open or the line does not split
+fn words_before(line: &str, pos: usize) -> Option<(Vec<String>, usize)> {
+    let head = &line[..pos];
+    let start = head.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+    let words = split_args(&head.as_bytes()[..start]).ok()?;
+    let words = words
+        .iter()
+        .map(|w| String::from_utf8_lossy(w).into_owned())
+        .collect();
+    Some((words, start))
+}
+
+impl Completer for ConsoleHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let (words, start) = match words_before(line, pos) {
+            Some(found) => found,
+            None => return Ok((pos, vec![])),
+        };
+        let typed = &line[start..pos];
+        //command names, or subcommands when the typed words name a container
+        let prefix = if words.is_empty() {
+            String::new()
+        } else {
+            format!("{} ", words.join(" ").to_uppercase())
+        };
+        let mut names: Vec<Pair> = self
+            .docs
+            .iter()
+            .filter_map(|doc| {
+                let rest = doc.name.strip_prefix(&prefix)?;
+                let word = rest.split(' ').next()?;
+                if word.is_empty() || !word.starts_with(&typed.to_uppercase()) {
+                    return None;
+                }
+                Some(Pair {
+                    display: word.to_string(),
+                    replacement: match_case(typed, word),
+                })
+            })
+            .collect();
+        names.dedup_by(|a, b| a.display == b.display);
+        if !names.is_empty() {
+            return Ok((start, names));
+        }
+
+        let is_key = self
+            .find_doc(&words)
+            .is_some_and(|(doc, used)| is_key_arg(doc, words.len() - used));
+        if !is_key {
+            return Ok((pos, vec![]));
+        }
+        let keys = self.keys.borrow_mut().keys_with_prefix(typed);
+        let pairs = key_prefixes(&keys, typed, ':')
+            .into_iter()
+            .map(|key| Pair {
+                display: key.clone(),
+                replacement: key,
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for ConsoleHelper {
+    type Hint = String;
+
+    /// argument signature of the typed command, minus the args already typed
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<String> {
+        if pos < line.len() {
+            return None;
+        }
+        let words: Vec<String> = split_args(line.as_bytes())
+            .ok()?
+            .iter()
+            .map(|w| String::from_utf8_lossy(w).into_owned())
+            .collect();
+        let (doc, used) = self.find_doc(&words)?;
+        let typed = words.len() - used;
+        let rest = signature_words(&doc.args);
+        let rest: Vec<&str> = rest.into_iter().skip(typed).collect();
+        if rest.is_empty() || (typed > 0 && !line.ends_with(' ')) {
+            return None;
+        }
+        let sep = if line.ends_with(' ') { "" } else { " " };
+        Some(format!("{}{}", sep, rest.join(" ")))
+    }
+}
+
+impl Highlighter for ConsoleHelper {
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        _default: bool,
+    ) -> Cow<'b, str> {
+        match self.prompt_color {
+            Some(color) => Cow::Owned(format!("\x1b[{}m{}\x1b[0m", color, prompt)),
+            None => Cow::Borrowed(prompt),
+        }
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("\x1b[90m{}\x1b[0m", hint))
+    }
+}
+
+impl Validator for ConsoleHelper {}
+
+impl Helper for ConsoleHelper {}
+
+#[test]
+fn test_complete_commands_and_hints() {
+    let helper = ConsoleHelper::new(builtin_docs(), None);
+    let history = rustyline::history::History::new();
+    let ctx = Context::new(&history);
+
+    let (start, found) = helper.complete("hge", 3, &ctx).unwrap();
+    assert_eq!(0, start);
+    let names: Vec<&str> = found.iter().map(|p| p.replacement.as_str()).collect();
+    assert_eq!(vec!["hget", "hgetall"], names);
+
+    let (start, found) = helper.complete("CONFIG S", 8, &ctx).unwrap();
+    assert_eq!(7, start);
+    assert_eq!("SET", found[0].replacement);
+    assert_eq!(1, found.len());
+
+    assert_eq!(Some(" key field".to_string()), helper.hint("hget", 4, &ctx));
+    assert_eq!(
+        Some("field".to_string()),
+        helper.hint("hget user:1 ", 12, &ctx)
+    );
+    assert_eq!(None, helper.hint("hget user:1", 11, &ctx));
+    assert_eq!(
+        Some("parameter [parameter ...]".to_string()),
+        helper.hint("config get ", 11, &ctx)
+    );
+}
+
+#[test]
+fn test_key_args_and_prefixes() {
+    let docs = builtin_docs();
+    let doc = |name: &str| docs.iter().find(|d| d.name == name).unwrap().clone();
+    assert!(is_key_arg(&doc("GET"), 0));
+    assert!(!is_key_arg(&doc("HGET"), 1));
+    assert!(is_key_arg(&doc("DEL"), 3));
+    assert!(!is_key_arg(&doc("SELECT"), 0));
+
+    let keys: Vec<String> = vec!["user:1:name", "user:1:age", "user:10", "order:7"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let users: Vec<String> = keys
+        .iter()
+        .filter(|k| k.starts_with("user:"))
+        .cloned()
+        .collect();
+    assert_eq!(
+        vec!["user:10", "user:1:"],
+        key_prefixes(&users, "user:", ':')
+    );
+    assert_eq!(
+        vec!["user:1:age", "user:1:name"],
+        key_prefixes(&users[..2], "user:1:", ':')
+    );
+}
+
+#[test]
+fn test_parse_command_docs() {
+    let data = |s: &str| Value::Data(s.as_bytes().to_vec());
+    let arg = |fields: Vec<(&str, Value)>| {
+        Value::Bulk(
+            fields
+                .into_iter()
+                .flat_map(|(k, v)| vec![data(k), v])
+                .collect(),
+        )
+    };
+    let reply = Value::Bulk(vec![
+        data("get"),
+        arg(vec![
+            ("summary", data("Returns the string value of a key.")),
+            (
+                "arguments",
+                Value::Bulk(vec![arg(vec![
+                    ("name", data("key")),
+                    ("type", data("key")),
+                ])]),
+            ),
+        ]),
+        data("expire"),
+        arg(vec![(
+            "arguments",
+            Value::Bulk(vec![
+                arg(vec![("name", data("key")), ("type", data("key"))]),
+                arg(vec![("name", data("seconds")), ("type", data("integer"))]),
+                arg(vec![
+                    ("name", data("condition")),
+                    ("type", data("oneof")),
+                    ("flags", Value::Bulk(vec![data("optional")])),
+                    (
+                        "arguments",
+                        Value::Bulk(vec![
+                            arg(vec![
+                                ("name", data("nx")),
+                                ("type", data("pure-token")),
+                                ("token", data("NX")),
+                            ]),
+                            arg(vec![
+                                ("name", data("xx")),
+                                ("type", data("pure-token")),
+                                ("token", data("XX")),
+                            ]),
+                        ]),
+                    ),
+                ]),
+            ]),
+        )]),
+    ]);
+    let docs = parse_command_docs(&reply);
+    assert_eq!(
+        vec![
+            CommandDoc {
+                name: "EXPIRE".into(),
+                args: "key seconds [NX|XX]".into()
+            },
+            CommandDoc {
+                name: "GET".into(),
+                args: "key".into()
+            },
+        ],
+        docs
+    );
+}
@@ -0,0 +1,202 @@
+use std::fmt;
+use std::io::{self, Read};
+
+//values bigger than this after decompression are refused, zip bomb guard
+pub const DEFAULT_SIZE_CAP: usize = 64 * 1024 * 1024;
+//compressed payloads are seldom nested deeper, a loop guard for crafted values
+const MAX_LAYERS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zlib,
+    Zstd,
+    Lz4,
+    Snappy,
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Compression::Gzip => "gzip",
+            Compression::Zlib => "zlib",
+            Compression::Zstd => "zstd",
+            Compression::Lz4 => "lz4",
+            Compression::Snappy => "snappy",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Peeled {
+    //outermost layer first
+    pub layers: Vec<Compression>,
+    pub data: Vec<u8>,
+    //why an inner layer could not be peeled or peeling stopped, data is still wrapped then
+    pub error: Option<String>,
+}
+
+pub fn detect_compression(data: &[u8]) -> Option<Compression> {
+    if data.starts_with(&[0x1f, 0x8b, 0x08]) {
+        Some(Compression::Gzip)
+    } else if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Some(Compression::Zstd)
+    } else if data.starts_with(&[0x04, 0x22, 0x4d, 0x18]) {
+        Some(Compression::Lz4)
+    } else if data.starts_with(b"\xff\x06\x00\x00sNaPpY") {
+        Some(Compression::Snappy)
+    } else if data.len() > 2
+        && data[0] == 0x78
+        && data[1] & 0x20 == 0
+        && (u16::from(data[0]) * 256 + u16::from(data[1])) % 31 == 0
+    {
+        //deflate with 32k window, no preset dictionary and a valid header check
+        Some(Compression::Zlib)
+    } else {
+        None
+    }
+}
+
+//a value that inflates past the cap, told apart from data that is not compressed at all
+#[derive(Debug)]
+struct OverCap(usize);
+
+impl fmt::Display for OverCap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "decompressed size exceeds the {} bytes cap", self.0)
+    }
+}
+
+impl std::error::Error for OverCap {}
+
+fn is_over_cap(err: &io::Error) -> bool {
+    err.get_ref().is_some_and(|inner| inner.is::<OverCap>())
+}
+
+fn read_capped<R: Read>(reader: R, cap: usize) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    reader.take(cap as u64 + 1).read_to_end(&mut out)?;
+    if out.len() > cap {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, OverCap(cap)));
+    }
+    Ok(out)
+}
+
+pub fn decompress(data: &[u8], kind: Compression, cap: usize) -> io::Result<Vec<u8>> {
+    match kind {
+        Compression::Gzip => read_capped(flate2::read::GzDecoder::new(data), cap),
+        Compression::Zlib => read_capped(flate2::read::ZlibDecoder::new(data), cap),
+        Compression::Zstd => read_capped(zstd::stream::read::Decoder::new(data)?, cap),
+        Compression::Lz4 => read_capped(lz4_flex::frame::FrameDecoder::new(data), cap),
+        Compression::Snappy => read_capped(snap::read::FrameDecoder::new(data), cap),
+    }
+}
+
+/// strip every compression layer the value is wrapped in. the zlib sniff is weak, data
+/// that does not inflate is taken as not compressed. an outer layer that fails is an
+/// error, an inner one or too many layers stop peeling there
+pub fn peel(data: &[u8], cap: usize) -> io::Result<Peeled> {
+    let mut layers = vec![];
+    let mut cur = data.to_vec();
+    let mut error = None;
+    while let Some(kind) = detect_compression(&cur) {
+        if layers.len() == MAX_LAYERS {
+            error = Some(format!("more than {} compression layers", MAX_LAYERS));
+            break;
+        }
+        match decompress(&cur, kind, cap) {
+            Ok(data) => cur = data,
+            Err(err) if kind == Compression::Zlib && !is_over_cap(&err) => break,
+            Err(err) if layers.is_empty() => return Err(err),
+            Err(err) => {
+                error = Some(format!("{} layer: {}", kind, err));
+                break;
+            }
+        }
+        layers.push(kind);
+    }
+    Ok(Peeled {
+        layers,
+        data: cur,
+        error,
+    })
+}
+
+#[cfg(test)]
+fn gzip(data: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+    let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    enc.write_all(data).unwrap();
+    enc.finish().unwrap()
+}
+
+#[test]
+fn test_peel_each_format() {
+    use std::io::Write;
+    let plain = br#"{"name": "alex"}"#.to_vec();
+
+    let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    zlib.write_all(&plain).unwrap();
+    let mut snappy = snap::write::FrameEncoder::new(Vec::new());
+    snappy.write_all(&plain).unwrap();
+    let mut lz4 = lz4_flex::frame::FrameEncoder::new(Vec::new());
+    lz4.write_all(&plain).unwrap();
+
+    let cases = vec![
+        (Compression::Gzip, gzip(&plain)),
+        (Compression::Zlib, zlib.finish().unwrap()),
+        (Compression::Zstd, zstd::encode_all(&plain[..], 3).unwrap()),
+        (Compression::Lz4, lz4.finish().unwrap()),
+        (Compression::Snappy, snappy.into_inner().unwrap()),
+    ];
+    for (kind, packed) in cases {
+        let peeled = peel(&packed, DEFAULT_SIZE_CAP).unwrap();
+        assert_eq!(vec![kind], peeled.layers);
+        assert_eq!(plain, peeled.data);
+    }
+}
+
+#[test]
+fn test_peel_nested_and_capped() {
+    let plain = vec![b'a'; 4096];
+    let nested = gzip(&zstd::encode_all(&plain[..], 3).unwrap());
+    let peeled = peel(&nested, DEFAULT_SIZE_CAP).unwrap();
+    assert_eq!(vec![Compression::Gzip, Compression::Zstd], peeled.layers);
+    assert_eq!(plain, peeled.data);
+
+    assert!(peel(&gzip(&plain), 1024).is_err());
+    let untouched = peel(b"plain text", DEFAULT_SIZE_CAP).unwrap();
+    assert!(untouched.layers.is_empty());
+
+    //text that starts like a zlib header
+    for text in [&b"x = 1"[..], b"x}y", b"x\x9cnot deflate"] {
+        let peeled = peel(text, DEFAULT_SIZE_CAP).unwrap();
+        assert!(peeled.layers.is_empty());
+        assert_eq!(text, &peeled.data[..]);
+        assert_eq!(None, peeled.error);
+    }
+    //the gzip layer stays peeled when the zstd inside is too big
+    let inner = zstd::encode_all(&plain[..], 3).unwrap();
+    let peeled = peel(&gzip(&inner), 1024).unwrap();
+    assert_eq!(vec![Compression::Gzip], peeled.layers);
+    assert_eq!(inner, peeled.data);
+    assert!(peeled.error.unwrap().starts_with("zstd layer: "));
+
+    //a zlib bomb is refused, not taken for text that only looks like zlib
+    let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    std::io::Write::write_all(&mut zlib, &plain).unwrap();
+    let zlib = zlib.finish().unwrap();
+    assert!(peel(&zlib, 1024).is_err());
+    let peeled = peel(&gzip(&zlib), 1024).unwrap();
+    assert_eq!(vec![Compression::Gzip], peeled.layers);
+    assert!(peeled.error.unwrap().starts_with("zlib layer: decompressed size exceeds"));
+
+    let mut deep = plain.clone();
+    for _ in 0..=MAX_LAYERS {
+        deep = gzip(&deep);
+    }
+    let peeled = peel(&deep, DEFAULT_SIZE_CAP).unwrap();
+    assert_eq!(MAX_LAYERS, peeled.layers.len());
+    assert_eq!(Some("more than 4 compression layers".to_string()), peeled.error);
+}
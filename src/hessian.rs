@@ -258,7 +258,7 @@ impl<'a> Serializer<'a> {
         let mut str_val = String::with_capacity(len * 3);
         for _ in 0..len {
             let chr = self.parse_utf8()?;
-            let chr = std::char::from_u32(chr).unwrap();
+            let chr = std::char::from_u32(chr).unwrap_or(std::char::REPLACEMENT_CHARACTER);
             str_val.push(chr);
         }
         Ok(str_val)
@@ -318,12 +318,12 @@ impl<'a> Serializer<'a> {
     fn read_int_bytag(&self, tag: u8) -> Result<i32, ParseErr> {
         match tag {
             0x80..=0xbf => {
-                Ok((tag - 0x90) as i32)
+                Ok(i32::from(tag) - 0x90)
             }
             0xc0..=0xcf => {
                 //byte int
                 let (i, l) = be_u8(self.cur_offset())?;
-                let val = (i32::from(tag - 0xc8) << 8) + i32::from(l);
+                let val = ((i32::from(tag) - 0xc8) << 8) + i32::from(l);
                 self.incr_offset(i);
                 Ok(val)
             }
@@ -339,6 +339,7 @@ impl<'a> Serializer<'a> {
             0x49 | 0x59 => {
                 //int
                 let (i, val) = be_i32(self.cur_offset())?;
+                self.incr_offset(i);
                 Ok(val)
             }
             _ => Err(Err::Error(make_error(self.cur_offset(), ErrorKind::Eof)))
@@ -363,7 +364,7 @@ impl<'a> Serializer<'a> {
     fn read_long_bytag(&self, tag: u8) -> Result<i64, ParseErr> {
         match tag {
             0xd8..=0xef => {
-                Ok(i64::from(tag - 0xe0))
+                Ok(i64::from(tag) - 0xe0)
             }
             0xf0..=0xff => {
                 let (i, l) = be_u8(self.cur_offset())?;
@@ -598,6 +599,12 @@ impl<'a> Serializer<'a> {
     }
 }
 
+/// read the first hessian value out of the buffer, on error tells how many bytes were left
+pub fn decode(buf: &[u8]) -> Result<Object, Err<(usize, ErrorKind)>> {
+    let ser = Serializer::new(buf);
+    ser.read_object().map_err(|e| e.map_input(|i| i.len()))
+}
+
 #[derive(Debug)]
 pub enum ListValue {
     Empty,
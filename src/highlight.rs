@@ -1,9 +1,67 @@
 extern crate syntect;
 use syntect::easy::HighlightLines;
-use syntect::highlighting::{Style, ThemeSet};
+use syntect::highlighting::{Style, Theme, ThemeSet};
 use syntect::parsing::SyntaxSet;
 use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
 
+/// syntaxes and theme loaded once, loading them takes a noticeable moment
+pub struct CodeHighlighter {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+impl Default for CodeHighlighter {
+    fn default() -> Self {
+        Self::new("Solarized (dark)")
+    }
+}
+
+impl CodeHighlighter {
+    /// theme is one of syntect's default themes, an unknown name falls back to base16-ocean.dark
+    pub fn new(theme: &str) -> CodeHighlighter {
+        let mut themes = ThemeSet::load_defaults().themes;
+        let theme = themes
+            .remove(theme)
+            .or_else(|| themes.remove("base16-ocean.dark"))
+            .expect("syntect ships base16-ocean.dark");
+        CodeHighlighter {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme,
+        }
+    }
+
+    /// styled pieces per line, extension picks the syntax like json or xml,
+    /// unknown ones come back as plain text
+    pub fn highlight(&self, text: &str, extension: &str) -> Vec<Vec<(Style, String)>> {
+        let syntax = self
+            .syntaxes
+            .find_syntax_by_extension(extension)
+            .unwrap_or_else(|| self.syntaxes.find_syntax_plain_text());
+        let mut h = HighlightLines::new(syntax, &self.theme);
+        LinesWithEndings::from(text)
+            .map(|line| {
+                h.highlight(line, &self.syntaxes)
+                    .into_iter()
+                    .map(|(style, piece)| (style, piece.trim_end_matches('\n').to_string()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// the text with 24 bit color escapes for a plain terminal
+    pub fn to_terminal(&self, text: &str, extension: &str) -> String {
+        self.highlight(text, extension)
+            .iter()
+            .map(|line| {
+                let pieces: Vec<(Style, &str)> =
+                    line.iter().map(|(style, s)| (*style, s.as_str())).collect();
+                as_24_bit_terminal_escaped(&pieces, false)
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
 #[test]
 fn test() {
     // Load these once at the start of your program
@@ -26,3 +84,15 @@ fn test() {
         println!("{}", escaped);
     }
 }
+
+#[test]
+fn test_code_highlighter() {
+    let h = CodeHighlighter::default();
+    let lines = h.highlight("{\n  \"name\": \"alex\"\n}", "json");
+    assert_eq!(3, lines.len());
+    let second: String = lines[1].iter().map(|(_, s)| s.as_str()).collect();
+    assert_eq!("  \"name\": \"alex\"", second);
+    //keys and braces get different colors
+    assert!(lines[1].iter().any(|(style, _)| style.foreground != lines[0][0].0.foreground));
+    assert_eq!(1, h.highlight("plain", "no-such-ext").len());
+}
@@ -0,0 +1,416 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// raw fields of one INFO section
+pub type Section = BTreeMap<String, String>;
+
+fn get<T: FromStr + Default>(section: Option<&Section>, key: &str) -> T {
+    section
+        .and_then(|s| s.get(key))
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Server {
+    pub redis_version: String,
+    pub redis_mode: String,
+    pub os: String,
+    pub process_id: u32,
+    pub tcp_port: u16,
+    pub uptime_in_seconds: u64,
+    pub hz: u32,
+    pub config_file: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Clients {
+    pub connected_clients: u64,
+    pub blocked_clients: u64,
+    pub tracking_clients: u64,
+    //only reported since redis 5
+    pub maxclients: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Memory {
+    pub used_memory: u64,
+    pub used_memory_human: String,
+    pub used_memory_rss: u64,
+    pub used_memory_peak: u64,
+    pub used_memory_lua: u64,
+    //0 is no limit
+    pub maxmemory: u64,
+    pub maxmemory_policy: String,
+    pub mem_fragmentation_ratio: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Persistence {
+    pub loading: bool,
+    pub rdb_changes_since_last_save: u64,
+    pub rdb_bgsave_in_progress: bool,
+    pub rdb_last_save_time: i64,
+    pub rdb_last_bgsave_status: String,
+    pub aof_enabled: bool,
+    pub aof_rewrite_in_progress: bool,
+    pub aof_last_write_status: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub total_connections_received: u64,
+    pub total_commands_processed: u64,
+    pub instantaneous_ops_per_sec: u64,
+    pub total_net_input_bytes: u64,
+    pub total_net_output_bytes: u64,
+    pub rejected_connections: u64,
+    pub expired_keys: u64,
+    pub evicted_keys: u64,
+    pub keyspace_hits: u64,
+    pub keyspace_misses: u64,
+    pub pubsub_channels: u64,
+    pub pubsub_patterns: u64,
+}
+
+impl Stats {
+    /// hits over all lookups since the server started, None before the first lookup
+    pub fn hit_ratio(&self) -> Option<f64> {
+        ratio(self.keyspace_hits, self.keyspace_misses)
+    }
+}
+
+fn ratio(hits: u64, misses: u64) -> Option<f64> {
+    let total = hits + misses;
+    if total == 0 {
+        None
+    } else {
+        Some(hits as f64 / total as f64)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Replica {
+    pub ip: String,
+    pub port: u16,
+    pub state: String,
+    pub offset: u64,
+    pub lag: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Replication {
+    pub role: String,
+    pub connected_slaves: u32,
+    //set on replicas only
+    pub master_host: String,
+    pub master_port: u16,
+    pub master_link_status: String,
+    pub master_repl_offset: u64,
+    pub slaves: Vec<Replica>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cpu {
+    //seconds of cpu time since start
+    pub used_cpu_sys: f64,
+    pub used_cpu_user: f64,
+    pub used_cpu_sys_children: f64,
+    pub used_cpu_user_children: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DbKeyspace {
+    pub db: u32,
+    pub keys: u64,
+    pub expires: u64,
+    pub avg_ttl: u64,
+}
+
+/// INFO ALL parsed into typed sections, sections this model does not know
+/// stay reachable through `sections`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Info {
+    pub server: Server,
+    pub clients: Clients,
+    pub memory: Memory,
+    pub persistence: Persistence,
+    pub stats: Stats,
+    pub replication: Replication,
+    pub cpu: Cpu,
+    pub keyspace: Vec<DbKeyspace>,
+    //lower case section name to its raw fields
+    pub sections: BTreeMap<String, Section>,
+}
+
+//slave0:ip=10.0.0.2,port=6380,state=online,offset=42,lag=0 and db0:keys=1,expires=0
+fn sub_fields(value: &str) -> Section {
+    value
+        .split(',')
+        .filter_map(|kv| kv.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+fn flag(section: Option<&Section>, key: &str) -> bool {
+    get::<u8>(section, key) == 1
+}
+
+impl Info {
+    pub fn parse(text: &str) -> Info {
+        let mut sections: BTreeMap<String, Section> = BTreeMap::new();
+        let mut current = String::new();
+        for line in text.lines() {
+            let line = line.trim_end_matches('\r');
+            if let Some(name) = line.strip_prefix("# ") {
+                current = name.trim().to_lowercase();
+                sections.entry(current.clone()).or_default();
+            } else if let Some((key, value)) = line.split_once(':') {
+                sections
+                    .entry(current.clone())
+                    .or_default()
+                    .insert(key.to_string(), value.to_string());
+            }
+        }
+
+        let section = |name: &str| sections.get(name);
+        let server = section("server");
+        let clients = section("clients");
+        let memory = section("memory");
+        let persistence = section("persistence");
+        let stats = section("stats");
+        let replication = section("replication");
+        let cpu = section("cpu");
+
+        let slaves = replication
+            .map(|r| {
+                r.iter()
+                    .filter(|(k, _)| {
+                        k.strip_prefix("slave")
+                            .is_some_and(|n| n.chars().all(|c| c.is_ascii_digit()))
+                    })
+                    .map(|(_, v)| {
+                        let fields = sub_fields(v);
+                        let f = Some(&fields);
+                        Replica {
+                            ip: get(f, "ip"),
+                            port: get(f, "port"),
+                            state: get(f, "state"),
+                            offset: get(f, "offset"),
+                            lag: get(f, "lag"),
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut keyspace: Vec<DbKeyspace> = section("keyspace")
+            .map(|k| {
+                k.iter()
+                    .filter_map(|(name, v)| {
+                        let db = name.strip_prefix("db")?.parse().ok()?;
+                        let fields = sub_fields(v);
+                        let f = Some(&fields);
+                        Some(DbKeyspace {
+                            db,
+                            keys: get(f, "keys"),
+                            expires: get(f, "expires"),
+                            avg_ttl: get(f, "avg_ttl"),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        keyspace.sort_by_key(|k| k.db);
+
+        Info {
+            server: Server {
+                redis_version: get(server, "redis_version"),
+                redis_mode: get(server, "redis_mode"),
+                os: get(server, "os"),
+                process_id: get(server, "process_id"),
+                tcp_port: get(server, "tcp_port"),
+                uptime_in_seconds: get(server, "uptime_in_seconds"),
+                hz: get(server, "hz"),
+                config_file: get(server, "config_file"),
+            },
+            clients: Clients {
+                connected_clients: get(clients, "connected_clients"),
+                blocked_clients: get(clients, "blocked_clients"),
+                tracking_clients: get(clients, "tracking_clients"),
+                maxclients: get(clients, "maxclients"),
+            },
+            memory: Memory {
+                used_memory: get(memory, "used_memory"),
+                used_memory_human: get(memory, "used_memory_human"),
+                used_memory_rss: get(memory, "used_memory_rss"),
+                used_memory_peak: get(memory, "used_memory_peak"),
+                used_memory_lua: get(memory, "used_memory_lua"),
+                maxmemory: get(memory, "maxmemory"),
+                maxmemory_policy: get(memory, "maxmemory_policy"),
+                mem_fragmentation_ratio: get(memory, "mem_fragmentation_ratio"),
+            },
+            persistence: Persistence {
+                loading: flag(persistence, "loading"),
+                rdb_changes_since_last_save: get(persistence, "rdb_changes_since_last_save"),
+                rdb_bgsave_in_progress: flag(persistence, "rdb_bgsave_in_progress"),
+                rdb_last_save_time: get(persistence, "rdb_last_save_time"),
+                rdb_last_bgsave_status: get(persistence, "rdb_last_bgsave_status"),
+                aof_enabled: flag(persistence, "aof_enabled"),
+                aof_rewrite_in_progress: flag(persistence, "aof_rewrite_in_progress"),
+                aof_last_write_status: get(persistence, "aof_last_write_status"),
+            },
+            stats: Stats {
+                total_connections_received: get(stats, "total_connections_received"),
+                total_commands_processed: get(stats, "total_commands_processed"),
+                instantaneous_ops_per_sec: get(stats, "instantaneous_ops_per_sec"),
+                total_net_input_bytes: get(stats, "total_net_input_bytes"),
+                total_net_output_bytes: get(stats, "total_net_output_bytes"),
+                rejected_connections: get(stats, "rejected_connections"),
+                expired_keys: get(stats, "expired_keys"),
+                evicted_keys: get(stats, "evicted_keys"),
+                keyspace_hits: get(stats, "keyspace_hits"),
+                keyspace_misses: get(stats, "keyspace_misses"),
+                pubsub_channels: get(stats, "pubsub_channels"),
+                pubsub_patterns: get(stats, "pubsub_patterns"),
+            },
+            replication: Replication {
+                role: get(replication, "role"),
+                connected_slaves: get(replication, "connected_slaves"),
+                master_host: get(replication, "master_host"),
+                master_port: get(replication, "master_port"),
+                master_link_status: get(replication, "master_link_status"),
+                master_repl_offset: get(replication, "master_repl_offset"),
+                slaves,
+            },
+            cpu: Cpu {
+                used_cpu_sys: get(cpu, "used_cpu_sys"),
+                used_cpu_user: get(cpu, "used_cpu_user"),
+                used_cpu_sys_children: get(cpu, "used_cpu_sys_children"),
+                used_cpu_user_children: get(cpu, "used_cpu_user_children"),
+            },
+            keyspace,
+            sections,
+        }
+    }
+
+    pub fn total_keys(&self) -> u64 {
+        self.keyspace.iter().map(|k| k.keys).sum()
+    }
+}
+
+/// rates between two INFO snapshots
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Delta {
+    pub elapsed: Duration,
+    pub ops_per_sec: f64,
+    //hits over lookups made within the interval, None when there were none
+    pub hit_ratio: Option<f64>,
+    pub net_input_per_sec: f64,
+    pub net_output_per_sec: f64,
+    pub connections_per_sec: f64,
+    pub expired_per_sec: f64,
+    pub evicted_per_sec: f64,
+    //cpu time of the server process over wall time, 100 is one core busy
+    pub cpu_percent: f64,
+    pub used_memory_change: i64,
+}
+
+//counters go back to 0 on restart or CONFIG RESETSTAT
+fn per_sec(prev: u64, cur: u64, secs: f64) -> f64 {
+    cur.saturating_sub(prev) as f64 / secs
+}
+
+impl Delta {
+    /// elapsed is the wall time between the two INFO calls; when the server was
+    /// restarted in between the counters restart too and rates are taken from 0
+    pub fn between(prev: &Info, cur: &Info, elapsed: Duration) -> Delta {
+        let secs = elapsed.as_secs_f64().max(0.001);
+        let restarted = cur.server.uptime_in_seconds < prev.server.uptime_in_seconds;
+        let zero = Info::default();
+        let base = if restarted { &zero } else { prev };
+        let (p, c) = (&base.stats, &cur.stats);
+        let cpu_time = |cpu: &Cpu| cpu.used_cpu_sys + cpu.used_cpu_user;
+        Delta {
+            elapsed,
+            ops_per_sec: per_sec(p.total_commands_processed, c.total_commands_processed, secs),
+            hit_ratio: ratio(
+                c.keyspace_hits.saturating_sub(p.keyspace_hits),
+                c.keyspace_misses.saturating_sub(p.keyspace_misses),
+            ),
+            net_input_per_sec: per_sec(p.total_net_input_bytes, c.total_net_input_bytes, secs),
+            net_output_per_sec: per_sec(p.total_net_output_bytes, c.total_net_output_bytes, secs),
+            connections_per_sec: per_sec(
+                p.total_connections_received,
+                c.total_connections_received,
+                secs,
+            ),
+            expired_per_sec: per_sec(p.expired_keys, c.expired_keys, secs),
+            evicted_per_sec: per_sec(p.evicted_keys, c.evicted_keys, secs),
+            cpu_percent: ((cpu_time(&cur.cpu) - cpu_time(&base.cpu)) / secs * 100.0).max(0.0),
+            used_memory_change: cur.memory.used_memory as i64 - prev.memory.used_memory as i64,
+        }
+    }
+}
+
+#[test]
+fn test_parse_info_sections() {
+    let text = std::fs::read_to_string("testdata/info_1.txt").unwrap();
+    let info = Info::parse(&text);
+    assert_eq!("7.0.11", info.server.redis_version);
+    assert_eq!(6379, info.server.tcp_port);
+    assert_eq!(3600, info.server.uptime_in_seconds);
+    assert_eq!(12, info.clients.connected_clients);
+    assert_eq!(1099392, info.memory.used_memory);
+    assert_eq!("1.05M", info.memory.used_memory_human);
+    assert_eq!("allkeys-lru", info.memory.maxmemory_policy);
+    assert!((info.memory.mem_fragmentation_ratio - 9.43).abs() < 1e-9);
+    assert!(info.persistence.aof_enabled);
+    assert!(!info.persistence.rdb_bgsave_in_progress);
+    assert_eq!("ok", info.persistence.rdb_last_bgsave_status);
+    assert_eq!(Some(0.8), info.stats.hit_ratio());
+    assert_eq!("master", info.replication.role);
+    assert_eq!(
+        vec![Replica {
+            ip: "10.0.0.2".into(),
+            port: 6380,
+            state: "online".into(),
+            offset: 5012,
+            lag: 1
+        }],
+        info.replication.slaves
+    );
+    assert!((info.cpu.used_cpu_user - 1.5).abs() < 1e-9);
+    assert_eq!(2, info.keyspace.len());
+    assert_eq!(
+        DbKeyspace {
+            db: 3,
+            keys: 20,
+            expires: 0,
+            avg_ttl: 0
+        },
+        info.keyspace[1]
+    );
+    assert_eq!(120, info.total_keys());
+    assert_eq!("jemalloc-5.2.1", info.sections["memory"]["mem_allocator"]);
+}
+
+#[test]
+fn test_delta_between_refreshes() {
+    let prev = Info::parse(&std::fs::read_to_string("testdata/info_1.txt").unwrap());
+    let cur = Info::parse(&std::fs::read_to_string("testdata/info_2.txt").unwrap());
+    let delta = Delta::between(&prev, &cur, Duration::from_secs(2));
+    assert!((delta.ops_per_sec - 500.0).abs() < 1e-9);
+    //300 hits and 100 misses within the interval
+    assert_eq!(Some(0.75), delta.hit_ratio);
+    assert!((delta.cpu_percent - 25.0).abs() < 1e-6);
+    assert!((delta.net_input_per_sec - 1024.0).abs() < 1e-9);
+    assert_eq!(4096, delta.used_memory_change);
+
+    //a restart resets counters, rates count from zero instead of going negative
+    let mut restarted = cur.clone();
+    restarted.server.uptime_in_seconds = 1;
+    restarted.stats.total_commands_processed = 10;
+    let delta = Delta::between(&prev, &restarted, Duration::from_secs(2));
+    assert!((delta.ops_per_sec - 5.0).abs() < 1e-9);
+}
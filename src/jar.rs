@@ -0,0 +1,446 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use crate::classfile::{self, ClassFile, Constant, MemberInfo};
+use crate::classfile::{ACC_ABSTRACT, ACC_FINAL, ACC_INTERFACE, ACC_NATIVE, ACC_PRIVATE};
+use crate::classfile::{ACC_PROTECTED, ACC_PUBLIC, ACC_STATIC, ACC_STRICT, ACC_SYNCHRONIZED};
+use crate::classfile::{ACC_TRANSIENT, ACC_VOLATILE};
+use crate::javaser::{ClassDesc, ObjectStream, SC_ENUM, SC_EXTERNALIZABLE};
+
+const CLASS_MODS: u16 = ACC_PUBLIC | ACC_FINAL | ACC_INTERFACE | ACC_ABSTRACT;
+const FIELD_MODS: u16 = ACC_PUBLIC
+    | ACC_PRIVATE
+    | ACC_PROTECTED
+    | ACC_STATIC
+    | ACC_FINAL
+    | ACC_VOLATILE
+    | ACC_TRANSIENT;
+const METHOD_MODS: u16 = ACC_PUBLIC
+    | ACC_PRIVATE
+    | ACC_PROTECTED
+    | ACC_STATIC
+    | ACC_FINAL
+    | ACC_SYNCHRONIZED
+    | ACC_NATIVE
+    | ACC_ABSTRACT
+    | ACC_STRICT;
+
+#[derive(Debug, Clone)]
+pub struct ClassEntry {
+    //binary name, eg demo.Account$Entry
+    pub name: String,
+    //entry path inside the jar
+    pub path: String,
+    pub class: ClassFile,
+    pub serial_version_uid: i64,
+    //false when the uid was computed the way the jvm does for classes without one
+    pub explicit_uid: bool,
+}
+
+impl ClassEntry {
+    /// non static, non transient fields, the ones default serialization writes
+    pub fn serial_fields(&self) -> Vec<(&str, &str)> {
+        let pool = &self.class.constant_pool;
+        self.class
+            .fields
+            .iter()
+            .filter(|f| f.access_flags & (ACC_STATIC | ACC_TRANSIENT) == 0)
+            .map(|f| (f.name(pool), f.descriptor(pool)))
+            .collect()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct JarIndex {
+    pub classes: HashMap<String, ClassEntry>,
+    //entries which look like classes but failed to parse, with the reason
+    pub errors: Vec<(String, String)>,
+}
+
+impl JarIndex {
+    pub fn get(&self, name: &str) -> Option<&ClassEntry> {
+        self.classes.get(name)
+    }
+
+    pub fn add_class(&mut self, path: &str, data: &[u8]) {
+        match classfile::parse_class(data) {
+            Ok((_, class)) => {
+                let name = class.class_name().replace('/', ".");
+                let (serial_version_uid, explicit_uid) = match explicit_suid(&class) {
+                    Some(uid) => (uid, true),
+                    None => (compute_default_suid(&class), false),
+                };
+                let entry = ClassEntry {
+                    name: name.clone(),
+                    path: path.to_string(),
+                    class,
+                    serial_version_uid,
+                    explicit_uid,
+                };
+                self.classes.insert(name, entry);
+            }
+            Err(err) => self
+                .errors
+                .push((path.to_string(), format!("{:?}", err.map_input(|i| i.len())))),
+        }
+    }
+}
+
+//a class file past this is not read, the jvm's own limits keep real ones far below
+const MAX_CLASS_SIZE: u64 = 64 << 20;
+
+/// index every class in a jar, module-info and multi-release copies included
+pub fn scan_jar<P: AsRef<Path>>(path: P) -> Result<JarIndex, ZipError> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut index = JarIndex::default();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if !entry.is_file() || !entry.name().ends_with(".class") {
+            continue;
+        }
+        let name = entry.name().to_string();
+        //the size in the header is not to be trusted, the read is capped as well
+        let mut data = Vec::with_capacity(entry.size().min(1 << 20) as usize);
+        if let Err(err) = entry.by_ref().take(MAX_CLASS_SIZE + 1).read_to_end(&mut data) {
+            index.errors.push((name, err.to_string()));
+            continue;
+        }
+        if data.len() as u64 > MAX_CLASS_SIZE {
+            index
+                .errors
+                .push((name, format!("larger than {} bytes", MAX_CLASS_SIZE)));
+            continue;
+        }
+        index.add_class(&name, &data);
+    }
+    Ok(index)
+}
+
+/// value of a `static final long serialVersionUID` field
+pub fn explicit_suid(class: &ClassFile) -> Option<i64> {
+    let pool = &class.constant_pool;
+    let field = class.field("serialVersionUID")?;
+    if !field.has_flag(ACC_STATIC | ACC_FINAL) || field.descriptor(pool) != "J" {
+        return None;
+    }
+    field.attributes.iter().find_map(|attr| match attr {
+        classfile::Attribute::ConstantValue(idx) => match pool.get(*idx)? {
+            Constant::Long(val) => Some(*val),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+//DataOutputStream.writeUTF, length prefixed modified utf8
+fn write_utf(out: &mut Vec<u8>, s: &str) {
+    let mut bytes = vec![];
+    for unit in s.encode_utf16() {
+        match unit {
+            0x0001..=0x007f => bytes.push(unit as u8),
+            0x0000 | 0x0080..=0x07ff => {
+                bytes.push(0xc0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3f) as u8);
+            }
+            _ => {
+                bytes.push(0xe0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3f) as u8);
+                bytes.push(0x80 | (unit & 0x3f) as u8);
+            }
+        }
+    }
+    out.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    out.extend_from_slice(&bytes);
+}
+
+fn write_int(out: &mut Vec<u8>, val: u16) {
+    out.extend_from_slice(&i32::from(val).to_be_bytes());
+}
+
+/// serialVersionUID the jvm assigns to a class which does not declare one,
+/// same algorithm as ObjectStreamClass.computeDefaultSUID
+pub fn compute_default_suid(class: &ClassFile) -> i64 {
+    let pool = &class.constant_pool;
+    let mut out = vec![];
+    write_utf(&mut out, &class.class_name().replace('/', "."));
+
+    let methods: Vec<&MemberInfo> = class
+        .methods
+        .iter()
+        .filter(|m| !m.name(pool).starts_with('<'))
+        .collect();
+    let mut class_mods = class.modifiers() & CLASS_MODS;
+    if class_mods & ACC_INTERFACE != 0 {
+        if methods.is_empty() {
+            class_mods &= !ACC_ABSTRACT;
+        } else {
+            class_mods |= ACC_ABSTRACT;
+        }
+    }
+    write_int(&mut out, class_mods);
+
+    let mut interfaces: Vec<String> = class
+        .interface_names()
+        .iter()
+        .map(|name| name.replace('/', "."))
+        .collect();
+    interfaces.sort();
+    for name in &interfaces {
+        write_utf(&mut out, name);
+    }
+
+    let mut fields: Vec<&MemberInfo> = class.fields.iter().collect();
+    fields.sort_by_key(|f| f.name(pool));
+    for field in fields {
+        let mods = field.access_flags & FIELD_MODS;
+        if mods & ACC_PRIVATE == 0 || mods & (ACC_STATIC | ACC_TRANSIENT) == 0 {
+            write_utf(&mut out, field.name(pool));
+            write_int(&mut out, mods);
+            write_utf(&mut out, field.descriptor(pool));
+        }
+    }
+
+    if class.method("<clinit>", "()V").is_some() {
+        write_utf(&mut out, "<clinit>");
+        write_int(&mut out, ACC_STATIC);
+        write_utf(&mut out, "()V");
+    }
+
+    let mut ctors: Vec<&MemberInfo> = class
+        .methods
+        .iter()
+        .filter(|m| m.name(pool) == "<init>")
+        .collect();
+    ctors.sort_by_key(|m| m.descriptor(pool));
+    for ctor in ctors {
+        let mods = ctor.access_flags & METHOD_MODS;
+        if mods & ACC_PRIVATE == 0 {
+            write_utf(&mut out, "<init>");
+            write_int(&mut out, mods);
+            write_utf(&mut out, &ctor.descriptor(pool).replace('/', "."));
+        }
+    }
+
+    let mut methods = methods;
+    methods.sort_by_key(|m| (m.name(pool), m.descriptor(pool)));
+    for method in methods {
+        let mods = method.access_flags & METHOD_MODS;
+        if mods & ACC_PRIVATE == 0 {
+            write_utf(&mut out, method.name(pool));
+            write_int(&mut out, mods);
+            write_utf(&mut out, &method.descriptor(pool).replace('/', "."));
+        }
+    }
+
+    let digest = sha1::Sha1::from(&out).digest().bytes();
+    let mut head = [0u8; 8];
+    head.copy_from_slice(&digest[..8]);
+    i64::from_le_bytes(head)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mismatch {
+    ClassNotFound(String),
+    SerialVersionUid {
+        class: String,
+        stream: i64,
+        jar: i64,
+    },
+    //in the stream, gone from the jar class
+    MissingField {
+        class: String,
+        field: String,
+    },
+    //added to the jar class, absent in the stream
+    ExtraField {
+        class: String,
+        field: String,
+    },
+    FieldType {
+        class: String,
+        field: String,
+        stream: String,
+        jar: String,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::ClassNotFound(class) => write!(f, "{}: not found in jar", class),
+            Mismatch::SerialVersionUid { class, stream, jar } => write!(
+                f,
+                "{}: serialVersionUID {} in stream, {} in jar, readObject throws InvalidClassException",
+                class, stream, jar
+            ),
+            Mismatch::MissingField { class, field } => {
+                write!(f, "{}.{}: in stream but not in jar, value dropped", class, field)
+            }
+            Mismatch::ExtraField { class, field } => write!(
+                f,
+                "{}.{}: in jar but not in stream, left as default",
+                class, field
+            ),
+            Mismatch::FieldType {
+                class,
+                field,
+                stream,
+                jar,
+            } => write!(
+                f,
+                "{}.{}: {} in stream, {} in jar",
+                class,
+                field,
+                classfile::descriptor_to_java(stream),
+                classfile::descriptor_to_java(jar)
+            ),
+        }
+    }
+}
+
+fn field_sig(type_code: u8, class_name: &Option<String>) -> String {
+    match class_name {
+        Some(name) => name.clone(),
+        None => (type_code as char).to_string(),
+    }
+}
+
+fn check_desc(index: &JarIndex, desc: &ClassDesc, out: &mut Vec<Mismatch>) {
+    let entry = match index.get(&desc.name) {
+        Some(entry) => entry,
+        None => {
+            out.push(Mismatch::ClassNotFound(desc.name.clone()));
+            return;
+        }
+    };
+    if entry.serial_version_uid != desc.serial_version_uid {
+        out.push(Mismatch::SerialVersionUid {
+            class: desc.name.clone(),
+            stream: desc.serial_version_uid,
+            jar: entry.serial_version_uid,
+        });
+    }
+    //externalizable classes write their own format, and serialPersistentFields
+    //replaces the declared fields, neither can be compared by field
+    if desc.has_flag(SC_EXTERNALIZABLE) || entry.class.field("serialPersistentFields").is_some() {
+        return;
+    }
+    let jar_fields: BTreeMap<&str, &str> = entry.serial_fields().into_iter().collect();
+    for field in &desc.fields {
+        let stream_sig = field_sig(field.type_code, &field.class_name);
+        match jar_fields.get(field.name.as_str()) {
+            None => out.push(Mismatch::MissingField {
+                class: desc.name.clone(),
+                field: field.name.clone(),
+            }),
+            Some(jar_sig) if *jar_sig != stream_sig => out.push(Mismatch::FieldType {
+                class: desc.name.clone(),
+                field: field.name.clone(),
+                stream: stream_sig,
+                jar: jar_sig.to_string(),
+            }),
+            _ => {}
+        }
+    }
+    for name in jar_fields.keys() {
+        if !desc.fields.iter().any(|f| f.name == *name) {
+            out.push(Mismatch::ExtraField {
+                class: desc.name.clone(),
+                field: name.to_string(),
+            });
+        }
+    }
+}
+
+/// compare every class described in a serialized stream against the jar,
+/// jdk classes, arrays, enums and proxies are skipped
+pub fn check_stream(index: &JarIndex, buff: &[u8]) -> Result<Vec<Mismatch>, String> {
+    let mut stream = ObjectStream::raw(buff);
+    stream
+        .read_header()
+        .and_then(|_| stream.read_all())
+        .map_err(|err| format!("{:?}", err.map_input(|i| i.len())))?;
+    let mut out = vec![];
+    let mut seen = vec![];
+    for desc in stream.class_descs() {
+        if desc.name.is_empty()
+            || desc.name.starts_with("java.")
+            || desc.name.starts_with("javax.")
+            || desc.name.starts_with('[')
+            || desc.has_flag(SC_ENUM)
+            || !desc.proxy_interfaces.is_empty()
+            || seen.contains(&desc.name)
+        {
+            continue;
+        }
+        seen.push(desc.name.clone());
+        check_desc(index, desc, &mut out);
+    }
+    Ok(out)
+}
+
+#[test]
+fn test_scan_jar_suid() {
+    let index = scan_jar("testdata/demo.jar").unwrap();
+    assert!(index.errors.is_empty());
+    assert_eq!(4, index.classes.len());
+
+    let sample = index.get("Sample").unwrap();
+    assert!(!sample.explicit_uid);
+    assert_eq!(-385088713925040979, sample.serial_version_uid);
+    let entry = index.get("demo.Account$Entry").unwrap();
+    assert_eq!("demo/Account$Entry.class", entry.path);
+    assert_eq!(-6781484581045324406, entry.serial_version_uid);
+    let account = index.get("demo.Account").unwrap();
+    assert!(account.explicit_uid);
+    assert_eq!(2, account.serial_version_uid);
+}
+
+#[test]
+fn test_check_stream_against_jar() {
+    let index = scan_jar("testdata/demo.jar").unwrap();
+    let ser = std::fs::read("testdata/account_v1.ser").unwrap();
+    let report = check_stream(&index, &ser).unwrap();
+    let account = "demo.Account".to_string();
+    let entry = "demo.Account$Entry".to_string();
+    assert!(report.contains(&Mismatch::SerialVersionUid {
+        class: account.clone(),
+        stream: 1,
+        jar: 2
+    }));
+    assert!(report.contains(&Mismatch::FieldType {
+        class: account.clone(),
+        field: "balance".into(),
+        stream: "J".into(),
+        jar: "I".into()
+    }));
+    assert!(report.contains(&Mismatch::MissingField {
+        class: account.clone(),
+        field: "level".into()
+    }));
+    assert!(report.contains(&Mismatch::ExtraField {
+        class: account,
+        field: "email".into()
+    }));
+    assert!(report.contains(&Mismatch::ExtraField {
+        class: entry.clone(),
+        field: "memo".into()
+    }));
+    assert!(report
+        .iter()
+        .any(|m| matches!(m, Mismatch::SerialVersionUid { class, .. } if *class == entry)));
+    assert_eq!(
+        "demo.Account.balance: long in stream, int in jar",
+        report
+            .iter()
+            .find(|m| matches!(m, Mismatch::FieldType { .. }))
+            .unwrap()
+            .to_string()
+    );
+}
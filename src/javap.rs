@@ -0,0 +1,1036 @@
+use std::fmt::Write;
+
+use crate::classfile::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    None,
+    //bipush, sipush
+    Value(i32),
+    //load/store/ret, wide ones included
+    Local(u16),
+    //ldc, field/method refs, new, checkcast ...
+    Cp(u16),
+    //absolute branch target
+    Branch(u32),
+    //local index, increment
+    Iinc(u16, i16),
+    //interface method ref, arg count
+    InvokeInterface(u16, u8),
+    InvokeDynamic(u16),
+    //class ref, dimensions
+    MultiANewArray(u16, u8),
+    //primitive array type code
+    NewArray(u8),
+    //default target, low, high
+    TableSwitch(u32, i32, i32),
+    //default target
+    LookupSwitch(u32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub pc: u32,
+    pub opcode: u8,
+    //true for instructions prefixed by wide
+    pub wide: bool,
+    pub operand: Operand,
+    //switch cases as (key, absolute target)
+    pub cases: Vec<(i32, u32)>,
+}
+
+impl Instruction {
+    pub fn mnemonic(&self) -> String {
+        let name = MNEMONICS
+            .get(self.opcode as usize)
+            .copied()
+            .unwrap_or("undefined");
+        if self.wide {
+            format!("{}_w", name)
+        } else {
+            name.to_string()
+        }
+    }
+}
+
+const MNEMONICS: [&str; 202] = [
+    "nop",
+    "aconst_null",
+    "iconst_m1",
+    "iconst_0",
+    "iconst_1",
+    "iconst_2",
+    "iconst_3",
+    "iconst_4",
+    "iconst_5",
+    "lconst_0",
+    "lconst_1",
+    "fconst_0",
+    "fconst_1",
+    "fconst_2",
+    "dconst_0",
+    "dconst_1",
+    "bipush",
+    "sipush",
+    "ldc",
+    "ldc_w",
+    "ldc2_w",
+    "iload",
+    "lload",
+    "fload",
+    "dload",
+    "aload",
+    "iload_0",
+    "iload_1",
+    "iload_2",
+    "iload_3",
+    "lload_0",
+    "lload_1",
+    "lload_2",
+    "lload_3",
+    "fload_0",
+    "fload_1",
+    "fload_2",
+    "fload_3",
+    "dload_0",
+    "dload_1",
+    "dload_2",
+    "dload_3",
+    "aload_0",
+    "aload_1",
+    "aload_2",
+    "aload_3",
+    "iaload",
+    "laload",
+    "faload",
+    "daload",
+    "aaload",
+    "baload",
+    "caload",
+    "saload",
+    "istore",
+    "lstore",
+    "fstore",
+    "dstore",
+    "astore",
+    "istore_0",
+    "istore_1",
+    "istore_2",
+    "istore_3",
+    "lstore_0",
+    "lstore_1",
+    "lstore_2",
+    "lstore_3",
+    "fstore_0",
+    "fstore_1",
+    "fstore_2",
+    "fstore_3",
+    "dstore_0",
+    "dstore_1",
+    "dstore_2",
+    "dstore_3",
+    "astore_0",
+    "astore_1",
+    "astore_2",
+    "astore_3",
+    "iastore",
+    "lastore",
+    "fastore",
+    "dastore",
+    "aastore",
+    "bastore",
+    "castore",
+    "sastore",
+    "pop",
+    "pop2",
+    "dup",
+    "dup_x1",
+    "dup_x2",
+    "dup2",
+    "dup2_x1",
+    "dup2_x2",
+    "swap",
+    "iadd",
+    "ladd",
+    "fadd",
+    "dadd",
+    "isub",
+    "lsub",
+    "fsub",
+    "dsub",
+    "imul",
+    "lmul",
+    "fmul",
+    "dmul",
+    "idiv",
+    "ldiv",
+    "fdiv",
+    "ddiv",
+    "irem",
+    "lrem",
+    "frem",
+    "drem",
+    "ineg",
+    "lneg",
+    "fneg",
+    "dneg",
+    "ishl",
+    "lshl",
+    "ishr",
+    "lshr",
+    "iushr",
+    "lushr",
+    "iand",
+    "land",
+    "ior",
+    "lor",
+    "ixor",
+    "lxor",
+    "iinc",
+    "i2l",
+    "i2f",
+    "i2d",
+    "l2i",
+    "l2f",
+    "l2d",
+    "f2i",
+    "f2l",
+    "f2d",
+    "d2i",
+    "d2l",
+    "d2f",
+    "i2b",
+    "i2c",
+    "i2s",
+    "lcmp",
+    "fcmpl",
+    "fcmpg",
+    "dcmpl",
+    "dcmpg",
+    "ifeq",
+    "ifne",
+    "iflt",
+    "ifge",
+    "ifgt",
+    "ifle",
+    "if_icmpeq",
+    "if_icmpne",
+    "if_icmplt",
+    "if_icmpge",
+    "if_icmpgt",
+    "if_icmple",
+    "if_acmpeq",
+    "if_acmpne",
+    "goto",
+    "jsr",
+    "ret",
+    "tableswitch",
+    "lookupswitch",
+    "ireturn",
+    "lreturn",
+    "freturn",
+    "dreturn",
+    "areturn",
+    "return",
+    "getstatic",
+    "putstatic",
+    "getfield",
+    "putfield",
+    "invokevirtual",
+    "invokespecial",
+    "invokestatic",
+    "invokeinterface",
+    "invokedynamic",
+    "new",
+    "newarray",
+    "anewarray",
+    "arraylength",
+    "athrow",
+    "checkcast",
+    "instanceof",
+    "monitorenter",
+    "monitorexit",
+    "wide",
+    "multianewarray",
+    "ifnull",
+    "ifnonnull",
+    "goto_w",
+    "jsr_w",
+];
+
+const WIDE: u8 = 0xc4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodeError {
+    pub pc: u32,
+    pub message: String,
+}
+
+struct CodeReader<'a> {
+    code: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> CodeReader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        match self.code.get(self.pos..self.pos + len) {
+            Some(bytes) => {
+                self.pos += len;
+                Ok(bytes)
+            }
+            None => Err(DecodeError {
+                pc: self.pos as u32,
+                message: String::from("truncated instruction"),
+            }),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, DecodeError> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn i32(&mut self) -> Result<i32, DecodeError> {
+        let b = self.bytes(4)?;
+        Ok(i32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
+
+fn target(pc: u32, offset: i32) -> u32 {
+    (pc as i64 + offset as i64) as u32
+}
+
+/// decode the bytecode of a Code attribute into instructions
+pub fn decode(code: &[u8]) -> Result<Vec<Instruction>, DecodeError> {
+    let mut reader = CodeReader { code, pos: 0 };
+    let mut insns = vec![];
+    while reader.pos < code.len() {
+        let pc = reader.pos as u32;
+        let mut opcode = reader.u8()?;
+        let mut wide = false;
+        let mut cases = vec![];
+        if opcode == WIDE {
+            opcode = reader.u8()?;
+            wide = true;
+        }
+        let operand = match opcode {
+            0x10 => Operand::Value(reader.u8()? as i8 as i32),
+            0x11 => Operand::Value(reader.u16()? as i16 as i32),
+            0x12 => Operand::Cp(reader.u8()? as u16),
+            0x13 | 0x14 | 0xb2..=0xb8 | 0xbb | 0xbd | 0xc0 | 0xc1 => Operand::Cp(reader.u16()?),
+            0x15..=0x19 | 0x36..=0x3a | 0xa9 => {
+                if wide {
+                    Operand::Local(reader.u16()?)
+                } else {
+                    Operand::Local(reader.u8()? as u16)
+                }
+            }
+            0x84 => {
+                if wide {
+                    Operand::Iinc(reader.u16()?, reader.u16()? as i16)
+                } else {
+                    Operand::Iinc(reader.u8()? as u16, reader.u8()? as i8 as i16)
+                }
+            }
+            0x99..=0xa8 | 0xc6 | 0xc7 => Operand::Branch(target(pc, reader.u16()? as i16 as i32)),
+            0xc8 | 0xc9 => Operand::Branch(target(pc, reader.i32()?)),
+            0xaa | 0xab => {
+                //operands are 4 byte aligned from the start of the method
+                reader.bytes((4 - reader.pos % 4) % 4)?;
+                let default = target(pc, reader.i32()?);
+                if opcode == 0xaa {
+                    let low = reader.i32()?;
+                    let high = reader.i32()?;
+                    for key in low..=high {
+                        cases.push((key, target(pc, reader.i32()?)));
+                    }
+                    Operand::TableSwitch(default, low, high)
+                } else {
+                    let pairs = reader.i32()?;
+                    for _ in 0..pairs {
+                        let key = reader.i32()?;
+                        cases.push((key, target(pc, reader.i32()?)));
+                    }
+                    Operand::LookupSwitch(default)
+                }
+            }
+            0xb9 => {
+                let idx = reader.u16()?;
+                let count = reader.u8()?;
+                reader.u8()?;
+                Operand::InvokeInterface(idx, count)
+            }
+            0xba => {
+                let idx = reader.u16()?;
+                reader.u16()?;
+                Operand::InvokeDynamic(idx)
+            }
+            0xbc => Operand::NewArray(reader.u8()?),
+            0xc5 => Operand::MultiANewArray(reader.u16()?, reader.u8()?),
+            0x00..=0xc9 => Operand::None,
+            _ => {
+                return Err(DecodeError {
+                    pc,
+                    message: format!("unknown opcode {:#x}", opcode),
+                })
+            }
+        };
+        insns.push(Instruction {
+            pc,
+            opcode,
+            wide,
+            operand,
+            cases,
+        });
+    }
+    Ok(insns)
+}
+
+fn array_type(code: u8) -> &'static str {
+    match code {
+        4 => "boolean",
+        5 => "char",
+        6 => "float",
+        7 => "double",
+        8 => "byte",
+        9 => "short",
+        10 => "int",
+        11 => "long",
+        _ => "?",
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            '"' => out.push_str("\\\""),
+            '\'' => out.push_str("\\'"),
+            '\\' => out.push_str("\\\\"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn quote_name(name: &str) -> String {
+    if name.starts_with('<') || name.starts_with('[') {
+        format!("\"{}\"", name)
+    } else {
+        name.to_string()
+    }
+}
+
+const REF_KINDS: [&str; 10] = [
+    "",
+    "REF_getField",
+    "REF_getStatic",
+    "REF_putField",
+    "REF_putStatic",
+    "REF_invokeVirtual",
+    "REF_invokeStatic",
+    "REF_invokeSpecial",
+    "REF_newInvokeSpecial",
+    "REF_invokeInterface",
+];
+
+/// javap comment text of a constant pool entry referenced by an instruction
+fn constant_desc(pool: &ConstantPool, idx: u16, this_class: &str) -> String {
+    let member = |kind: &str| match pool.member_ref(idx) {
+        Some((class, name, desc)) if class == this_class => {
+            format!("{} {}:{}", kind, quote_name(name), desc)
+        }
+        Some((class, name, desc)) => {
+            format!(
+                "{} {}.{}:{}",
+                kind,
+                quote_name(class),
+                quote_name(name),
+                desc
+            )
+        }
+        None => format!("{} ?", kind),
+    };
+    match pool.get(idx) {
+        Some(Constant::Utf8(val)) => format!("utf8 {}", escape(val)),
+        Some(Constant::Integer(val)) => format!("int {}", val),
+        Some(Constant::Float(val)) => format!("float {:?}f", val),
+        Some(Constant::Long(val)) => format!("long {}l", val),
+        Some(Constant::Double(val)) => format!("double {:?}d", val),
+        Some(Constant::Class(_)) => {
+            format!("class {}", quote_name(pool.class_name(idx).unwrap_or("?")))
+        }
+        Some(Constant::Str(val)) => format!("String {}", escape(pool.utf8(*val).unwrap_or(""))),
+        Some(Constant::FieldRef(_, _)) => member("Field"),
+        Some(Constant::MethodRef(_, _)) => member("Method"),
+        Some(Constant::InterfaceMethodRef(_, _)) => member("InterfaceMethod"),
+        Some(Constant::NameAndType(_, _)) => match pool.name_and_type(idx) {
+            Some((name, desc)) => format!("NameAndType {}:{}", quote_name(name), desc),
+            None => String::from("NameAndType ?"),
+        },
+        Some(Constant::MethodHandle(kind, reference)) => format!(
+            "MethodHandle {}:{}",
+            REF_KINDS.get(*kind as usize).unwrap_or(&"?"),
+            constant_desc(pool, *reference, this_class)
+        ),
+        Some(Constant::MethodType(desc)) => {
+            format!("MethodType {}", pool.utf8(*desc).unwrap_or("?"))
+        }
+        Some(Constant::Dynamic(bsm, nat)) | Some(Constant::InvokeDynamic(bsm, nat)) => {
+            let kind = match pool.get(idx) {
+                Some(Constant::Dynamic(_, _)) => "Dynamic",
+                _ => "InvokeDynamic",
+            };
+            match pool.name_and_type(*nat) {
+                Some((name, desc)) => format!("{} #{}:{}:{}", kind, bsm, name, desc),
+                None => format!("{} #{}:?", kind, bsm),
+            }
+        }
+        Some(Constant::Module(name)) => format!("Module {}", pool.utf8(*name).unwrap_or("?")),
+        Some(Constant::Package(name)) => format!("Package {}", pool.utf8(*name).unwrap_or("?")),
+        Some(Constant::Unusable) | None => format!("invalid #{}", idx),
+    }
+}
+
+//comments start at this column, like javap's tab column of 40 plus the code indent
+const COMMENT_COLUMN: usize = 44;
+
+fn write_instruction(out: &mut String, insn: &Instruction, pool: &ConstantPool, this_class: &str) {
+    let mut line = format!("{:>8}: {}", insn.pc, insn.mnemonic());
+    //mnemonics are left aligned in 13 columns
+    let pad = |line: &mut String| {
+        while line.len() < 23 {
+            line.push(' ');
+        }
+        line.push(' ');
+    };
+    let comment = |line: &mut String, text: String| {
+        while line.len() < COMMENT_COLUMN {
+            line.push(' ');
+        }
+        if !line.ends_with(' ') {
+            line.push(' ');
+        }
+        line.push_str("// ");
+        line.push_str(&text);
+    };
+    match insn.operand {
+        Operand::None => {}
+        Operand::Value(val) => {
+            pad(&mut line);
+            line.push_str(&val.to_string());
+        }
+        Operand::Local(idx) => {
+            pad(&mut line);
+            line.push_str(&idx.to_string());
+        }
+        Operand::Branch(target) => {
+            pad(&mut line);
+            line.push_str(&target.to_string());
+        }
+        Operand::Iinc(idx, inc) => {
+            pad(&mut line);
+            let _ = write!(line, "{}, {}", idx, inc);
+        }
+        Operand::NewArray(code) => {
+            pad(&mut line);
+            let _ = write!(line, " {}", array_type(code));
+        }
+        Operand::Cp(idx) => {
+            pad(&mut line);
+            let _ = write!(line, "#{}", idx);
+            comment(&mut line, constant_desc(pool, idx, this_class));
+        }
+        Operand::InvokeInterface(idx, count) => {
+            pad(&mut line);
+            let _ = write!(line, "#{},  {}", idx, count);
+            comment(&mut line, constant_desc(pool, idx, this_class));
+        }
+        Operand::InvokeDynamic(idx) => {
+            pad(&mut line);
+            let _ = write!(line, "#{},  0", idx);
+            comment(&mut line, constant_desc(pool, idx, this_class));
+        }
+        Operand::MultiANewArray(idx, dims) => {
+            pad(&mut line);
+            let _ = write!(line, "#{},  {}", idx, dims);
+            comment(&mut line, constant_desc(pool, idx, this_class));
+        }
+        Operand::TableSwitch(default, low, high) => {
+            pad(&mut line);
+            let _ = write!(line, "{{ // {} to {}", low, high);
+            write_cases(&mut line, &insn.cases, default);
+        }
+        Operand::LookupSwitch(default) => {
+            pad(&mut line);
+            let _ = write!(line, "{{ // {}", insn.cases.len());
+            write_cases(&mut line, &insn.cases, default);
+        }
+    }
+    out.push_str(&line);
+    out.push('\n');
+}
+
+fn write_cases(line: &mut String, cases: &[(i32, u32)], default: u32) {
+    for (key, target) in cases {
+        let _ = write!(line, "\n{:>22}: {}", key, target);
+    }
+    let _ = write!(line, "\n{:>22}: {}", "default", default);
+    line.push_str("\n          }");
+}
+
+pub struct Options {
+    //show private members like javap -p
+    pub private: bool,
+    //print LineNumberTable like javap -l
+    pub line_numbers: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            private: true,
+            line_numbers: false,
+        }
+    }
+}
+
+/// generic signature to java source form, reading a single type
+struct SignatureReader<'s> {
+    sig: &'s str,
+}
+
+impl<'s> SignatureReader<'s> {
+    fn peek(&self) -> Option<char> {
+        self.sig.chars().next()
+    }
+
+    //signatures come from modified utf-8, a char may take more than one byte
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.sig = &self.sig[c.len_utf8()..];
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn until(&mut self, stops: &[char]) -> &'s str {
+        let end = self
+            .sig
+            .find(|c| stops.contains(&c))
+            .unwrap_or(self.sig.len());
+        let (head, tail) = self.sig.split_at(end);
+        self.sig = tail;
+        head
+    }
+
+    fn type_sig(&mut self) -> Option<String> {
+        match self.peek()? {
+            'L' => {
+                self.bump();
+                let mut name = self.until(&['<', ';', '.']).replace('/', ".");
+                loop {
+                    if self.eat('<') {
+                        let mut args = vec![];
+                        while !self.eat('>') {
+                            args.push(self.type_arg()?);
+                        }
+                        let _ = write!(name, "<{}>", args.join(", "));
+                    } else if self.eat('.') {
+                        name.push('.');
+                        name.push_str(self.until(&['<', ';', '.']));
+                    } else if self.eat(';') {
+                        return Some(name);
+                    } else {
+                        return None;
+                    }
+                }
+            }
+            'T' => {
+                self.bump();
+                let name = self.until(&[';']).to_string();
+                self.eat(';');
+                Some(name)
+            }
+            '[' => {
+                self.bump();
+                Some(format!("{}[]", self.type_sig()?))
+            }
+            'B' | 'C' | 'D' | 'F' | 'I' | 'J' | 'S' | 'Z' | 'V' => {
+                let c = self.bump()?;
+                Some(descriptor_to_java(c.encode_utf8(&mut [0; 4])))
+            }
+            _ => None,
+        }
+    }
+
+    fn type_arg(&mut self) -> Option<String> {
+        if self.eat('*') {
+            Some(String::from("?"))
+        } else if self.eat('+') {
+            Some(format!("? extends {}", self.type_sig()?))
+        } else if self.eat('-') {
+            Some(format!("? super {}", self.type_sig()?))
+        } else {
+            self.type_sig()
+        }
+    }
+
+    fn type_params(&mut self) -> Option<String> {
+        if !self.eat('<') {
+            return Some(String::new());
+        }
+        let mut params = vec![];
+        while !self.eat('>') {
+            let name = self.until(&[':']).to_string();
+            //every parameter has a bound, a missing one means the signature ended early
+            if name.is_empty() || self.peek() != Some(':') {
+                return None;
+            }
+            let mut bounds = vec![];
+            while self.eat(':') {
+                //the class bound may be empty when there are only interface bounds
+                if self.peek() != Some(':') {
+                    bounds.push(self.type_sig()?);
+                }
+            }
+            bounds.retain(|b| b != "java.lang.Object");
+            if bounds.is_empty() {
+                params.push(name);
+            } else {
+                params.push(format!("{} extends {}", name, bounds.join(" & ")));
+            }
+        }
+        Some(format!("<{}>", params.join(", ")))
+    }
+}
+
+struct MethodSig {
+    type_params: String,
+    params: Vec<String>,
+    ret: String,
+    throws: Vec<String>,
+}
+
+fn method_sig(sig: &str) -> Option<MethodSig> {
+    let mut reader = SignatureReader { sig };
+    let type_params = reader.type_params()?;
+    if !reader.eat('(') {
+        return None;
+    }
+    let mut params = vec![];
+    while !reader.eat(')') {
+        params.push(reader.type_sig()?);
+    }
+    let ret = reader.type_sig()?;
+    let mut throws = vec![];
+    while reader.eat('^') {
+        throws.push(reader.type_sig()?);
+    }
+    Some(MethodSig {
+        type_params,
+        params,
+        ret,
+        throws,
+    })
+}
+
+fn modifiers(flags: u16, table: &[(u16, &str)]) -> String {
+    table
+        .iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, name)| format!("{} ", name))
+        .collect()
+}
+
+const FIELD_MODIFIERS: [(u16, &str); 7] = [
+    (ACC_PUBLIC, "public"),
+    (ACC_PRIVATE, "private"),
+    (ACC_PROTECTED, "protected"),
+    (ACC_STATIC, "static"),
+    (ACC_FINAL, "final"),
+    (ACC_VOLATILE, "volatile"),
+    (ACC_TRANSIENT, "transient"),
+];
+
+const METHOD_MODIFIERS: [(u16, &str); 9] = [
+    (ACC_PUBLIC, "public"),
+    (ACC_PRIVATE, "private"),
+    (ACC_PROTECTED, "protected"),
+    (ACC_STATIC, "static"),
+    (ACC_FINAL, "final"),
+    (ACC_SYNCHRONIZED, "synchronized"),
+    (ACC_NATIVE, "native"),
+    (ACC_ABSTRACT, "abstract"),
+    (ACC_STRICT, "strictfp"),
+];
+
+fn class_header(class: &ClassFile) -> String {
+    let interface = class.has_flag(ACC_INTERFACE);
+    let mut flags = class.access_flags & (ACC_PUBLIC | ACC_FINAL | ACC_ABSTRACT);
+    if interface {
+        flags &= !ACC_ABSTRACT;
+    }
+    let mut header = modifiers(
+        flags,
+        &[
+            (ACC_PUBLIC, "public"),
+            (ACC_FINAL, "final"),
+            (ACC_ABSTRACT, "abstract"),
+        ],
+    );
+    header.push_str(if interface { "interface " } else { "class " });
+    header.push_str(&class.class_name().replace('/', "."));
+
+    let (type_params, super_class, mut interfaces) = match class.signature() {
+        Some(sig) => {
+            let mut reader = SignatureReader { sig };
+            let type_params = reader.type_params().unwrap_or_default();
+            let super_class = reader.type_sig();
+            let mut interfaces = vec![];
+            while let Some(name) = reader.type_sig() {
+                interfaces.push(name);
+            }
+            (type_params, super_class, interfaces)
+        }
+        None => (
+            String::new(),
+            class.super_class_name().map(|name| name.replace('/', ".")),
+            class
+                .interface_names()
+                .iter()
+                .map(|name| name.replace('/', "."))
+                .collect(),
+        ),
+    };
+    header.push_str(&type_params);
+    if interface {
+        //interfaces list their super interfaces with extends
+        interfaces.retain(|name| name != "java.lang.Object");
+        if !interfaces.is_empty() {
+            let _ = write!(header, " extends {}", interfaces.join(", "));
+        }
+        return header;
+    }
+    if let Some(name) = super_class.filter(|name| name != "java.lang.Object") {
+        let _ = write!(header, " extends {}", name);
+    }
+    if !interfaces.is_empty() {
+        let _ = write!(header, " implements {}", interfaces.join(", "));
+    }
+    header
+}
+
+fn field_decl(class: &ClassFile, field: &MemberInfo) -> String {
+    let pool = &class.constant_pool;
+    let field_type = match field.signature(pool) {
+        Some(sig) => SignatureReader { sig }.type_sig(),
+        None => None,
+    }
+    .unwrap_or_else(|| descriptor_to_java(field.descriptor(pool)));
+    format!(
+        "{}{} {};",
+        modifiers(field.access_flags, &FIELD_MODIFIERS),
+        field_type,
+        field.name(pool)
+    )
+}
+
+fn method_decl(class: &ClassFile, method: &MemberInfo) -> String {
+    let pool = &class.constant_pool;
+    let name = method.name(pool);
+    if name == "<clinit>" {
+        return String::from("static {};");
+    }
+    let mut flags = method.access_flags;
+    let mut decl = String::new();
+    if class.has_flag(ACC_INTERFACE)
+        && !method.has_flag(ACC_ABSTRACT)
+        && !method.has_flag(ACC_STATIC)
+        && method.has_flag(ACC_PUBLIC)
+    {
+        flags &= !ACC_PUBLIC;
+        decl.push_str("public default ");
+    }
+    decl.push_str(&modifiers(flags, &METHOD_MODIFIERS));
+
+    let erased = method_descriptor(method.descriptor(pool));
+    let sig = method.signature(pool).and_then(method_sig);
+    let (type_params, mut params, ret, mut throws) = match (sig, erased) {
+        (Some(sig), _) => (sig.type_params, sig.params, sig.ret, sig.throws),
+        (None, Some((params, ret))) => (String::new(), params, ret, vec![]),
+        (None, None) => (String::new(), vec![], String::from("?"), vec![]),
+    };
+    if throws.is_empty() {
+        throws = method
+            .attributes
+            .iter()
+            .find_map(|attr| match attr {
+                Attribute::Exceptions(list) => Some(
+                    list.iter()
+                        .filter_map(|idx| pool.class_name(*idx))
+                        .map(|name| name.replace('/', "."))
+                        .collect(),
+                ),
+                _ => None,
+            })
+            .unwrap_or_default();
+    }
+    //ACC_VARARGS shares its bit with ACC_TRANSIENT
+    if method.has_flag(ACC_TRANSIENT) {
+        if let Some(last) = params.last_mut() {
+            if last.ends_with("[]") {
+                last.truncate(last.len() - 2);
+                last.push_str("...");
+            }
+        }
+    }
+    if !type_params.is_empty() {
+        decl.push_str(&type_params);
+        decl.push(' ');
+    }
+    if name == "<init>" {
+        decl.push_str(&class.class_name().replace('/', "."));
+    } else {
+        let _ = write!(decl, "{} {}", ret, name);
+    }
+    let _ = write!(decl, "({})", params.join(", "));
+    if !throws.is_empty() {
+        let _ = write!(decl, " throws {}", throws.join(", "));
+    }
+    decl.push(';');
+    decl
+}
+
+fn write_code(out: &mut String, class: &ClassFile, code: &Code, opts: &Options) {
+    let pool = &class.constant_pool;
+    out.push_str("    Code:\n");
+    match decode(&code.code) {
+        Ok(insns) => {
+            for insn in insns.iter() {
+                write_instruction(out, insn, pool, class.class_name());
+            }
+        }
+        Err(err) => {
+            let _ = writeln!(out, "      error at {}: {}", err.pc, err.message);
+        }
+    }
+    if !code.exception_table.is_empty() {
+        out.push_str("    Exception table:\n");
+        out.push_str("       from    to  target type\n");
+        for entry in code.exception_table.iter() {
+            let catch_type = if entry.catch_type == 0 {
+                String::from("any")
+            } else {
+                format!("Class {}", pool.class_name(entry.catch_type).unwrap_or("?"))
+            };
+            let _ = writeln!(
+                out,
+                "{:>12}{:>6}{:>6}   {}",
+                entry.start_pc, entry.end_pc, entry.handler_pc, catch_type
+            );
+        }
+    }
+    if opts.line_numbers {
+        let lines = code.line_numbers();
+        if !lines.is_empty() {
+            out.push_str("    LineNumberTable:\n");
+            for line in lines {
+                let _ = writeln!(out, "      line {}: {}", line.line, line.start_pc);
+            }
+        }
+    }
+}
+
+/// render the class like javap -c, plus -p and -l according to the options
+pub fn disassemble(class: &ClassFile, opts: &Options) -> String {
+    let mut out = String::new();
+    if let Some(source) = class.source_file() {
+        let _ = writeln!(out, "Compiled from \"{}\"", source);
+    }
+    let _ = writeln!(out, "{} {{", class_header(class));
+    let visible = |m: &&MemberInfo| opts.private || !m.has_flag(ACC_PRIVATE);
+    let mut members = vec![];
+    for field in class.fields.iter().filter(visible) {
+        members.push(format!("  {}\n", field_decl(class, field)));
+    }
+    for method in class.methods.iter().filter(visible) {
+        let mut text = format!("  {}\n", method_decl(class, method));
+        if let Some(code) = method.code() {
+            write_code(&mut text, class, code, opts);
+        }
+        members.push(text);
+    }
+    out.push_str(&members.join("\n"));
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+fn assert_same_as_javap(class_file: &str, javap_file: &str) {
+    let buf = std::fs::read(class_file).unwrap();
+    let (_, class) = parse_class(&buf).unwrap();
+    let expected = std::fs::read_to_string(javap_file).unwrap();
+    let actual = disassemble(&class, &Options::default());
+    for (exp, act) in expected.lines().zip(actual.lines()) {
+        assert_eq!(exp, act);
+    }
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn test_disassemble_like_javap() {
+    assert_same_as_javap("testdata/Sample.class", "testdata/Sample.javap");
+    assert_same_as_javap("testdata/Sample$Inner.class", "testdata/Sample$Inner.javap");
+    assert_same_as_javap("testdata/Shapes.class", "testdata/Shapes.javap");
+    assert_same_as_javap("testdata/Shapes$Kind.class", "testdata/Shapes$Kind.javap");
+}
+
+#[test]
+fn test_decode_switch_and_lines() {
+    let buf = std::fs::read("testdata/Sample.class").unwrap();
+    let (_, class) = parse_class(&buf).unwrap();
+    let code = class.method("classify", "(I)I").unwrap().code().unwrap();
+    let insns = decode(&code.code).unwrap();
+    assert_eq!(Operand::TableSwitch(37, 1, 3), insns[1].operand);
+    assert_eq!(vec![(1, 28), (2, 31), (3, 34)], insns[1].cases);
+
+    let text = disassemble(
+        &class,
+        &Options {
+            private: false,
+            line_numbers: true,
+        },
+    );
+    assert!(text.contains("    LineNumberTable:\n      line "));
+    assert!(!text.contains("private"));
+}
+
+#[test]
+fn test_non_ascii_signatures() {
+    let sig = method_sig("<T:Ljava/lang/Object;>(Lé/Ça<TT;>;Tβ;)[Lπ;").unwrap();
+    assert_eq!(vec!["é.Ça<T>", "β"], sig.params);
+    assert_eq!("π[]", sig.ret);
+    assert!(method_sig("(é)V").is_none());
+    assert!(SignatureReader { sig: "ü" }.type_sig().is_none());
+    for truncated in ["<T", "<T>", "<T:", "<T:Ljava/lang/Object;", "<:>"] {
+        assert!(SignatureReader { sig: truncated }.type_params().is_none());
+    }
+    assert!(method_sig("<T:Ljava/lang/Object;>(TT;").is_none());
+}
@@ -0,0 +1,1035 @@
+use nom::bytes::complete::take;
+use nom::error::{make_error, ErrorKind};
+use nom::number::complete::{be_f32, be_f64, be_i16, be_i32, be_i64, be_i8, be_u16, be_u32, be_u8};
+use nom::Err;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub const STREAM_MAGIC: u16 = 0xaced;
+pub const STREAM_VERSION: u16 = 5;
+
+const TC_NULL: u8 = 0x70;
+const TC_REFERENCE: u8 = 0x71;
+const TC_CLASSDESC: u8 = 0x72;
+const TC_OBJECT: u8 = 0x73;
+const TC_STRING: u8 = 0x74;
+const TC_ARRAY: u8 = 0x75;
+const TC_CLASS: u8 = 0x76;
+const TC_BLOCKDATA: u8 = 0x77;
+const TC_ENDBLOCKDATA: u8 = 0x78;
+const TC_RESET: u8 = 0x79;
+const TC_BLOCKDATALONG: u8 = 0x7a;
+const TC_EXCEPTION: u8 = 0x7b;
+const TC_LONGSTRING: u8 = 0x7c;
+const TC_PROXYCLASSDESC: u8 = 0x7d;
+const TC_ENUM: u8 = 0x7e;
+
+const BASE_WIRE_HANDLE: u32 = 0x7e0000;
+
+//objects nested deeper are refused, each level is a few frames of recursion
+const MAX_DEPTH: u32 = 256;
+//bytes of strings back references may copy, a short reference can name a long string
+const MAX_REF_COPY: usize = 16 << 20;
+
+pub const SC_WRITE_METHOD: u8 = 0x01;
+pub const SC_SERIALIZABLE: u8 = 0x02;
+pub const SC_EXTERNALIZABLE: u8 = 0x04;
+pub const SC_BLOCK_DATA: u8 = 0x08;
+pub const SC_ENUM: u8 = 0x10;
+
+pub type ParseErr<'a> = Err<(&'a [u8], ErrorKind)>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDesc {
+    pub type_code: u8,
+    pub name: String,
+    //field type signature for object and array fields, eg Ljava/lang/String;
+    pub class_name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassDesc {
+    pub name: String,
+    pub serial_version_uid: i64,
+    pub flags: u8,
+    pub fields: Vec<FieldDesc>,
+    pub super_class: Option<Rc<ClassDesc>>,
+    //interface names of a dynamic proxy class
+    pub proxy_interfaces: Vec<String>,
+}
+
+impl ClassDesc {
+    /// class chain ordered from the top most serializable super class down to this class,
+    /// which is the order class data is written in
+    pub fn hierarchy(&self) -> Vec<&ClassDesc> {
+        let mut chain = vec![self];
+        let mut cur = self;
+        while let Some(sup) = &cur.super_class {
+            chain.push(sup);
+            cur = sup;
+        }
+        chain.reverse();
+        chain
+    }
+
+    pub fn has_flag(&self, flag: u8) -> bool {
+        self.flags & flag == flag
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassData {
+    pub class_name: String,
+    pub fields: Vec<(String, Content)>,
+    //whatever the custom writeObject/writeExternal put after the default fields
+    pub annotation: Vec<Content>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JavaObject {
+    pub class_desc: Rc<ClassDesc>,
+    pub class_data: Vec<ClassData>,
+}
+
+impl JavaObject {
+    pub fn class_name(&self) -> &str {
+        &self.class_desc.name
+    }
+
+    pub fn data_of(&self, class_name: &str) -> Option<&ClassData> {
+        self.class_data.iter().find(|d| d.class_name == class_name)
+    }
+
+    /// lookup a default field, searching from the most derived class upward
+    pub fn field(&self, name: &str) -> Option<&Content> {
+        self.class_data
+            .iter()
+            .rev()
+            .flat_map(|d| d.fields.iter())
+            .find(|(field, _)| field == name)
+            .map(|(_, val)| val)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Content {
+    Null,
+    Byte(i8),
+    Char(u16),
+    Double(f64),
+    Float(f32),
+    Integer(i32),
+    Long(i64),
+    Short(i16),
+    Boolean(bool),
+    Str(String),
+    Class(Rc<ClassDesc>),
+    Enum(String, String),
+    Array(String, Vec<Content>),
+    Object(JavaObject),
+    BlockData(Vec<u8>),
+    //back reference to an object, array or collection, which may still be being read,
+    //only strings and scalars are copied
+    Ref(u32),
+    //values produced by the interpreters of well known jdk classes
+    Map(String, Vec<(Content, Content)>),
+    List(String, Vec<Content>),
+    Set(String, Vec<Content>),
+    Date(i64),
+    Decimal(String),
+}
+
+macro_rules! as_content_val {
+    ($this: ident, $val_type: ident) => {
+        match $this {
+            Content::$val_type(ref val) => Some(val),
+            _ => None,
+        }
+    };
+}
+
+impl Content {
+    pub fn as_str(&self) -> Option<&String> {
+        as_content_val!(self, Str)
+    }
+
+    pub fn as_int(&self) -> Option<&i32> {
+        as_content_val!(self, Integer)
+    }
+
+    pub fn as_long(&self) -> Option<&i64> {
+        as_content_val!(self, Long)
+    }
+
+    pub fn as_object(&self) -> Option<&JavaObject> {
+        as_content_val!(self, Object)
+    }
+
+    pub fn as_bytes(&self) -> Option<Vec<u8>> {
+        match self {
+            Content::Array(_, items) => items
+                .iter()
+                .map(|item| match item {
+                    Content::Byte(b) => Some(*b as u8),
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Handle {
+    Class(Rc<ClassDesc>),
+    Value(Option<Content>),
+}
+
+/// turns the raw class data of a well known class into a logical value,
+/// returns None when the object doesn't look like what it should
+pub type Interpreter = fn(&JavaObject) -> Option<Content>;
+
+pub struct ObjectStream<'a> {
+    buff: &'a [u8],
+    handles: Vec<Handle>,
+    interpreters: HashMap<String, Interpreter>,
+    //every class descriptor read so far, survives TC_RESET
+    descs: Vec<Rc<ClassDesc>>,
+    depth: u32,
+    //string bytes copied by back references so far
+    ref_copied: usize,
+}
+
+impl<'a> ObjectStream<'a> {
+    pub fn new(buff: &'a [u8]) -> Self {
+        let mut stream = Self::raw(buff);
+        for (class_name, interpreter) in jdk_interpreters() {
+            stream.register(class_name, interpreter);
+        }
+        stream
+    }
+
+    /// raw stream without any interpreter, custom block data stays as it is
+    pub fn raw(buff: &'a [u8]) -> Self {
+        Self {
+            buff,
+            handles: Vec::new(),
+            interpreters: HashMap::new(),
+            descs: Vec::new(),
+            depth: 0,
+            ref_copied: 0,
+        }
+    }
+
+    pub fn class_descs(&self) -> &[Rc<ClassDesc>] {
+        &self.descs
+    }
+
+    pub fn register(&mut self, class_name: &str, interpreter: Interpreter) {
+        self.interpreters.insert(class_name.to_string(), interpreter);
+    }
+
+    fn fail<T>(&self, kind: ErrorKind) -> Result<T, ParseErr<'a>> {
+        Err(Err::Error(make_error(self.buff, kind)))
+    }
+
+    fn u8(&mut self) -> Result<u8, ParseErr<'a>> {
+        let (i, val) = be_u8(self.buff)?;
+        self.buff = i;
+        Ok(val)
+    }
+
+    fn peek(&self) -> Result<u8, ParseErr<'a>> {
+        let (_, val) = be_u8(self.buff)?;
+        Ok(val)
+    }
+
+    fn u16(&mut self) -> Result<u16, ParseErr<'a>> {
+        let (i, val) = be_u16(self.buff)?;
+        self.buff = i;
+        Ok(val)
+    }
+
+    fn u32(&mut self) -> Result<u32, ParseErr<'a>> {
+        let (i, val) = be_u32(self.buff)?;
+        self.buff = i;
+        Ok(val)
+    }
+
+    fn i64(&mut self) -> Result<i64, ParseErr<'a>> {
+        let (i, val) = be_i64(self.buff)?;
+        self.buff = i;
+        Ok(val)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], ParseErr<'a>> {
+        let (i, val) = take(len)(self.buff)?;
+        self.buff = i;
+        Ok(val)
+    }
+
+    fn utf(&mut self, len: usize) -> Result<String, ParseErr<'a>> {
+        let bytes = self.bytes(len)?;
+        Ok(decode_modified_utf8(bytes))
+    }
+
+    fn new_handle(&mut self, handle: Handle) -> u32 {
+        self.handles.push(handle);
+        self.handles.len() as u32 - 1
+    }
+
+    //a TC_RESET inside the content clears the handle table, the handle is gone then
+    fn put_handle(&mut self, idx: u32, handle: Handle) -> Result<(), ParseErr<'a>> {
+        match self.handles.get_mut(idx as usize) {
+            Some(slot) => {
+                *slot = handle;
+                Ok(())
+            }
+            None => self.fail(ErrorKind::Verify),
+        }
+    }
+
+    //objects and collections stay unset, references to them are Ref instead of deep copies
+    fn set_handle(&mut self, idx: u32, val: &Content) -> Result<(), ParseErr<'a>> {
+        let val = match val {
+            Content::Array(..)
+            | Content::Object(_)
+            | Content::Map(..)
+            | Content::List(..)
+            | Content::Set(..) => None,
+            _ => Some(val.clone()),
+        };
+        self.put_handle(idx, Handle::Value(val))
+    }
+
+    fn nested<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, ParseErr<'a>>,
+    ) -> Result<T, ParseErr<'a>> {
+        if self.depth >= MAX_DEPTH {
+            return self.fail(ErrorKind::TooLarge);
+        }
+        self.depth += 1;
+        let val = read(self);
+        self.depth -= 1;
+        val
+    }
+
+    /// check the stream header, must be called before reading any content
+    pub fn read_header(&mut self) -> Result<u16, ParseErr<'a>> {
+        let magic = self.u16()?;
+        if magic != STREAM_MAGIC {
+            return self.fail(ErrorKind::Tag);
+        }
+        let version = self.u16()?;
+        if version != STREAM_VERSION {
+            return self.fail(ErrorKind::Verify);
+        }
+        Ok(version)
+    }
+
+    pub fn is_eof(&self) -> bool {
+        self.buff.is_empty()
+    }
+
+    /// read every top level content till the end of the stream
+    pub fn read_all(&mut self) -> Result<Vec<Content>, ParseErr<'a>> {
+        let mut contents = vec![];
+        while !self.is_eof() {
+            contents.push(self.read_content()?);
+        }
+        Ok(contents)
+    }
+
+    /// read a single content, object or block data
+    pub fn read_content(&mut self) -> Result<Content, ParseErr<'a>> {
+        loop {
+            let tag = self.u8()?;
+            match tag {
+                TC_RESET => {
+                    self.handles.clear();
+                }
+                TC_BLOCKDATA => {
+                    let len = self.u8()? as usize;
+                    return Ok(Content::BlockData(self.bytes(len)?.to_vec()));
+                }
+                TC_BLOCKDATALONG => {
+                    let len = self.u32()? as usize;
+                    return Ok(Content::BlockData(self.bytes(len)?.to_vec()));
+                }
+                _ => return self.read_object_bytag(tag),
+            }
+        }
+    }
+
+    pub fn read_object(&mut self) -> Result<Content, ParseErr<'a>> {
+        let tag = self.u8()?;
+        self.read_object_bytag(tag)
+    }
+
+    fn read_object_bytag(&mut self, tag: u8) -> Result<Content, ParseErr<'a>> {
+        self.nested(|stream| stream.read_tagged(tag))
+    }
+
+    fn read_tagged(&mut self, tag: u8) -> Result<Content, ParseErr<'a>> {
+        match tag {
+            TC_NULL => Ok(Content::Null),
+            TC_REFERENCE => self.read_reference(),
+            TC_STRING | TC_LONGSTRING => {
+                let val = self.read_string_bytag(tag)?;
+                self.new_handle(Handle::Value(Some(Content::Str(val.clone()))));
+                Ok(Content::Str(val))
+            }
+            TC_CLASSDESC | TC_PROXYCLASSDESC => {
+                let desc = self.read_class_desc_bytag(tag)?;
+                match desc {
+                    Some(desc) => Ok(Content::Class(desc)),
+                    None => Ok(Content::Null),
+                }
+            }
+            TC_CLASS => {
+                let desc = self.read_class_desc()?;
+                let desc = match desc {
+                    Some(desc) => desc,
+                    None => return self.fail(ErrorKind::Verify),
+                };
+                self.new_handle(Handle::Class(desc.clone()));
+                Ok(Content::Class(desc))
+            }
+            TC_ARRAY => self.read_array(),
+            TC_ENUM => self.read_enum(),
+            TC_OBJECT => self.read_new_object(),
+            TC_EXCEPTION => {
+                //the stream is reset before and after the exception object
+                self.handles.clear();
+                let obj = self.read_object()?;
+                self.handles.clear();
+                Ok(obj)
+            }
+            _ => self.fail(ErrorKind::Tag),
+        }
+    }
+
+    fn read_reference(&mut self) -> Result<Content, ParseErr<'a>> {
+        let handle = self.u32()?;
+        let idx = handle.wrapping_sub(BASE_WIRE_HANDLE) as usize;
+        match self.handles.get(idx) {
+            Some(Handle::Value(Some(val))) => {
+                let len = match val {
+                    Content::Str(text) | Content::Decimal(text) => text.len(),
+                    Content::Enum(class_name, constant) => class_name.len() + constant.len(),
+                    _ => 0,
+                };
+                self.ref_copied += len;
+                if self.ref_copied > MAX_REF_COPY {
+                    return self.fail(ErrorKind::TooLarge);
+                }
+                Ok(val.clone())
+            }
+            Some(Handle::Value(None)) => Ok(Content::Ref(handle)),
+            Some(Handle::Class(desc)) => Ok(Content::Class(desc.clone())),
+            None => self.fail(ErrorKind::Verify),
+        }
+    }
+
+    fn read_string_bytag(&mut self, tag: u8) -> Result<String, ParseErr<'a>> {
+        if tag == TC_STRING {
+            let len = self.u16()? as usize;
+            self.utf(len)
+        } else {
+            let len = self.i64()? as usize;
+            self.utf(len)
+        }
+    }
+
+    /// string object used as field type, either a new string or a reference to one
+    fn read_type_string(&mut self) -> Result<String, ParseErr<'a>> {
+        match self.read_object()? {
+            Content::Str(val) => Ok(val),
+            _ => self.fail(ErrorKind::Verify),
+        }
+    }
+
+    fn read_class_desc(&mut self) -> Result<Option<Rc<ClassDesc>>, ParseErr<'a>> {
+        let tag = self.u8()?;
+        self.read_class_desc_bytag(tag)
+    }
+
+    fn read_class_desc_bytag(&mut self, tag: u8) -> Result<Option<Rc<ClassDesc>>, ParseErr<'a>> {
+        self.nested(|stream| stream.read_class_desc_tagged(tag))
+    }
+
+    fn read_class_desc_tagged(&mut self, tag: u8) -> Result<Option<Rc<ClassDesc>>, ParseErr<'a>> {
+        match tag {
+            TC_NULL => Ok(None),
+            TC_REFERENCE => match self.read_reference()? {
+                Content::Class(desc) => Ok(Some(desc)),
+                _ => self.fail(ErrorKind::Verify),
+            },
+            TC_CLASSDESC => {
+                let name_len = self.u16()? as usize;
+                let name = self.utf(name_len)?;
+                let serial_version_uid = self.i64()?;
+                let idx = self.new_handle(Handle::Value(None));
+                let flags = self.u8()?;
+                let count = self.u16()?;
+                let mut fields = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let type_code = self.u8()?;
+                    let name_len = self.u16()? as usize;
+                    let name = self.utf(name_len)?;
+                    let class_name = match type_code {
+                        b'L' | b'[' => Some(self.read_type_string()?),
+                        b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' => None,
+                        _ => return self.fail(ErrorKind::Tag),
+                    };
+                    fields.push(FieldDesc {
+                        type_code,
+                        name,
+                        class_name,
+                    });
+                }
+                self.skip_annotation()?;
+                let super_class = self.read_class_desc()?;
+                let desc = Rc::new(ClassDesc {
+                    name,
+                    serial_version_uid,
+                    flags,
+                    fields,
+                    super_class,
+                    proxy_interfaces: vec![],
+                });
+                self.put_handle(idx, Handle::Class(desc.clone()))?;
+                self.descs.push(desc.clone());
+                Ok(Some(desc))
+            }
+            TC_PROXYCLASSDESC => {
+                let idx = self.new_handle(Handle::Value(None));
+                let count = self.u32()?;
+                let mut proxy_interfaces = vec![];
+                for _ in 0..count {
+                    let len = self.u16()? as usize;
+                    proxy_interfaces.push(self.utf(len)?);
+                }
+                self.skip_annotation()?;
+                let super_class = self.read_class_desc()?;
+                let desc = Rc::new(ClassDesc {
+                    name: String::from("$Proxy"),
+                    serial_version_uid: 0,
+                    flags: SC_SERIALIZABLE,
+                    fields: vec![],
+                    super_class,
+                    proxy_interfaces,
+                });
+                self.put_handle(idx, Handle::Class(desc.clone()))?;
+                self.descs.push(desc.clone());
+                Ok(Some(desc))
+            }
+            _ => self.fail(ErrorKind::Tag),
+        }
+    }
+
+    fn skip_annotation(&mut self) -> Result<(), ParseErr<'a>> {
+        self.read_annotation().map(|_| ())
+    }
+
+    /// contents written by annotateClass/writeObject/writeExternal till TC_ENDBLOCKDATA
+    fn read_annotation(&mut self) -> Result<Vec<Content>, ParseErr<'a>> {
+        let mut contents = vec![];
+        loop {
+            if self.peek()? == TC_ENDBLOCKDATA {
+                self.u8()?;
+                return Ok(contents);
+            }
+            contents.push(self.read_content()?);
+        }
+    }
+
+    fn read_value(&mut self, type_code: u8) -> Result<Content, ParseErr<'a>> {
+        let input = self.buff;
+        let (i, val) = match type_code {
+            b'B' => be_i8(input).map(|(i, v)| (i, Content::Byte(v)))?,
+            b'C' => be_u16(input).map(|(i, v)| (i, Content::Char(v)))?,
+            b'D' => be_f64(input).map(|(i, v)| (i, Content::Double(v)))?,
+            b'F' => be_f32(input).map(|(i, v)| (i, Content::Float(v)))?,
+            b'I' => be_i32(input).map(|(i, v)| (i, Content::Integer(v)))?,
+            b'J' => be_i64(input).map(|(i, v)| (i, Content::Long(v)))?,
+            b'S' => be_i16(input).map(|(i, v)| (i, Content::Short(v)))?,
+            b'Z' => be_u8(input).map(|(i, v)| (i, Content::Boolean(v != 0)))?,
+            b'L' | b'[' => return self.read_object(),
+            _ => return self.fail(ErrorKind::Tag),
+        };
+        self.buff = i;
+        Ok(val)
+    }
+
+    fn read_array(&mut self) -> Result<Content, ParseErr<'a>> {
+        let desc = match self.read_class_desc()? {
+            Some(desc) => desc,
+            None => return self.fail(ErrorKind::Verify),
+        };
+        let idx = self.new_handle(Handle::Value(None));
+        let size = self.u32()?;
+        let type_code = desc.name.as_bytes().get(1).copied().unwrap_or(b'L');
+        let mut items = Vec::with_capacity(size.min(4096) as usize);
+        for _ in 0..size {
+            items.push(self.read_value(type_code)?);
+        }
+        let val = Content::Array(desc.name.clone(), items);
+        self.set_handle(idx, &val)?;
+        Ok(val)
+    }
+
+    fn read_enum(&mut self) -> Result<Content, ParseErr<'a>> {
+        let desc = match self.read_class_desc()? {
+            Some(desc) => desc,
+            None => return self.fail(ErrorKind::Verify),
+        };
+        let idx = self.new_handle(Handle::Value(None));
+        let constant = self.read_type_string()?;
+        let val = Content::Enum(desc.name.clone(), constant);
+        self.set_handle(idx, &val)?;
+        Ok(val)
+    }
+
+    fn read_new_object(&mut self) -> Result<Content, ParseErr<'a>> {
+        let desc = match self.read_class_desc()? {
+            Some(desc) => desc,
+            None => return self.fail(ErrorKind::Verify),
+        };
+        let idx = self.new_handle(Handle::Value(None));
+        let mut class_data = vec![];
+        for class in desc.hierarchy() {
+            let mut data = ClassData {
+                class_name: class.name.clone(),
+                fields: vec![],
+                annotation: vec![],
+            };
+            if class.has_flag(SC_EXTERNALIZABLE) {
+                if !class.has_flag(SC_BLOCK_DATA) {
+                    //protocol version 1 externalizable data can only be read by the class itself
+                    return self.fail(ErrorKind::Verify);
+                }
+                data.annotation = self.read_annotation()?;
+            } else if class.has_flag(SC_SERIALIZABLE) {
+                for field in class.fields.iter() {
+                    let val = self.read_value(field.type_code)?;
+                    data.fields.push((field.name.clone(), val));
+                }
+                if class.has_flag(SC_WRITE_METHOD) {
+                    data.annotation = self.read_annotation()?;
+                }
+            }
+            class_data.push(data);
+        }
+        let obj = JavaObject {
+            class_desc: desc,
+            class_data,
+        };
+        let val = match self.interpreters.get(obj.class_name()) {
+            Some(interpreter) => interpreter(&obj).unwrap_or(Content::Object(obj)),
+            None => Content::Object(obj),
+        };
+        self.set_handle(idx, &val)?;
+        Ok(val)
+    }
+}
+
+/// java's modified utf-8, nul is two bytes and supplementary chars are surrogate pairs
+pub fn decode_modified_utf8(bytes: &[u8]) -> String {
+    let mut units = Vec::<u16>::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let b = bytes[idx];
+        if b < 0x80 {
+            units.push(b as u16);
+            idx += 1;
+        } else if b & 0xe0 == 0xc0 && idx + 1 < bytes.len() {
+            units.push((u16::from(b & 0x1f) << 6) | u16::from(bytes[idx + 1] & 0x3f));
+            idx += 2;
+        } else if b & 0xf0 == 0xe0 && idx + 2 < bytes.len() {
+            units.push(
+                (u16::from(b & 0x0f) << 12)
+                    | (u16::from(bytes[idx + 1] & 0x3f) << 6)
+                    | u16::from(bytes[idx + 2] & 0x3f),
+            );
+            idx += 3;
+        } else {
+            units.push(0xfffd);
+            idx += 1;
+        }
+    }
+    String::from_utf16_lossy(&units)
+}
+
+/// parse a whole serialized stream, header included
+pub fn parse(buff: &[u8]) -> Result<Vec<Content>, ParseErr<'_>> {
+    let mut stream = ObjectStream::new(buff);
+    stream.read_header()?;
+    stream.read_all()
+}
+
+/// reads primitives and objects out of a writeObject annotation,
+/// primitives may be split across several block data records
+pub struct BlockReader<'b> {
+    contents: &'b [Content],
+    block: &'b [u8],
+}
+
+impl<'b> BlockReader<'b> {
+    pub fn new(contents: &'b [Content]) -> Self {
+        Self {
+            contents,
+            block: &[],
+        }
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Option<Vec<u8>> {
+        let mut out = Vec::with_capacity(len);
+        while out.len() < len {
+            if self.block.is_empty() {
+                match self.contents.split_first() {
+                    Some((Content::BlockData(data), rest)) => {
+                        self.block = data;
+                        self.contents = rest;
+                    }
+                    _ => return None,
+                }
+            }
+            let n = (len - out.len()).min(self.block.len());
+            out.extend_from_slice(&self.block[..n]);
+            self.block = &self.block[n..];
+        }
+        Some(out)
+    }
+
+    pub fn read_int(&mut self) -> Option<i32> {
+        let bytes = self.read_bytes(4)?;
+        Some(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn read_long(&mut self) -> Option<i64> {
+        let bytes = self.read_bytes(8)?;
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(&bytes);
+        Some(i64::from_be_bytes(buf))
+    }
+
+    pub fn read_float(&mut self) -> Option<f32> {
+        self.read_int().map(|v| f32::from_bits(v as u32))
+    }
+
+    pub fn read_object(&mut self) -> Option<&'b Content> {
+        if !self.block.is_empty() {
+            return None;
+        }
+        match self.contents.split_first() {
+            Some((Content::BlockData(_), _)) | None => None,
+            Some((obj, rest)) => {
+                self.contents = rest;
+                Some(obj)
+            }
+        }
+    }
+}
+
+fn jdk_interpreters() -> Vec<(&'static str, Interpreter)> {
+    vec![
+        ("java.util.HashMap", interpret_hash_map),
+        ("java.util.LinkedHashMap", interpret_hash_map),
+        ("java.util.ArrayList", interpret_array_list),
+        ("java.util.HashSet", interpret_hash_set),
+        ("java.util.LinkedHashSet", interpret_hash_set),
+        ("java.util.Date", interpret_date),
+        ("java.math.BigDecimal", interpret_big_decimal),
+        ("java.lang.Long", interpret_box),
+        ("java.lang.Integer", interpret_box),
+        ("java.lang.Short", interpret_box),
+        ("java.lang.Byte", interpret_box),
+        ("java.lang.Boolean", interpret_box),
+        ("java.lang.Double", interpret_box),
+        ("java.lang.Float", interpret_box),
+        ("java.lang.Character", interpret_box),
+    ]
+}
+
+fn interpret_hash_map(obj: &JavaObject) -> Option<Content> {
+    let data = obj.data_of("java.util.HashMap")?;
+    let mut reader = BlockReader::new(&data.annotation);
+    let _buckets = reader.read_int()?;
+    let size = reader.read_int()?;
+    let mut entries = Vec::with_capacity(size.clamp(0, 4096) as usize);
+    for _ in 0..size {
+        let key = reader.read_object()?.clone();
+        let val = reader.read_object()?.clone();
+        entries.push((key, val));
+    }
+    Some(Content::Map(obj.class_name().to_string(), entries))
+}
+
+fn interpret_array_list(obj: &JavaObject) -> Option<Content> {
+    let data = obj.data_of("java.util.ArrayList")?;
+    let mut reader = BlockReader::new(&data.annotation);
+    //capacity, same as the size field since java 1.7
+    let _capacity = reader.read_int()?;
+    let size = *obj.field("size")?.as_int()?;
+    let mut items = Vec::with_capacity(size.clamp(0, 4096) as usize);
+    for _ in 0..size {
+        items.push(reader.read_object()?.clone());
+    }
+    Some(Content::List(obj.class_name().to_string(), items))
+}
+
+fn interpret_hash_set(obj: &JavaObject) -> Option<Content> {
+    let data = obj.data_of("java.util.HashSet")?;
+    let mut reader = BlockReader::new(&data.annotation);
+    let _capacity = reader.read_int()?;
+    let _load_factor = reader.read_float()?;
+    let size = reader.read_int()?;
+    let mut items = Vec::with_capacity(size.clamp(0, 4096) as usize);
+    for _ in 0..size {
+        items.push(reader.read_object()?.clone());
+    }
+    Some(Content::Set(obj.class_name().to_string(), items))
+}
+
+fn interpret_date(obj: &JavaObject) -> Option<Content> {
+    let data = obj.data_of("java.util.Date")?;
+    let mut reader = BlockReader::new(&data.annotation);
+    Some(Content::Date(reader.read_long()?))
+}
+
+fn interpret_box(obj: &JavaObject) -> Option<Content> {
+    obj.field("value").cloned()
+}
+
+//an extreme scale would spell out as many zeros, past this it stays an exponent
+const MAX_DECIMAL_ZEROS: i64 = 1000;
+
+fn interpret_big_decimal(obj: &JavaObject) -> Option<Content> {
+    let scale = *obj.field("scale")?.as_int()?;
+    let unscaled = match obj.field("intVal")? {
+        Content::Object(int_val) => big_integer_string(int_val)?,
+        _ => return None,
+    };
+    let (sign, digits) = match unscaled.strip_prefix('-') {
+        Some(digits) => ("-", digits.to_string()),
+        None => ("", unscaled),
+    };
+    let zeros = if scale <= 0 {
+        -i64::from(scale)
+    } else {
+        i64::from(scale) - digits.len() as i64
+    };
+    let text = if zeros > MAX_DECIMAL_ZEROS {
+        format!("{}{}E{}", sign, digits, -i64::from(scale))
+    } else if scale <= 0 {
+        format!("{}{}{}", sign, digits, "0".repeat(zeros as usize))
+    } else {
+        let scale = scale as usize;
+        let digits = if digits.len() <= scale {
+            format!("{}{}", "0".repeat(scale - digits.len() + 1), digits)
+        } else {
+            digits
+        };
+        let (int_part, frac_part) = digits.split_at(digits.len() - scale);
+        format!("{}{}.{}", sign, int_part, frac_part)
+    };
+    Some(Content::Decimal(text))
+}
+
+/// java.math.BigInteger keeps signum and big endian magnitude bytes
+fn big_integer_string(obj: &JavaObject) -> Option<String> {
+    let signum = *obj.field("signum")?.as_int()?;
+    let mut magnitude = obj.field("magnitude")?.as_bytes()?;
+    let mut digits = vec![];
+    while magnitude.iter().any(|b| *b != 0) {
+        let mut rem = 0u32;
+        for b in magnitude.iter_mut() {
+            let cur = (rem << 8) | u32::from(*b);
+            *b = (cur / 10) as u8;
+            rem = cur % 10;
+        }
+        digits.push(b'0' + rem as u8);
+    }
+    if digits.is_empty() {
+        digits.push(b'0');
+    }
+    if signum < 0 {
+        digits.push(b'-');
+    }
+    digits.reverse();
+    String::from_utf8(digits).ok()
+}
+
+#[test]
+fn test_parse_hash_map() {
+    let buf = std::fs::read("testdata/hashmap.ser").unwrap();
+    let contents = parse(&buf).unwrap();
+    match &contents[0] {
+        Content::Map(class_name, entries) => {
+            assert_eq!("java.util.HashMap", class_name);
+            assert_eq!(3, entries.len());
+            let get = |key: &str| {
+                entries
+                    .iter()
+                    .find(|(k, _)| k.as_str().map(|s| s.as_str()) == Some(key))
+                    .map(|(_, v)| v.clone())
+            };
+            assert_eq!(Some(Content::Str("张三丰".to_string())), get("name"));
+            assert_eq!(Some(Content::Integer(100)), get("age"));
+            assert_eq!(Some(Content::Long(10086)), get("id"));
+        }
+        other => panic!("not a map {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_linked_hash_map() {
+    let buf = std::fs::read("testdata/linkedhashmap.ser").unwrap();
+    let contents = parse(&buf).unwrap();
+    assert_eq!(
+        vec![Content::Map(
+            "java.util.LinkedHashMap".to_string(),
+            vec![
+                (Content::Str("b".to_string()), Content::Integer(2)),
+                (Content::Str("a".to_string()), Content::Integer(1)),
+            ]
+        )],
+        contents
+    );
+}
+
+#[test]
+fn test_parse_array_list() {
+    let buf = std::fs::read("testdata/arraylist.ser").unwrap();
+    let contents = parse(&buf).unwrap();
+    assert_eq!(
+        vec![Content::List(
+            "java.util.ArrayList".to_string(),
+            vec![
+                Content::Str("10086".to_string()),
+                Content::Str("10010".to_string()),
+                Content::Date(1617181920000),
+                Content::Decimal("-12345678901234567890.125".to_string()),
+                Content::Str("10086".to_string()),
+            ]
+        )],
+        contents
+    );
+}
+
+#[test]
+fn test_parse_scalars() {
+    let set = parse(&std::fs::read("testdata/hashset.ser").unwrap()).unwrap();
+    match &set[0] {
+        Content::Set(_, items) => {
+            let mut ints: Vec<i32> = items.iter().map(|i| *i.as_int().unwrap()).collect();
+            ints.sort();
+            assert_eq!(vec![1, 2, 3], ints);
+        }
+        other => panic!("not a set {:?}", other),
+    }
+    let date = parse(&std::fs::read("testdata/date.ser").unwrap()).unwrap();
+    assert_eq!(vec![Content::Date(1617181920000)], date);
+    let decimal = parse(&std::fs::read("testdata/bigdecimal.ser").unwrap()).unwrap();
+    assert_eq!(vec![Content::Decimal("3.1415".to_string())], decimal);
+    let long = parse(&std::fs::read("testdata/long.ser").unwrap()).unwrap();
+    assert_eq!(vec![Content::Long(-42)], long);
+}
+
+#[test]
+fn test_raw_stream_keeps_block_data() {
+    let buf = std::fs::read("testdata/date.ser").unwrap();
+    let mut stream = ObjectStream::raw(&buf);
+    stream.read_header().unwrap();
+    let date = stream.read_content().unwrap();
+    let obj = date.as_object().unwrap();
+    assert_eq!("java.util.Date", obj.class_name());
+    assert_eq!(
+        vec![Content::BlockData(1617181920000i64.to_be_bytes().to_vec())],
+        obj.data_of("java.util.Date").unwrap().annotation
+    );
+}
+
+#[test]
+fn test_reset_inside_content() {
+    //an Object[] whose one element is an exception, which clears the handles of the array
+    let mut buf = vec![0xac, 0xed, 0x00, 0x05, TC_ARRAY, TC_CLASSDESC, 0x00, 0x13];
+    buf.extend_from_slice(b"[Ljava.lang.Object;");
+    buf.extend_from_slice(&[0; 8]);
+    buf.extend_from_slice(&[0x02, 0x00, 0x00, TC_ENDBLOCKDATA, TC_NULL]);
+    buf.extend_from_slice(&[0x00, 0x00, 0x00, 0x01, TC_EXCEPTION, TC_NULL]);
+    assert!(parse(&buf).is_err());
+}
+
+#[test]
+fn test_shared_and_nested_objects() {
+    let mut header = vec![0xac, 0xed, 0x00, 0x05, TC_ARRAY, TC_CLASSDESC, 0x00, 0x13];
+    header.extend_from_slice(b"[Ljava.lang.Object;");
+    header.extend_from_slice(&[0; 8]);
+    header.extend_from_slice(&[0x02, 0x00, 0x00, TC_ENDBLOCKDATA, TC_NULL]);
+    //an Object[] holding an empty Object[] twice, the second time as a back reference
+    let mut buf = header.clone();
+    buf.extend_from_slice(&[0x00, 0x00, 0x00, 0x02]);
+    buf.extend_from_slice(&[TC_ARRAY, TC_REFERENCE, 0x00, 0x7e, 0x00, 0x00, 0, 0, 0, 0]);
+    buf.extend_from_slice(&[TC_REFERENCE, 0x00, 0x7e, 0x00, 0x02]);
+    let empty = Content::Array("[Ljava.lang.Object;".to_string(), vec![]);
+    assert_eq!(
+        vec![Content::Array(
+            "[Ljava.lang.Object;".to_string(),
+            vec![empty, Content::Ref(0x7e0002)]
+        )],
+        parse(&buf).unwrap()
+    );
+
+    let nest = |depth: usize| {
+        let mut buf = header.clone();
+        for _ in 0..depth {
+            buf.extend_from_slice(&[0x00, 0x00, 0x00, 0x01, TC_ARRAY, TC_REFERENCE]);
+            buf.extend_from_slice(&[0x00, 0x7e, 0x00, 0x00]);
+        }
+        buf.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+        buf
+    };
+    assert!(parse(&nest(100)).is_ok());
+    assert!(parse(&nest(MAX_DEPTH as usize)).is_err());
+}
+
+#[test]
+fn test_big_decimal_scales() {
+    let big_integer = JavaObject {
+        class_desc: Rc::new(ClassDesc {
+            name: "java.math.BigInteger".to_string(),
+            serial_version_uid: 0,
+            flags: SC_SERIALIZABLE,
+            fields: vec![],
+            super_class: None,
+            proxy_interfaces: vec![],
+        }),
+        class_data: vec![ClassData {
+            class_name: "java.math.BigInteger".to_string(),
+            fields: vec![
+                ("signum".to_string(), Content::Integer(-1)),
+                (
+                    "magnitude".to_string(),
+                    Content::Array("[B".to_string(), vec![Content::Byte(12)]),
+                ),
+            ],
+            annotation: vec![],
+        }],
+    };
+    let decimal = |scale: i32| {
+        let mut obj = big_integer.clone();
+        obj.class_data[0].fields = vec![
+            ("scale".to_string(), Content::Integer(scale)),
+            ("intVal".to_string(), Content::Object(big_integer.clone())),
+        ];
+        interpret_big_decimal(&obj)
+    };
+    assert_eq!(Some(Content::Decimal("-0.012".to_string())), decimal(3));
+    assert_eq!(Some(Content::Decimal("-1200".to_string())), decimal(-2));
+    assert_eq!(Some(Content::Decimal("-12E2147483648".to_string())), decimal(i32::MIN));
+    assert_eq!(Some(Content::Decimal("-12E-2147483647".to_string())), decimal(i32::MAX));
+}
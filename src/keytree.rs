@@ -0,0 +1,334 @@
+use std::collections::BTreeMap;
+
+use redis::RedisResult;
+
+use crate::browser::{KeyBrowser, KeyInfo, ScanFilter};
+#[cfg(test)]
+use crate::browser::test_info;
+use crate::completion::escape_glob;
+use crate::redis_conn::ConnectionHolder;
+
+//keys looked at per expansion, stats of a bigger namespace are marked partial
+const SCAN_LIMIT: usize = 200_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NodeStats {
+    pub keys: u64,
+    //MEMORY USAGE summed up, 0 on servers without it
+    pub memory: u64,
+    pub expiring: u64,
+}
+
+impl NodeStats {
+    fn add(&mut self, key: &KeyInfo) {
+        self.keys += 1;
+        self.memory += key.memory.unwrap_or(0);
+        if key.ttl >= 0 {
+            self.expiring += 1;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TreeNode {
+    //the last segment, "list" of supcom:name:list, groups keep the separator
+    pub name: String,
+    //full key of a leaf, or the prefix all keys of a group share
+    pub path: Vec<u8>,
+    pub stats: NodeStats,
+    //set on leaves
+    pub key: Option<KeyInfo>,
+    //none until the group is expanded the first time
+    pub children: Option<Vec<TreeNode>>,
+    pub expanded: bool,
+    //the scan stopped at the limit, counts are a lower bound
+    pub partial: bool,
+}
+
+impl TreeNode {
+    fn group(name: String, path: Vec<u8>) -> TreeNode {
+        TreeNode {
+            name,
+            path,
+            stats: NodeStats::default(),
+            key: None,
+            children: None,
+            expanded: false,
+            partial: false,
+        }
+    }
+
+    pub fn is_group(&self) -> bool {
+        self.key.is_none()
+    }
+}
+
+/// one line of the flattened tree, path indexes children from the root
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub depth: usize,
+    pub path: Vec<usize>,
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() {
+        return None;
+    }
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// the direct children of prefix, keys deeper down only add to their group's stats
+pub fn group_keys(prefix: &[u8], separator: &[u8], keys: Vec<KeyInfo>) -> Vec<TreeNode> {
+    let mut groups: BTreeMap<Vec<u8>, TreeNode> = BTreeMap::new();
+    let mut leaves = Vec::new();
+    for key in keys {
+        if !key.key.starts_with(prefix) {
+            continue;
+        }
+        let rest = &key.key[prefix.len()..];
+        match find(rest, separator) {
+            Some(pos) => {
+                let path = key.key[..prefix.len() + pos + separator.len()].to_vec();
+                let name = String::from_utf8_lossy(&path[prefix.len()..]).into_owned();
+                groups
+                    .entry(path.clone())
+                    .or_insert_with(|| TreeNode::group(name, path))
+                    .stats
+                    .add(&key);
+            }
+            None => {
+                let mut stats = NodeStats::default();
+                stats.add(&key);
+                leaves.push(TreeNode {
+                    name: String::from_utf8_lossy(rest).into_owned(),
+                    path: key.key.clone(),
+                    stats,
+                    key: Some(key),
+                    children: Some(vec![]),
+                    expanded: false,
+                    partial: false,
+                });
+            }
+        }
+    }
+    leaves.sort_by(|a, b| a.path.cmp(&b.path));
+    groups.into_values().chain(leaves).collect()
+}
+
+fn fill(node: &mut TreeNode, separator: &[u8], keys: Vec<KeyInfo>, partial: bool) {
+    let mut stats = NodeStats::default();
+    keys.iter().for_each(|k| stats.add(k));
+    let mut children = group_keys(&node.path, separator, keys);
+    for child in children.iter_mut().filter(|c| c.is_group()) {
+        child.partial = partial;
+    }
+    node.children = Some(children);
+    node.stats = stats;
+    node.partial = partial;
+}
+
+/// keys grouped on a separator like directories, a group is scanned when first expanded
+#[derive(Debug, Clone)]
+pub struct KeyTree {
+    pub separator: String,
+    pub scan_limit: usize,
+    root: TreeNode,
+}
+
+impl Default for KeyTree {
+    fn default() -> Self {
+        Self::new(":")
+    }
+}
+
+impl KeyTree {
+    pub fn new(separator: &str) -> KeyTree {
+        let mut root = TreeNode::group(String::new(), vec![]);
+        root.expanded = true;
+        KeyTree {
+            separator: separator.to_string(),
+            scan_limit: SCAN_LIMIT,
+            root,
+        }
+    }
+
+    pub fn root(&self) -> &TreeNode {
+        &self.root
+    }
+
+    pub fn node(&self, path: &[usize]) -> Option<&TreeNode> {
+        path.iter()
+            .try_fold(&self.root, |node, &i| node.children.as_ref()?.get(i))
+    }
+
+    fn node_mut(&mut self, path: &[usize]) -> Option<&mut TreeNode> {
+        let mut node = &mut self.root;
+        for &i in path {
+            node = node.children.as_mut()?.get_mut(i)?;
+        }
+        Some(node)
+    }
+
+    /// scan everything below the group once, children get their aggregates from it
+    pub fn load(&mut self, holder: &mut ConnectionHolder, path: &[usize]) -> RedisResult<()> {
+        let separator = self.separator.clone();
+        let limit = self.scan_limit;
+        let node = match self.node_mut(path) {
+            Some(node) if node.is_group() => node,
+            _ => return Ok(()),
+        };
+        let pattern = format!("{}*", escape_glob(&String::from_utf8_lossy(&node.path)));
+        let mut browser = KeyBrowser::new(ScanFilter {
+            count: 1000,
+            ..ScanFilter::matching(&pattern)
+        });
+        let mut keys = Vec::new();
+        while !browser.is_done() && keys.len() < limit {
+            keys.extend(browser.next_page(holder, 1000)?);
+        }
+        let partial = !browser.is_done();
+        fill(node, separator.as_bytes(), keys, partial);
+        Ok(())
+    }
+
+    /// the top level from keys fetched elsewhere, eg a SCAN page or a dump file
+    pub fn set_keys(&mut self, keys: Vec<KeyInfo>) {
+        let separator = self.separator.clone();
+        fill(&mut self.root, separator.as_bytes(), keys, false);
+    }
+
+    /// expand a group, loading it the first time
+    pub fn expand(&mut self, holder: &mut ConnectionHolder, path: &[usize]) -> RedisResult<()> {
+        if self
+            .node(path)
+            .is_some_and(|n| n.is_group() && n.children.is_none())
+        {
+            self.load(holder, path)?;
+        }
+        if let Some(node) = self.node_mut(path) {
+            node.expanded = node.is_group();
+        }
+        Ok(())
+    }
+
+    pub fn collapse(&mut self, path: &[usize]) {
+        if let Some(node) = self.node_mut(path) {
+            node.expanded = false;
+        }
+    }
+
+    pub fn toggle(&mut self, holder: &mut ConnectionHolder, path: &[usize]) -> RedisResult<()> {
+        if self.node(path).is_some_and(|n| n.expanded) {
+            self.collapse(path);
+            Ok(())
+        } else {
+            self.expand(holder, path)
+        }
+    }
+
+    /// forget loaded groups so the next expand scans again
+    pub fn reset(&mut self) {
+        *self = KeyTree {
+            scan_limit: self.scan_limit,
+            ..KeyTree::new(&self.separator)
+        };
+    }
+
+    /// the expanded part of the tree in display order, the root itself is left out
+    pub fn rows(&self) -> Vec<Row> {
+        fn walk(node: &TreeNode, path: &mut Vec<usize>, rows: &mut Vec<Row>) {
+            if !node.expanded {
+                return;
+            }
+            for (i, child) in node.children.iter().flatten().enumerate() {
+                path.push(i);
+                rows.push(Row {
+                    depth: path.len() - 1,
+                    path: path.clone(),
+                });
+                walk(child, path, rows);
+                path.pop();
+            }
+        }
+        let mut rows = Vec::new();
+        walk(&self.root, &mut vec![], &mut rows);
+        rows
+    }
+}
+
+#[test]
+fn test_group_keys() {
+    let keys = vec![
+        test_info("supcom:name:list", "string", 1, 100, -1),
+        test_info("supcom:name:set", "string", 1, 50, 30),
+        test_info("supcom:age", "string", 1, 10, -1),
+        test_info("supcom", "string", 1, 5, -1),
+        test_info("other:x", "string", 1, 1, 5),
+    ];
+    let top = group_keys(b"", b":", keys.clone());
+    let names: Vec<&str> = top.iter().map(|n| n.name.as_str()).collect();
+    assert_eq!(vec!["other:", "supcom:", "supcom"], names);
+    assert_eq!(
+        NodeStats {
+            keys: 3,
+            memory: 160,
+            expiring: 1
+        },
+        top[1].stats
+    );
+    assert!(top[1].children.is_none());
+    assert!(!top[2].is_group());
+
+    let below = group_keys(b"supcom:", b":", keys.clone());
+    assert_eq!(2, below.len());
+    assert_eq!(b"supcom:name:".to_vec(), below[0].path);
+    assert_eq!(2, below[0].stats.keys);
+    assert_eq!("age", below[1].name);
+
+    let mut tree = KeyTree::default();
+    tree.set_keys(keys);
+    assert_eq!(5, tree.root().stats.keys);
+    tree.node_mut(&[1]).unwrap().children = Some(below);
+    assert_eq!(3, tree.rows().len());
+    tree.node_mut(&[1]).unwrap().expanded = true;
+    let rows = tree.rows();
+    assert_eq!(5, rows.len());
+    assert_eq!(
+        Row {
+            depth: 1,
+            path: vec![1, 0]
+        },
+        rows[2]
+    );
+    assert_eq!("name:", tree.node(&rows[2].path).unwrap().name);
+}
+
+#[test]
+fn test_expand_key_tree() -> RedisResult<()> {
+    let mut holder = ConnectionHolder::new();
+    holder.put("redis://127.0.0.1:6379/0")?;
+    holder.execute(&["SET", "tree:a:1", "x"])?;
+    holder.execute(&["SET", "tree:a:2", "x", "EX", "100"])?;
+    holder.execute(&["SET", "tree:b", "x"])?;
+
+    let mut tree = KeyTree::default();
+    tree.expand(&mut holder, &[])?;
+    let pos = tree
+        .root()
+        .children
+        .as_ref()
+        .unwrap()
+        .iter()
+        .position(|n| n.name == "tree:")
+        .unwrap();
+    assert_eq!(3, tree.node(&[pos]).unwrap().stats.keys);
+    tree.expand(&mut holder, &[pos])?;
+    let group = tree.node(&[pos, 0]).unwrap();
+    assert_eq!("a:", group.name);
+    assert_eq!(2, group.stats.keys);
+    assert_eq!(1, group.stats.expiring);
+    assert!(group.children.is_none());
+
+    holder.execute(&["DEL", "tree:a:1", "tree:a:2", "tree:b"])?;
+    Ok(())
+}
@@ -1,23 +1,43 @@
 extern crate hashers;
 extern crate redis;
 
+pub mod aof;
+pub mod browser;
+pub mod classfile;
 pub mod cli;
 pub mod clourse;
+pub mod completion;
+pub mod compress;
 pub mod fullindex;
 pub mod highlight;
+pub mod jar;
+pub mod keytree;
 pub mod live;
 pub mod magic_macro;
 pub mod map;
+pub mod memreport;
 pub mod micro;
+pub mod migrate;
+pub mod monitor;
 pub mod obj_ref;
 pub mod pointer;
+pub mod profile;
+pub mod purge;
+pub mod rdb;
 pub mod redis_conn;
 pub mod redis_mo;
+pub mod safemode;
+pub mod sniff;
+pub mod transfer;
 pub mod ui;
+pub mod value;
+pub mod viewer;
 pub mod xmltest;
 pub mod zen;
-// pub mod nomtest;
 pub mod hessian;
+pub mod info;
+pub mod javap;
+pub mod javaser;
 
 pub use xmltest::test_dom;
 
@@ -8,7 +8,22 @@ use std::{mem::size_of_val, process::exit};
 use encoding::{all::GBK, DecoderTrap, Encoding};
 
 use rust_a::{public::*, util::*};
+use rust_a::cli::{run_script, Repl};
+use rust_a::jar::{check_stream, scan_jar};
+use rust_a::profile::{profile_command, resolve_target, store_keyring_password, Profiles};
+use rust_a::redis_conn::ConnectionHolder;
 use rust_a::redis_mo::do_redis_code;
+use rust_a::transfer::{export_keys, import_keys, Conflict};
+use rust_a::aof::{cut, replay, write_aof, Aof, AofFilter, Cut};
+use rust_a::browser::ScanFilter;
+use rust_a::memreport::{report_rdb, sample_live, ReportOptions};
+use rust_a::migrate::{migrate, MigrateOptions};
+use rust_a::monitor::{monitor, MonitorFilter, MonitorLimit};
+use rust_a::purge::{confirmed, purge, PurgeOptions};
+use rust_a::rdb::{export_rdb, scan_rdb, RdbReader};
+use rust_a::safemode::{CommandTable, SafeMode};
+use rust_a::ui::{run_dashboard, App};
+use rust_a::value::DecoderChain;
 
 #[test]
 fn json_test() {
@@ -58,6 +73,520 @@ fn call_redis() {
         }
     }
 }
+fn load_profiles() -> Profiles {
+    Profiles::load(Profiles::default_path()).unwrap_or_else(|err| {
+        println!("read profiles failed: {}", err);
+        exit(1);
+    })
+}
+
+//connect by profile name, or by uri which then names the history
+fn redis_cli(args: &[String]) {
+    let name = args.first().map_or("redis://127.0.0.1:6379/0", |t| t.as_str());
+    let target = match resolve_target(name) {
+        Ok(target) => target,
+        Err(err) => {
+            println!("{}", err);
+            exit(1);
+        }
+    };
+    let mut holder = ConnectionHolder::new();
+    let id = match target.open(&mut holder, &target.name) {
+        Ok(id) => id,
+        Err(err) => {
+            println!("connect {} failed: {}", name, err);
+            exit(1);
+        }
+    };
+    let mut repl = Repl::new(holder);
+    repl.set_color(id, target.color);
+    repl.run();
+}
+
+//a single connection by profile name or uri, exits when it can't be made
+fn connect(name: &str) -> ConnectionHolder {
+    let mut holder = ConnectionHolder::new();
+    let connected = resolve_target(name)
+        .map_err(|err| err.to_string())
+        .and_then(|t| t.open(&mut holder, &t.name).map_err(|err| err.to_string()));
+    if let Err(err) = connected {
+        println!("connect {} failed: {}", name, err);
+        exit(1);
+    }
+    holder
+}
+
+fn export_cli(args: &[String]) {
+    if args.len() != 3 {
+        println!("usage: export <uri|profile> <pattern> <file>");
+        exit(1);
+    }
+    let mut holder = connect(&args[0]);
+    let file = std::fs::File::create(&args[2]).unwrap_or_else(|err| {
+        println!("create {} failed: {}", args[2], err);
+        exit(1);
+    });
+    let mut out = std::io::BufWriter::new(file);
+    match export_keys(&mut holder, ScanFilter::matching(&args[1]), &mut out) {
+        Ok(stats) => println!("exported {}", stats),
+        Err(err) => {
+            println!("export failed: {}", err);
+            exit(1);
+        }
+    }
+}
+
+fn import_cli(args: &[String]) {
+    if args.len() < 2 || args.len() > 3 {
+        println!("usage: import <uri|profile> <file> [skip|replace|fail]");
+        exit(1);
+    }
+    let policy = match args.get(2).map_or(Ok(Conflict::Skip), |p| p.parse()) {
+        Ok(policy) => policy,
+        Err(err) => {
+            println!("{}", err);
+            exit(1);
+        }
+    };
+    let mut holder = connect(&args[0]);
+    let file = std::fs::File::open(&args[1]).unwrap_or_else(|err| {
+        println!("open {} failed: {}", args[1], err);
+        exit(1);
+    });
+    match import_keys(&mut holder, std::io::BufReader::new(file), policy) {
+        Ok(stats) => println!("imported {}", stats),
+        Err(err) => {
+            println!("import failed: {}", err);
+            exit(1);
+        }
+    }
+}
+
+//list the keys of an rdb file, or export them when an output file is given
+fn rdb_cli(args: &[String]) {
+    if args.is_empty() || args.len() > 4 {
+        println!("usage: rdb <file> [pattern] [db] [export file]");
+        exit(1);
+    }
+    let filter = ScanFilter::matching(args.get(1).map_or("*", |p| p.as_str()));
+    let db = args.get(2).map_or(0, |d| d.parse().expect("db is a number!"));
+    let now = chrono::Utc::now().timestamp_millis();
+    let reader = RdbReader::open(&args[0]).unwrap_or_else(|err| {
+        println!("read {} failed: {}", args[0], err);
+        exit(1);
+    });
+    let done = match args.get(3) {
+        Some(path) => std::fs::File::create(path)
+            .map_err(|err| err.to_string())
+            .and_then(|file| {
+                let mut out = std::io::BufWriter::new(file);
+                export_rdb(reader, db, &filter, now, &mut out).map_err(|err| err.to_string())
+            })
+            .map(|stats| println!("exported {}", stats)),
+        None => scan_rdb(reader, db, &filter, now)
+            .map(|keys| {
+                for key in keys {
+                    let len = key.len.unwrap_or(0);
+                    println!("{} {} ttl {} len {}", key.name(), key.kind, key.ttl, len);
+                }
+            })
+            .map_err(|err| err.to_string()),
+    };
+    if let Err(err) = done {
+        println!("{}: {}", args[0], err);
+        exit(1);
+    }
+}
+
+//an rdb file is read whole, a live server is sampled with MEMORY USAGE
+fn memory_report(args: &[String]) {
+    if args.is_empty() || args.len() > 3 {
+        println!("usage: memreport <uri|profile|rdb file> [top] [text|csv|json]");
+        exit(1);
+    }
+    let options = ReportOptions {
+        top: args.get(1).map_or(10, |t| t.parse().expect("top is a number!")),
+        ..Default::default()
+    };
+    let report = if std::path::Path::new(&args[0]).is_file() {
+        RdbReader::open(&args[0])
+            .and_then(|reader| {
+                report_rdb(reader, chrono::Utc::now().timestamp_millis(), options)
+            })
+            .map_err(|err| err.to_string())
+    } else {
+        let mut holder = connect(&args[0]);
+        sample_live(&mut holder, ScanFilter::default(), None, options)
+            .map_err(|err| err.to_string())
+    };
+    match (report, args.get(2).map_or("text", |f| f.as_str())) {
+        (Ok(report), "csv") => print!("{}", report.to_csv()),
+        (Ok(report), "json") => println!("{}", report.to_json()),
+        (Ok(report), _) => print!("{}", report),
+        (Err(err), _) => {
+            println!("report failed: {}", err);
+            exit(1);
+        }
+    }
+}
+
+//aof <path> [list | write <file> | replay <target>] [match=..] [cmd=a,b] [pos=n] [time=secs]
+fn aof_cli(args: &[String]) {
+    if args.is_empty() {
+        println!("usage: aof <path> [list | write <file> | replay <uri|profile>]");
+        println!("\t[match=pattern] [cmd=name,..] [pos=offset] [time=unix seconds]");
+        exit(1);
+    }
+    let mut filter = AofFilter::default();
+    let mut at = None;
+    let mut action = vec![];
+    for arg in &args[1..] {
+        match arg.split_once('=') {
+            Some(("match", pattern)) => filter.pattern = Some(pattern.to_string()),
+            Some(("cmd", names)) => {
+                filter.commands = names.split(',').map(String::from).collect()
+            }
+            Some(("pos", pos)) => at = Some(Cut::Pos(pos.parse().expect("pos is a number!"))),
+            Some(("time", ts)) => {
+                at = Some(Cut::Time(ts.parse().expect("time is unix seconds!")))
+            }
+            _ => action.push(arg.as_str()),
+        }
+    }
+    let commands = match Aof::open(&args[0]).and_then(|aof| aof.commands()) {
+        Ok(commands) => commands,
+        Err(err) => {
+            println!("read {} failed: {}", args[0], err);
+            exit(1);
+        }
+    };
+    let mut commands = match at {
+        Some(at) => cut(commands, at),
+        None => commands,
+    };
+    commands.retain(|c| filter.matches(c));
+    match action.as_slice() {
+        [] | ["list"] => {
+            for cmd in &commands {
+                let time = cmd.time.map_or(String::new(), |t| format!(" @{}", t));
+                println!("{}{}\t{}", cmd.pos, time, cmd);
+            }
+        }
+        ["write", path] => {
+            let written = std::fs::File::create(path)
+                .and_then(|file| write_aof(&commands, &mut std::io::BufWriter::new(file)));
+            match written {
+                Ok(()) => println!("{} commands written to {}", commands.len(), path),
+                Err(err) => {
+                    println!("write {} failed: {}", path, err);
+                    exit(1);
+                }
+            }
+        }
+        ["replay", target] => {
+            let mut holder = connect(target);
+            match replay(&mut holder, &commands, 500) {
+                Ok(done) => println!("{} commands replayed", done),
+                Err(err) => {
+                    println!("replay failed: {}", err);
+                    exit(1);
+                }
+            }
+        }
+        other => {
+            println!("unknown aof action {:?}", other);
+            exit(1);
+        }
+    }
+}
+
+fn migrate_cli(args: &[String]) {
+    if args.len() < 3 || args.len() > 5 {
+        println!("usage: migrate <source> <target> <pattern> [skip|replace|fail] [move]");
+        exit(1);
+    }
+    let mut options = MigrateOptions {
+        remove_source: args.get(4).is_some_and(|m| m == "move"),
+        ..Default::default()
+    };
+    if let Some(policy) = args.get(3) {
+        options.conflict = policy.parse().unwrap_or_else(|err| {
+            println!("{}", err);
+            exit(1);
+        });
+    }
+    let mut holder = ConnectionHolder::new();
+    let mut ids = vec![];
+    for name in &args[..2] {
+        let connected = resolve_target(name)
+            .map_err(|err| err.to_string())
+            .and_then(|t| t.open(&mut holder, &t.name).map_err(|err| err.to_string()));
+        match connected {
+            Ok(id) => ids.push(id),
+            Err(err) => {
+                println!("connect {} failed: {}", name, err);
+                exit(1);
+            }
+        }
+    }
+    let filter = ScanFilter::matching(&args[2]);
+    let done = migrate(&mut holder, ids[0], ids[1], filter, &options, &mut |stats| {
+        print!("\r{}", stats);
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+    });
+    println!();
+    match done {
+        Ok(stats) => {
+            for (key, err) in &stats.failed {
+                println!("{}: {}", key, err);
+            }
+            println!("{}", stats);
+        }
+        Err(err) => {
+            println!("migrate failed: {}", err);
+            exit(1);
+        }
+    }
+}
+
+//purge <uri|profile> <pattern> [type=..] [idle=secs] [rate=keys/s] [dry-run]
+fn purge_cli(args: &[String]) {
+    if args.len() < 2 {
+        println!("usage: purge <uri|profile> <pattern> [type=..] [idle=secs] [rate=n] [dry-run]");
+        exit(1);
+    }
+    let mut options = PurgeOptions {
+        filter: ScanFilter::matching(&args[1]),
+        ..Default::default()
+    };
+    for arg in &args[2..] {
+        match arg.split_once('=') {
+            Some(("type", kind)) => options.filter.kind = Some(kind.to_string()),
+            Some(("idle", secs)) => {
+                options.min_idle = Some(secs.parse().expect("idle is seconds!"))
+            }
+            Some(("rate", rate)) => options.rate = Some(rate.parse().expect("rate is keys/s!")),
+            _ if arg == "dry-run" => options.dry_run = true,
+            _ => {
+                println!("unknown option {}", arg);
+                exit(1);
+            }
+        }
+    }
+    let target = resolve_target(&args[0]).unwrap_or_else(|err| {
+        println!("{}", err);
+        exit(1);
+    });
+    if target.production && !options.dry_run {
+        println!("{} is production, type its name to delete {}:", target.name, args[1]);
+        if !confirmed(&target.name, std::io::stdin().lock()) {
+            println!("not confirmed, nothing deleted");
+            exit(1);
+        }
+    }
+    let mut holder = connect(&args[0]);
+    let dry_run = options.dry_run;
+    let done = purge(&mut holder, &options, &mut |keys, stats| {
+        if dry_run {
+            for key in keys {
+                println!("{}", String::from_utf8_lossy(key));
+            }
+        } else {
+            println!("{} scanned, {} deleting", stats.scanned, stats.matched);
+        }
+    });
+    match done {
+        Ok(stats) if dry_run => println!("{} keys would be deleted", stats.matched),
+        Ok(stats) => println!("{} keys deleted of {} scanned", stats.deleted, stats.scanned),
+        Err(err) => {
+            println!("purge failed: {}", err);
+            exit(1);
+        }
+    }
+}
+
+fn monitor_cli(args: &[String]) {
+    if args.is_empty() {
+        println!("usage: monitor <uri|profile> [cmd=name,..] [match=pattern] [client=addr]");
+        println!("\t[seconds=n] [count=n] [top=n] [every=seconds] [force], enter stops it");
+        exit(1);
+    }
+    let mut filter = MonitorFilter::default();
+    let mut limit = MonitorLimit::default();
+    let mut top = 10;
+    let mut every = Duration::from_secs(10);
+    let mut force = false;
+    for arg in &args[1..] {
+        match arg.split_once('=') {
+            None if arg == "force" => force = true,
+            Some(("cmd", names)) => {
+                filter.commands = names.split(',').map(String::from).collect()
+            }
+            Some(("match", pattern)) => filter.pattern = Some(pattern.to_string()),
+            Some(("client", client)) => filter.client = Some(client.to_string()),
+            //0 lifts the time limit, a count limit should be given then
+            Some(("seconds", secs)) => {
+                let secs: u64 = secs.parse().expect("seconds is a number!");
+                limit.duration = Some(Duration::from_secs(secs)).filter(|_| secs > 0);
+            }
+            Some(("count", count)) => {
+                limit.count = Some(count.parse().expect("count is a number!"))
+            }
+            Some(("top", n)) => top = n.parse().expect("top is a number!"),
+            Some(("every", secs)) => {
+                every = Duration::from_secs(secs.parse().expect("every is seconds!"))
+            }
+            _ => {
+                println!("unknown option {}", arg);
+                exit(1);
+            }
+        }
+    }
+    if limit.duration.is_none() && limit.count.is_none() {
+        println!("monitor needs seconds= or count=, it slows the server down while it runs");
+        exit(1);
+    }
+    let target = resolve_target(&args[0]).unwrap_or_else(|err| {
+        println!("{}", err);
+        exit(1);
+    });
+    //MONITOR is admin, safe mode refuses it on careful profiles unless forced
+    if target.read_only || target.production {
+        let safe = SafeMode::new(CommandTable::builtin());
+        if let Err(blocked) = safe.check(&["MONITOR"]) {
+            if !force {
+                safe.log(&target.name, "blocked", &["MONITOR"]);
+                println!("{}, add force to run it anyway", blocked);
+                exit(1);
+            }
+            safe.log(&target.name, "forced", &["MONITOR"]);
+        }
+    }
+    let stop = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let stopper = stop.clone();
+    std::thread::spawn(move || {
+        let mut line = String::new();
+        let _ = std::io::stdin().read_line(&mut line);
+        stopper.store(true, std::sync::atomic::Ordering::Relaxed);
+    });
+    let mut last = std::time::Instant::now();
+    let done = monitor(target.info, &filter, limit, &stop, &mut |line, stats| {
+        println!("{}", line);
+        if last.elapsed() >= every {
+            println!("{}", stats.report(top));
+            last = std::time::Instant::now();
+        }
+    });
+    match done {
+        Ok(stats) => print!("{}", stats.report(top)),
+        Err(err) => {
+            println!("monitor failed: {}", err);
+            exit(1);
+        }
+    }
+}
+
+//every uri or profile given becomes a connection in the dashboard
+fn dashboard(args: &[String]) {
+    let default = ["redis://127.0.0.1:6379/0".to_string()];
+    let targets = if args.is_empty() { &default[..] } else { args };
+    let mut app = App::new(ConnectionHolder::new());
+    for name in targets {
+        let connected = resolve_target(name)
+            .map_err(|err| err.to_string())
+            .and_then(|t| {
+                let chain = DecoderChain::with_rules(&t.decoders)?;
+                let id = t
+                    .open(app.holder(), &t.name)
+                    .map_err(|err| err.to_string())?;
+                app.set_decoders(id, chain);
+                Ok(())
+            });
+        if let Err(err) = connected {
+            println!("connect {} failed: {}", name, err);
+            exit(1);
+        }
+    }
+    if let Err(err) = run_dashboard(app, Duration::from_secs(2)) {
+        println!("dashboard failed: {}", err);
+        exit(1);
+    }
+}
+
+fn profile_cli(args: &[String]) {
+    let mut profiles = load_profiles();
+    //keyring <name> reads the password from stdin so it stays out of shell history
+    if args.len() == 2 && args[0] == "keyring" {
+        let mut password = String::new();
+        println!("password for {}:", args[1]);
+        std::io::stdin()
+            .read_line(&mut password)
+            .expect("read password failed!");
+        let stored = store_keyring_password(&args[1], password.trim_end_matches(&['\r', '\n'][..]))
+            .and_then(|_| profiles.edit(&args[1], &[("password", "keyring")]))
+            .and_then(|_| profiles.save(Profiles::default_path()));
+        match stored {
+            Ok(()) => println!("password of {} kept in the keyring", args[1]),
+            Err(err) => println!("{}", err),
+        }
+        return;
+    }
+    match profile_command(&mut profiles, args) {
+        Ok(out) => {
+            if args.first().is_some_and(|cmd| cmd != "list") {
+                if let Err(err) = profiles.save(Profiles::default_path()) {
+                    println!("save profiles failed: {}", err);
+                    exit(1);
+                }
+            }
+            println!("{}", out);
+        }
+        Err(err) => {
+            println!("{}", err);
+            exit(1);
+        }
+    }
+}
+
+fn jar_check(args: &[String]) {
+    if args.is_empty() {
+        println!("usage: jarcheck <jar> [serialized file]...");
+        exit(1);
+    }
+    let index = match scan_jar(&args[0]) {
+        Ok(index) => index,
+        Err(err) => {
+            println!("read {} failed: {}", args[0], err);
+            exit(1);
+        }
+    };
+    if args.len() == 1 {
+        let mut names: Vec<&String> = index.classes.keys().collect();
+        names.sort();
+        for name in names {
+            let entry = &index.classes[name];
+            let kind = if entry.explicit_uid { "declared" } else { "computed" };
+            println!("{} {}L ({})", name, entry.serial_version_uid, kind);
+        }
+    }
+    for (path, err) in &index.errors {
+        println!("{}: {}", path, err);
+    }
+    for ser in &args[1..] {
+        let data = std::fs::read(ser).expect("read serialized file failed!");
+        match check_stream(&index, &data) {
+            Ok(report) if report.is_empty() => println!("{}: compatible", ser),
+            Ok(report) => {
+                println!("{}:", ser);
+                for mismatch in report {
+                    println!("\t{}", mismatch);
+                }
+            }
+            Err(err) => println!("{}: not a serialized stream {}", ser, err),
+        }
+    }
+}
+
 fn main() {
     // call_redis();
     let args: Vec<String> = env::args().collect();
@@ -66,6 +595,64 @@ fn main() {
         println!("{}", args.get(i).unwrap());
     }
     let g1 = args.get(1).unwrap();
+    if g1 == "profile" {
+        profile_cli(&args[2..]);
+        return;
+    }
+    if g1 == "cli" {
+        redis_cli(&args[2..]);
+        return;
+    }
+    if g1 == "ui" {
+        dashboard(&args[2..]);
+        return;
+    }
+    if g1 == "export" {
+        export_cli(&args[2..]);
+        return;
+    }
+    if g1 == "import" {
+        import_cli(&args[2..]);
+        return;
+    }
+    if g1 == "memreport" {
+        memory_report(&args[2..]);
+        return;
+    }
+    if g1 == "aof" {
+        aof_cli(&args[2..]);
+        return;
+    }
+    if g1 == "migrate" {
+        migrate_cli(&args[2..]);
+        return;
+    }
+    if g1 == "purge" {
+        purge_cli(&args[2..]);
+        return;
+    }
+    if g1 == "monitor" {
+        monitor_cli(&args[2..]);
+        return;
+    }
+    if g1 == "rdb" {
+        rdb_cli(&args[2..]);
+        return;
+    }
+    if g1 == "script" && args.len() == 4 {
+        let mut holder = ConnectionHolder::new();
+        holder.put(&args[2]).expect("connect redis failed!");
+        let text = std::fs::read(&args[3]).expect("read script failed!");
+        if let Err(err) = run_script(&mut holder, &text) {
+            println!("{}", err);
+            exit(1);
+        }
+        return;
+    }
+    if g1 == "jarcheck" {
+        jar_check(&args[2..]);
+        return;
+    }
     rust_a::test_dom(g1.parse::<u32>().unwrap());
     // use rust_a::fullindex::call_full_basic;
     // call_full_basic();
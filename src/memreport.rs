@@ -0,0 +1,364 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Read;
+
+use redis::RedisResult;
+use serde::Serialize;
+
+use crate::browser::{KeyBrowser, KeyInfo, ScanFilter};
+#[cfg(test)]
+use crate::browser::test_info;
+use crate::rdb::{RdbError, RdbReader};
+use crate::redis_conn::ConnectionHolder;
+
+//element count buckets go up by a factor of ten, the last one takes the rest
+const BUCKETS: [u64; 7] = [1, 10, 100, 1_000, 10_000, 100_000, u64::MAX];
+
+#[derive(Debug, Clone)]
+pub struct ReportOptions {
+    //biggest keys kept per type
+    pub top: usize,
+    pub separator: String,
+    //how many separated parts make a prefix
+    pub depth: usize,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        ReportOptions {
+            top: 10,
+            separator: ":".to_string(),
+            depth: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BigKey {
+    pub key: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub memory: u64,
+    pub len: Option<u64>,
+    pub ttl: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PrefixStats {
+    pub keys: u64,
+    pub memory: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Bucket {
+    //most elements a key of the bucket has
+    pub upto: u64,
+    pub keys: u64,
+}
+
+/// where the memory goes, memory of keys read from an rdb is their size in the file
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryReport {
+    #[serde(skip)]
+    options: ReportOptions,
+    pub keys: u64,
+    pub memory: u64,
+    pub no_ttl: u64,
+    pub top: BTreeMap<String, Vec<BigKey>>,
+    //keys without separator are under the empty prefix
+    pub prefixes: BTreeMap<String, PrefixStats>,
+    //element counts of collections, strings are left out
+    pub histogram: Vec<Bucket>,
+}
+
+impl MemoryReport {
+    pub fn new(options: ReportOptions) -> MemoryReport {
+        MemoryReport {
+            options,
+            keys: 0,
+            memory: 0,
+            no_ttl: 0,
+            top: BTreeMap::new(),
+            prefixes: BTreeMap::new(),
+            histogram: BUCKETS
+                .iter()
+                .map(|&upto| Bucket { upto, keys: 0 })
+                .collect(),
+        }
+    }
+
+    pub fn prefix(&self, key: &[u8]) -> String {
+        let name = String::from_utf8_lossy(key);
+        let sep = self.options.separator.as_str();
+        if sep.is_empty() {
+            return String::new();
+        }
+        let parts: Vec<&str> = name.split(sep).collect();
+        let depth = self.options.depth.min(parts.len() - 1);
+        parts[..depth].join(sep)
+    }
+
+    pub fn add(&mut self, info: &KeyInfo) {
+        //gone between SCAN and TYPE
+        if info.kind == "none" {
+            return;
+        }
+        let memory = info.memory.unwrap_or(0);
+        self.keys += 1;
+        self.memory += memory;
+        if info.ttl == -1 {
+            self.no_ttl += 1;
+        }
+
+        let stats = self.prefixes.entry(self.prefix(&info.key)).or_default();
+        stats.keys += 1;
+        stats.memory += memory;
+
+        if let (Some(len), false) = (info.len, info.kind == "string") {
+            let at = BUCKETS.iter().position(|&upto| len <= upto).unwrap();
+            self.histogram[at].keys += 1;
+        }
+
+        let top = self.options.top;
+        let biggest = self.top.entry(info.kind.clone()).or_default();
+        if biggest.len() < top || biggest.last().is_some_and(|k| k.memory < memory) {
+            let at = biggest.partition_point(|k| k.memory >= memory);
+            biggest.insert(
+                at,
+                BigKey {
+                    key: info.name(),
+                    kind: info.kind.clone(),
+                    memory,
+                    len: info.len,
+                    ttl: info.ttl,
+                },
+            );
+            biggest.truncate(top);
+        }
+    }
+
+    pub fn no_ttl_share(&self) -> f64 {
+        if self.keys == 0 {
+            0.0
+        } else {
+            self.no_ttl as f64 / self.keys as f64
+        }
+    }
+
+    /// prefixes by memory, biggest first
+    pub fn prefixes_by_memory(&self) -> Vec<(&String, &PrefixStats)> {
+        let mut prefixes: Vec<_> = self.prefixes.iter().collect();
+        prefixes.sort_by(|a, b| b.1.memory.cmp(&a.1.memory).then(a.0.cmp(b.0)));
+        prefixes
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = serde_json::to_value(self).unwrap();
+        json["no_ttl_share"] = self.no_ttl_share().into();
+        serde_json::to_string_pretty(&json).unwrap()
+    }
+
+    /// one table, the section column tells top keys, prefixes, histogram and totals apart
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("section,name,type,keys,memory,len,ttl\n");
+        let mut row = |cells: &[&str]| {
+            let cells: Vec<String> = cells.iter().map(|c| csv_cell(c)).collect();
+            out.push_str(&cells.join(","));
+            out.push('\n');
+        };
+        for keys in self.top.values() {
+            for k in keys {
+                let len = k.len.map_or(String::new(), |l| l.to_string());
+                row(&[
+                    "top",
+                    &k.key,
+                    &k.kind,
+                    "1",
+                    &k.memory.to_string(),
+                    &len,
+                    &k.ttl.to_string(),
+                ]);
+            }
+        }
+        for (prefix, stats) in self.prefixes_by_memory() {
+            row(&[
+                "prefix",
+                prefix,
+                "",
+                &stats.keys.to_string(),
+                &stats.memory.to_string(),
+                "",
+                "",
+            ]);
+        }
+        for bucket in &self.histogram {
+            row(&[
+                "histogram",
+                &bucket_name(bucket.upto),
+                "",
+                &bucket.keys.to_string(),
+                "",
+                "",
+                "",
+            ]);
+        }
+        row(&[
+            "total",
+            "",
+            "",
+            &self.keys.to_string(),
+            &self.memory.to_string(),
+            "",
+            "",
+        ]);
+        row(&["no_ttl", "", "", &self.no_ttl.to_string(), "", "", ""]);
+        out
+    }
+}
+
+fn bucket_name(upto: u64) -> String {
+    if upto == u64::MAX {
+        format!(">{}", BUCKETS[BUCKETS.len() - 2])
+    } else {
+        format!("<={}", upto)
+    }
+}
+
+fn csv_cell(cell: &str) -> String {
+    if cell.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} keys, {} bytes, {:.1}% without ttl",
+            self.keys,
+            self.memory,
+            self.no_ttl_share() * 100.0
+        )?;
+        for (kind, keys) in &self.top {
+            writeln!(f, "biggest {}:", kind)?;
+            for k in keys {
+                writeln!(
+                    f,
+                    "\t{}\t{} bytes\t{} len",
+                    k.key,
+                    k.memory,
+                    k.len.unwrap_or(0)
+                )?;
+            }
+        }
+        writeln!(f, "prefixes:")?;
+        for (prefix, stats) in self.prefixes_by_memory() {
+            writeln!(
+                f,
+                "\t{}\t{} keys\t{} bytes",
+                prefix, stats.keys, stats.memory
+            )?;
+        }
+        writeln!(f, "element counts:")?;
+        for bucket in &self.histogram {
+            writeln!(f, "\t{}\t{} keys", bucket_name(bucket.upto), bucket.keys)?;
+        }
+        Ok(())
+    }
+}
+
+/// MEMORY USAGE of keys SCAN gives, stopping after limit keys when there is one
+pub fn sample_live(
+    holder: &mut ConnectionHolder,
+    filter: ScanFilter,
+    limit: Option<u64>,
+    options: ReportOptions,
+) -> RedisResult<MemoryReport> {
+    let mut report = MemoryReport::new(options);
+    let mut browser = KeyBrowser::new(filter);
+    let mut seen = 0;
+    while !browser.is_done() && limit.is_none_or(|l| seen < l) {
+        for info in browser.next_page(holder, 200)? {
+            seen += 1;
+            report.add(&info);
+        }
+    }
+    Ok(report)
+}
+
+/// every key of every db in the file
+pub fn report_rdb<R: Read>(
+    reader: RdbReader<R>,
+    now_ms: i64,
+    options: ReportOptions,
+) -> Result<MemoryReport, RdbError> {
+    let mut report = MemoryReport::new(options);
+    for entry in reader {
+        report.add(&entry?.key_info(now_ms));
+    }
+    Ok(report)
+}
+
+#[test]
+fn test_memory_report() {
+    let mut report = MemoryReport::new(ReportOptions {
+        top: 2,
+        ..Default::default()
+    });
+    report.add(&test_info("user:1", "hash", 5, 300, -1));
+    report.add(&test_info("user:2", "hash", 50, 900, 100));
+    report.add(&test_info("user:3", "hash", 20_000, 600, -1));
+    report.add(&test_info("session:a,b", "string", 4000, 100, 30));
+    report.add(&test_info("plain", "list", 1, 50, -1));
+    report.add(&test_info("gone", "none", 0, 0, -2));
+
+    assert_eq!((5, 1950, 3), (report.keys, report.memory, report.no_ttl));
+    assert_eq!(0.6, report.no_ttl_share());
+    let hashes: Vec<&str> = report.top["hash"].iter().map(|k| k.key.as_str()).collect();
+    assert_eq!(vec!["user:2", "user:3"], hashes);
+    let prefixes: Vec<(&str, u64)> = report
+        .prefixes_by_memory()
+        .iter()
+        .map(|(p, s)| (p.as_str(), s.memory))
+        .collect();
+    assert_eq!(vec![("user", 1800), ("session", 100), ("", 50)], prefixes);
+    let counts: Vec<u64> = report.histogram.iter().map(|b| b.keys).collect();
+    assert_eq!(vec![1, 1, 1, 0, 0, 1, 0], counts);
+
+    let csv = report.to_csv();
+    assert!(csv.contains("\ntop,\"session:a,b\",string,1,100,4000,30\n"));
+    assert!(csv.contains("\nprefix,user,,3,1800,,\n"));
+    assert!(csv.contains("\nhistogram,>100000,,0,,,\n"));
+    let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+    assert_eq!(900, json["top"]["hash"][0]["memory"]);
+    assert_eq!(0.6, json["no_ttl_share"]);
+
+    let deep = MemoryReport::new(ReportOptions {
+        depth: 2,
+        ..Default::default()
+    });
+    assert_eq!("a:b", deep.prefix(b"a:b:c:d"));
+    assert_eq!("a", deep.prefix(b"a:b"));
+}
+
+#[test]
+fn test_sample_live() -> RedisResult<()> {
+    let mut holder = ConnectionHolder::new();
+    holder.put("redis://127.0.0.1:6379/0")?;
+    holder.execute(&["DEL", "memrep:big", "memrep:small"])?;
+    holder.execute(&["SET", "memrep:big", &"x".repeat(10_000)])?;
+    holder.execute(&["SET", "memrep:small", "x"])?;
+    let report = sample_live(
+        &mut holder,
+        ScanFilter::matching("memrep:*"),
+        None,
+        ReportOptions::default(),
+    )?;
+    assert_eq!(2, report.keys);
+    assert_eq!("memrep:big", report.top["string"][0].key);
+    assert!(report.prefixes["memrep"].memory > 10_000);
+    holder.execute(&["DEL", "memrep:big", "memrep:small"])?;
+    Ok(())
+}
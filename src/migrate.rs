@@ -0,0 +1,407 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use redis::{RedisError, RedisResult, Value};
+
+use crate::browser::{KeyBrowser, ScanFilter};
+use crate::redis_conn::ConnectionHolder;
+use crate::transfer::{add_commands, fetch_records, Conflict};
+
+#[derive(Debug, Clone)]
+pub struct MigrateOptions {
+    pub conflict: Conflict,
+    //keys per SCAN page and per pipeline
+    pub batch: usize,
+    //unlink what was copied, a move instead of a copy
+    pub remove_source: bool,
+}
+
+impl Default for MigrateOptions {
+    fn default() -> Self {
+        MigrateOptions {
+            conflict: Conflict::Skip,
+            batch: 100,
+            remove_source: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MigrateStats {
+    pub scanned: u64,
+    pub migrated: u64,
+    //of migrated, the ones copied by type because RESTORE refused the payload
+    pub by_type: u64,
+    //existing on the target with the skip policy, or gone from the source meanwhile
+    pub skipped: u64,
+    //DUMP payload bytes
+    pub bytes: u64,
+    pub failed: Vec<(String, String)>,
+    started: Instant,
+}
+
+impl MigrateStats {
+    fn new() -> MigrateStats {
+        MigrateStats {
+            scanned: 0,
+            migrated: 0,
+            by_type: 0,
+            skipped: 0,
+            bytes: 0,
+            failed: vec![],
+            started: Instant::now(),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// keys and bytes per second
+    pub fn throughput(&self) -> (f64, f64) {
+        let secs = self.elapsed().as_secs_f64().max(0.001);
+        (self.migrated as f64 / secs, self.bytes as f64 / secs)
+    }
+}
+
+impl fmt::Display for MigrateStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (keys, bytes) = self.throughput();
+        write!(
+            f,
+            "{} scanned, {} migrated ({} by type), {} skipped, {} failed, {:.0} keys/s, {:.1} KB/s",
+            self.scanned,
+            self.migrated,
+            self.by_type,
+            self.skipped,
+            self.failed.len(),
+            keys,
+            bytes / 1024.0
+        )
+    }
+}
+
+fn client_err(msg: String) -> RedisError {
+    (redis::ErrorKind::ClientError, "migrate", msg).into()
+}
+
+//RESTORE refuses payloads of a newer rdb version than the server writes
+fn is_version_err(err: &RedisError) -> bool {
+    err.to_string().contains("payload version")
+}
+
+//RESTORE without REPLACE on a key that is there
+fn is_busy_err(err: &RedisError) -> bool {
+    err.code() == Some("BUSYKEY")
+}
+
+fn lossy(key: &[u8]) -> String {
+    String::from_utf8_lossy(key).into_owned()
+}
+
+/// keys the conflict policy lets through, the fail policy errors on the first existing one
+fn check_conflicts(
+    holder: &mut ConnectionHolder,
+    target: u32,
+    keys: Vec<Vec<u8>>,
+    policy: Conflict,
+    stats: &mut MigrateStats,
+) -> RedisResult<Vec<Vec<u8>>> {
+    if policy == Conflict::Replace {
+        return Ok(keys);
+    }
+    let mut pipe = redis::pipe();
+    for key in &keys {
+        pipe.cmd("EXISTS").arg(key.as_slice());
+    }
+    let exists: Vec<bool> = holder.pipeline_on(target, &pipe)?;
+    let mut free = Vec::with_capacity(keys.len());
+    for (key, exists) in keys.into_iter().zip(exists) {
+        match (exists, policy) {
+            (false, _) => free.push(key),
+            (true, Conflict::Fail) => {
+                return Err(client_err(format!("{} exists on the target", lossy(&key))))
+            }
+            (true, _) => stats.skipped += 1,
+        }
+    }
+    Ok(free)
+}
+
+//key, ttl for RESTORE and payload
+type Dump = (Vec<u8>, i64, Vec<u8>);
+
+//keys gone meanwhile are left out
+fn dump_keys(
+    holder: &mut ConnectionHolder,
+    source: u32,
+    keys: &[Vec<u8>],
+    stats: &mut MigrateStats,
+) -> RedisResult<Vec<Dump>> {
+    let mut pipe = redis::pipe();
+    for key in keys {
+        pipe.cmd("PTTL")
+            .arg(key.as_slice())
+            .cmd("DUMP")
+            .arg(key.as_slice());
+    }
+    let replies: Vec<(i64, Value)> = holder.pipeline_on(source, &pipe)?;
+    let mut dumps = Vec::with_capacity(keys.len());
+    for (key, (pttl, payload)) in keys.iter().zip(replies) {
+        match payload {
+            Value::Data(payload) if pttl != -2 => {
+                stats.bytes += payload.len() as u64;
+                dumps.push((key.clone(), pttl.max(0), payload));
+            }
+            _ => stats.skipped += 1,
+        }
+    }
+    Ok(dumps)
+}
+
+fn restore_cmd(key: &[u8], ttl: i64, payload: &[u8], replace: bool) -> redis::Cmd {
+    let mut cmd = redis::cmd("RESTORE");
+    cmd.arg(key).arg(ttl).arg(payload);
+    if replace {
+        cmd.arg("REPLACE");
+    }
+    cmd
+}
+
+/// whole values read by type on the source and written again, for servers too old for the payload
+fn copy_by_type(
+    holder: &mut ConnectionHolder,
+    target: u32,
+    keys: &[Vec<u8>],
+    replace: bool,
+    stats: &mut MigrateStats,
+) -> RedisResult<Vec<Vec<u8>>> {
+    let records = fetch_records(holder, keys)?;
+    let mut pipe = redis::pipe();
+    let mut copied = Vec::new();
+    for (key, record) in keys.iter().zip(records) {
+        let record = match record {
+            Some(record) => record,
+            //module types have no generic way to copy them
+            None => {
+                stats
+                    .failed
+                    .push((lossy(key), "no type copy for it".to_string()));
+                continue;
+            }
+        };
+        if replace {
+            pipe.cmd("DEL").arg(key.as_slice()).ignore();
+        }
+        add_commands(&mut pipe, key, &record).map_err(|err| client_err(err.to_string()))?;
+        copied.push(key.clone());
+    }
+    //refused by safe mode is an error of the whole run, not of these keys
+    holder.guard_pipeline(target, &pipe)?;
+    if let Err(err) = holder.pipeline_on::<()>(target, &pipe) {
+        for key in copied.drain(..) {
+            stats.failed.push((lossy(&key), err.to_string()));
+        }
+    }
+    stats.by_type += copied.len() as u64;
+    Ok(copied)
+}
+
+/// copy keys the filter matches from source to target connection with DUMP and RESTORE, ttls kept.
+/// the current connection of the holder is used for SCAN and is the same again afterwards
+pub fn migrate(
+    holder: &mut ConnectionHolder,
+    source: u32,
+    target: u32,
+    filter: ScanFilter,
+    options: &MigrateOptions,
+    progress: &mut dyn FnMut(&MigrateStats),
+) -> RedisResult<MigrateStats> {
+    if same_server(holder, source, target) {
+        return Err(client_err("the target is the source".to_string()));
+    }
+    let current = holder.current().map(|c| c.id);
+    holder.switch(&format!("#{}", source))?;
+    let mut stats = MigrateStats::new();
+    let done = run(
+        holder, source, target, filter, options, progress, &mut stats,
+    );
+    if let Some(id) = current {
+        holder.switch(&format!("#{}", id))?;
+    }
+    done.map(|_| stats)
+}
+
+/// two connections to the same address and db, restoring onto it could only clash
+/// and removing the source would lose the keys
+fn same_server(holder: &ConnectionHolder, source: u32, target: u32) -> bool {
+    if source == target {
+        return true;
+    }
+    let find = |id| holder.list().iter().find(|c| c.id == id);
+    match (find(source), find(target)) {
+        (Some(s), Some(t)) => s.info().addr == t.info().addr && s.db() == t.db(),
+        _ => false,
+    }
+}
+
+fn run(
+    holder: &mut ConnectionHolder,
+    source: u32,
+    target: u32,
+    filter: ScanFilter,
+    options: &MigrateOptions,
+    progress: &mut dyn FnMut(&MigrateStats),
+    stats: &mut MigrateStats,
+) -> RedisResult<()> {
+    let replace = options.conflict == Conflict::Replace;
+    let mut browser = KeyBrowser::new(filter);
+    //set once RESTORE refused a payload version, the rest goes by type
+    let mut by_type = false;
+    while !browser.is_done() {
+        let keys = browser.next_names(holder, options.batch)?;
+        stats.scanned += keys.len() as u64;
+        let keys = check_conflicts(holder, target, keys, options.conflict, stats)?;
+        if keys.is_empty() {
+            continue;
+        }
+
+        let mut moved = Vec::new();
+        let mut retry = Vec::new();
+        if by_type {
+            retry = keys;
+        } else {
+            let dumps = dump_keys(holder, source, &keys, stats)?;
+            let mut pipe = redis::pipe();
+            for (key, ttl, payload) in &dumps {
+                pipe.add_command(restore_cmd(key, *ttl, payload, replace))
+                    .ignore();
+            }
+            holder.guard_pipeline(target, &pipe)?;
+            match holder.pipeline_on::<()>(target, &pipe) {
+                Ok(()) => moved.extend(dumps.into_iter().map(|(key, _, _)| key)),
+                //one refused RESTORE fails the pipeline but the others ran, so one by one
+                //to tell which. BUSYKEY is the pipeline's own RESTORE only if the target
+                //holds the same payload, another client may have written the key meanwhile
+                Err(_) => {
+                    for (key, ttl, payload) in dumps {
+                        let cmd = restore_cmd(&key, ttl, &payload, replace);
+                        match holder.cmd_on::<()>(target, &cmd) {
+                            Ok(()) => moved.push(key),
+                            Err(err) if is_busy_err(&err) => {
+                                let there: Option<Vec<u8>> =
+                                    holder.cmd_on(target, redis::cmd("DUMP").arg(&key[..]))?;
+                                if there.as_deref() == Some(&payload[..]) {
+                                    moved.push(key);
+                                } else {
+                                    //left on the source, never unlinked
+                                    stats.skipped += 1;
+                                }
+                            }
+                            Err(err) if is_version_err(&err) => {
+                                by_type = true;
+                                retry.push(key);
+                            }
+                            Err(err) => stats.failed.push((lossy(&key), err.to_string())),
+                        }
+                    }
+                }
+            }
+        }
+        if !retry.is_empty() {
+            moved.extend(copy_by_type(holder, target, &retry, replace, stats)?);
+        }
+        stats.migrated += moved.len() as u64;
+
+        if options.remove_source && !moved.is_empty() {
+            holder.cmd_on::<()>(source, redis::cmd("UNLINK").arg(moved))?;
+        }
+        progress(stats);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_migrate_stats() {
+    let mut stats = MigrateStats::new();
+    stats.scanned = 10;
+    stats.migrated = 8;
+    stats.by_type = 2;
+    stats.skipped = 1;
+    stats.failed.push(("k".to_string(), "ERR".to_string()));
+    let text = stats.to_string();
+    assert!(text.starts_with("10 scanned, 8 migrated (2 by type), 1 skipped, 1 failed, "));
+    assert!(stats.throughput().0 > 0.0);
+    let err: RedisError = (
+        redis::ErrorKind::ResponseError,
+        "ERR",
+        "DUMP payload version or checksum are wrong".to_string(),
+    )
+        .into();
+    assert!(is_version_err(&err));
+    assert!(!is_busy_err(&err));
+    //as the server sends it, the code only comes out of the parser
+    let err = redis::parse_redis_value(b"-BUSYKEY Target key name already exists.\r\n");
+    assert!(is_busy_err(&err.unwrap_err()));
+}
+
+#[test]
+fn test_migrate_between_dbs() -> RedisResult<()> {
+    let mut holder = ConnectionHolder::new();
+    let target = holder.put("redis://127.0.0.1:6379/1")?;
+    holder.execute(&["DEL", "mig:a", "mig:b", "mig:c"])?;
+    holder.execute(&["SET", "mig:b", "old"])?;
+    let source = holder.put("redis://127.0.0.1:6379/0")?;
+    holder.execute(&["DEL", "mig:a", "mig:b", "mig:c"])?;
+    holder.execute(&["SET", "mig:a", "1", "PX", "100000"])?;
+    holder.execute(&["SET", "mig:b", "new"])?;
+    holder.execute(&["RPUSH", "mig:c", "x", "y"])?;
+
+    let options = MigrateOptions::default();
+    let again = holder.put("redis://127.0.0.1:6379/0")?;
+    for to in [source, again] {
+        let all = ScanFilter::matching("*");
+        let same = migrate(&mut holder, source, to, all, &options, &mut |_| {});
+        assert!(same.is_err());
+    }
+    holder.switch(&format!("#{}", source))?;
+
+    let mut calls = 0;
+    let stats = migrate(
+        &mut holder,
+        source,
+        target,
+        ScanFilter::matching("mig:*"),
+        &options,
+        &mut |_| calls += 1,
+    )?;
+    assert!(calls > 0);
+    assert_eq!((3, 2, 1), (stats.scanned, stats.migrated, stats.skipped));
+    assert_eq!(Some(source), holder.current().map(|c| c.id));
+
+    let options = MigrateOptions {
+        conflict: Conflict::Replace,
+        remove_source: true,
+        ..Default::default()
+    };
+    let stats = migrate(
+        &mut holder,
+        source,
+        target,
+        ScanFilter::matching("mig:*"),
+        &options,
+        &mut |_| {},
+    )?;
+    assert_eq!(3, stats.migrated);
+    let left: i64 =
+        redis::from_redis_value(&holder.execute(&["EXISTS", "mig:a", "mig:b", "mig:c"])?)?;
+    assert_eq!(0, left);
+
+    holder.switch(&format!("#{}", target))?;
+    let val: String = redis::from_redis_value(&holder.execute(&["GET", "mig:b"])?)?;
+    assert_eq!("new", val);
+    let pttl: i64 = redis::from_redis_value(&holder.execute(&["PTTL", "mig:a"])?)?;
+    assert!(pttl > 0);
+    holder.execute(&["DEL", "mig:a", "mig:b", "mig:c"])?;
+    Ok(())
+}
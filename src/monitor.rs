@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use redis::{ConnectionInfo, RedisResult, Value};
+
+use crate::aof::command_keys;
+use crate::browser::glob_match;
+use crate::cli::split_args;
+use crate::viewer::edit_arg;
+
+/// one line MONITOR sent, as 1339518083.107412 [0 127.0.0.1:60866] "keys" "*"
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorLine {
+    //unix seconds with microseconds
+    pub time: f64,
+    pub db: i64,
+    //address, unix:path for a socket, lua for scripts
+    pub client: String,
+    pub args: Vec<Vec<u8>>,
+}
+
+impl MonitorLine {
+    pub fn parse(line: &str) -> Option<MonitorLine> {
+        let (time, rest) = line.split_once(' ')?;
+        let rest = rest.strip_prefix('[')?;
+        let (source, args) = rest.split_once("] ")?;
+        let (db, client) = source.split_once(' ')?;
+        let args = split_args(args.as_bytes()).ok()?;
+        if args.is_empty() {
+            return None;
+        }
+        Some(MonitorLine {
+            time: time.parse().ok()?,
+            db: db.parse().ok()?,
+            client: client.to_string(),
+            args,
+        })
+    }
+
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.args[0]).to_uppercase()
+    }
+
+    pub fn keys(&self) -> Vec<&[u8]> {
+        command_keys(&self.args)
+    }
+}
+
+impl fmt::Display for MonitorLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time =
+            chrono::DateTime::from_timestamp(self.time as i64, (self.time.fract() * 1e9) as u32)
+                .map_or(String::new(), |t| {
+                    let t = t.with_timezone(&chrono::Local);
+                    t.format("%H:%M:%S%.3f").to_string()
+                });
+        let args: Vec<String> = self.args.iter().map(|a| edit_arg(a)).collect();
+        write!(
+            f,
+            "{} [{} {}] {}",
+            time,
+            self.db,
+            self.client,
+            args.join(" ")
+        )
+    }
+}
+
+/// what lines to keep, an empty filter keeps everything
+#[derive(Debug, Clone, Default)]
+pub struct MonitorFilter {
+    //command names, any case
+    pub commands: Vec<String>,
+    //glob on the key arguments
+    pub pattern: Option<String>,
+    //the client address starts with it, so an ip takes all its ports
+    pub client: Option<String>,
+}
+
+impl MonitorFilter {
+    pub fn matches(&self, line: &MonitorLine) -> bool {
+        let name = line.name();
+        let named =
+            self.commands.is_empty() || self.commands.iter().any(|c| c.eq_ignore_ascii_case(&name));
+        let keyed = match &self.pattern {
+            None => true,
+            Some(pattern) => line
+                .keys()
+                .iter()
+                .any(|k| glob_match(pattern.as_bytes(), k)),
+        };
+        let from = self
+            .client
+            .as_ref()
+            .is_none_or(|c| line.client.starts_with(c.as_str()));
+        named && keyed && from
+    }
+}
+
+/// when to stop, MONITOR costs the server on every command so there always is a limit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MonitorLimit {
+    pub duration: Option<Duration>,
+    //lines that passed the filter
+    pub count: Option<u64>,
+}
+
+impl Default for MonitorLimit {
+    fn default() -> Self {
+        MonitorLimit {
+            duration: Some(Duration::from_secs(60)),
+            count: None,
+        }
+    }
+}
+
+/// running counts of the lines that passed the filter
+#[derive(Debug, Clone)]
+pub struct MonitorStats {
+    //every line MONITOR sent
+    pub seen: u64,
+    pub matched: u64,
+    pub commands: HashMap<String, u64>,
+    pub keys: HashMap<Vec<u8>, u64>,
+    pub clients: HashMap<String, u64>,
+    started: Instant,
+}
+
+impl MonitorStats {
+    pub fn new() -> MonitorStats {
+        MonitorStats {
+            seen: 0,
+            matched: 0,
+            commands: HashMap::new(),
+            keys: HashMap::new(),
+            clients: HashMap::new(),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn add(&mut self, line: &MonitorLine) {
+        self.matched += 1;
+        *self.commands.entry(line.name()).or_default() += 1;
+        for key in line.keys() {
+            *self.keys.entry(key.to_vec()).or_default() += 1;
+        }
+        *self.clients.entry(line.client.clone()).or_default() += 1;
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// the n most frequent, ties by name
+    fn top<K: Ord + Clone>(counts: &HashMap<K, u64>, n: usize) -> Vec<(K, u64)> {
+        let mut top: Vec<(K, u64)> = counts.iter().map(|(k, c)| (k.clone(), *c)).collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        top.truncate(n);
+        top
+    }
+
+    pub fn top_commands(&self, n: usize) -> Vec<(String, u64)> {
+        Self::top(&self.commands, n)
+    }
+
+    /// hot keys
+    pub fn top_keys(&self, n: usize) -> Vec<(Vec<u8>, u64)> {
+        Self::top(&self.keys, n)
+    }
+
+    /// commands per second of the busiest clients
+    pub fn client_rates(&self, n: usize) -> Vec<(String, f64)> {
+        let secs = self.elapsed().as_secs_f64().max(0.001);
+        Self::top(&self.clients, n)
+            .into_iter()
+            .map(|(client, count)| (client, count as f64 / secs))
+            .collect()
+    }
+
+    /// the top n of each, one block per table
+    pub fn report(&self, n: usize) -> String {
+        let secs = self.elapsed().as_secs_f64().max(0.001);
+        let mut out = format!(
+            "{} of {} lines matched in {:.1}s, {:.0}/s\n",
+            self.matched,
+            self.seen,
+            secs,
+            self.matched as f64 / secs
+        );
+        out.push_str("top commands:\n");
+        for (name, count) in self.top_commands(n) {
+            out.push_str(&format!("\t{}\t{}\n", name, count));
+        }
+        out.push_str("top keys:\n");
+        for (key, count) in self.top_keys(n) {
+            out.push_str(&format!("\t{}\t{}\n", edit_arg(&key), count));
+        }
+        out.push_str("clients:\n");
+        for (client, rate) in self.client_rates(n) {
+            out.push_str(&format!("\t{}\t{:.1}/s\n", client, rate));
+        }
+        out
+    }
+}
+
+impl Default for MonitorStats {
+    fn default() -> Self {
+        MonitorStats::new()
+    }
+}
+
+//how often the loop wakes up without lines to check stop and the time limit
+const POLL: Duration = Duration::from_millis(200);
+
+/// MONITOR on a connection of its own until the limit is reached or stop is set,
+/// matching lines go to on_line with the stats they were counted in
+pub fn monitor(
+    info: ConnectionInfo,
+    filter: &MonitorFilter,
+    limit: MonitorLimit,
+    stop: &AtomicBool,
+    on_line: &mut dyn FnMut(&MonitorLine, &MonitorStats),
+) -> RedisResult<MonitorStats> {
+    let mut conn = redis::Client::open(info)?.get_connection()?;
+    redis::cmd("MONITOR").query::<()>(&mut conn)?;
+    conn.set_read_timeout(Some(POLL))?;
+    let mut stats = MonitorStats::new();
+    while !stop.load(Ordering::Relaxed)
+        && limit.duration.is_none_or(|d| stats.elapsed() < d)
+        && limit.count.is_none_or(|c| stats.matched < c)
+    {
+        let text = match conn.recv_response() {
+            Ok(Value::Status(text)) => text,
+            Ok(Value::Data(data)) => String::from_utf8_lossy(&data).into_owned(),
+            Ok(_) => continue,
+            Err(err) if err.is_timeout() => continue,
+            Err(err) => return Err(err),
+        };
+        stats.seen += 1;
+        let line = match MonitorLine::parse(&text) {
+            Some(line) => line,
+            None => continue,
+        };
+        if filter.matches(&line) {
+            stats.add(&line);
+            on_line(&line, &stats);
+        }
+    }
+    //the connection is dropped rather than reused, the server ends MONITOR with it
+    Ok(stats)
+}
+
+#[test]
+fn test_monitor_lines() {
+    let line =
+        MonitorLine::parse(r#"1339518083.107412 [0 127.0.0.1:60866] "set" "user:1" "a \"b\"\x00""#)
+            .unwrap();
+    assert_eq!((0, "127.0.0.1:60866"), (line.db, line.client.as_str()));
+    assert_eq!(1339518083, line.time as i64);
+    assert_eq!(b"a \"b\"\x00".to_vec(), line.args[2]);
+    assert_eq!(vec![&b"user:1"[..]], line.keys());
+    let lua = MonitorLine::parse(r#"1339518083.2 [3 lua] "get" "k""#).unwrap();
+    assert_eq!((3, "lua"), (lua.db, lua.client.as_str()));
+    assert_eq!(None, MonitorLine::parse("OK"));
+    assert_eq!(None, MonitorLine::parse("1.0 [0 x] \"open"));
+
+    let filter = MonitorFilter {
+        commands: vec!["set".to_string(), "MGET".to_string()],
+        pattern: Some("user:*".to_string()),
+        client: Some("127.0.0.1:".to_string()),
+    };
+    assert!(filter.matches(&line));
+    assert!(!filter.matches(&lua));
+    let mget = MonitorLine::parse(r#"1.5 [0 127.0.0.1:1] "MGET" "a" "user:2""#).unwrap();
+    assert!(filter.matches(&mget));
+    let info = MonitorLine::parse(r#"1.5 [0 127.0.0.1:1] "set" "session:1" "x""#).unwrap();
+    assert!(!filter.matches(&info));
+
+    let mut stats = MonitorStats::new();
+    for line in [&line, &lua, &mget, &mget] {
+        stats.add(line);
+    }
+    assert_eq!(4, stats.matched);
+    assert_eq!(
+        vec![("MGET".to_string(), 2), ("GET".to_string(), 1)],
+        stats.top_commands(2)
+    );
+    assert_eq!(
+        vec![(b"a".to_vec(), 2), (b"user:2".to_vec(), 2)],
+        stats.top_keys(2)
+    );
+    let rates = stats.client_rates(5);
+    assert_eq!("127.0.0.1:1", rates[0].0);
+    assert_eq!(3, rates.len());
+    stats.seen = 6;
+    let report = stats.report(1);
+    assert!(report.starts_with("4 of 6 lines matched"));
+    assert!(report.contains("top keys:\n\ta\t2\n"));
+}
+
+#[test]
+fn test_monitor_count_limit() -> RedisResult<()> {
+    use redis::IntoConnectionInfo;
+    let info = "redis://127.0.0.1:6379/0".into_connection_info()?;
+    let writer = std::thread::spawn(|| -> RedisResult<()> {
+        let mut conn = redis::Client::open("redis://127.0.0.1:6379/0")?.get_connection()?;
+        for _ in 0..50 {
+            std::thread::sleep(Duration::from_millis(20));
+            redis::cmd("GET")
+                .arg("monitor:hot")
+                .query::<Option<String>>(&mut conn)?;
+        }
+        Ok(())
+    });
+    let filter = MonitorFilter {
+        pattern: Some("monitor:*".to_string()),
+        ..Default::default()
+    };
+    let limit = MonitorLimit {
+        duration: Some(Duration::from_secs(5)),
+        count: Some(3),
+    };
+    let stop = AtomicBool::new(false);
+    let mut lines = 0;
+    let stats = monitor(info, &filter, limit, &stop, &mut |_, _| lines += 1)?;
+    assert_eq!((3, 3), (lines, stats.matched));
+    assert_eq!(vec![(b"monitor:hot".to_vec(), 3)], stats.top_keys(1));
+    writer.join().unwrap()
+}
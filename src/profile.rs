@@ -0,0 +1,579 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use redis::{ConnectionAddr, ConnectionInfo, IntoConnectionInfo, RedisResult};
+use serde::{Deserialize, Serialize};
+
+use crate::cli::data_dir;
+use crate::redis_conn::ConnectionHolder;
+use crate::value::{DecoderChain, DecoderRule};
+
+//service name passwords are filed under in the os keyring
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "rust_a.redis";
+
+const COLORS: [(&str, &str); 6] = [
+    ("red", "31"),
+    ("green", "32"),
+    ("yellow", "33"),
+    ("blue", "34"),
+    ("magenta", "35"),
+    ("cyan", "36"),
+];
+
+#[derive(Debug)]
+pub enum ProfileError {
+    NotFound(String),
+    Duplicate(String),
+    //unknown key or a value which does not parse, for add and edit
+    BadSetting(String),
+    //the env var or keyring entry holding a password is missing
+    Secret(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileError::NotFound(name) => write!(f, "no profile named {}", name),
+            ProfileError::Duplicate(name) => write!(f, "profile {} already exists", name),
+            ProfileError::BadSetting(msg) => write!(f, "bad setting: {}", msg),
+            ProfileError::Secret(msg) => write!(f, "password unavailable: {}", msg),
+            ProfileError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<io::Error> for ProfileError {
+    fn from(err: io::Error) -> Self {
+        ProfileError::Io(err)
+    }
+}
+
+/// where a password comes from, the profiles file itself never holds one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Secret {
+    //name of an environment variable holding the password
+    Env(String),
+    //os keyring entry under the profile name, needs the keyring feature
+    Keyring,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Tls {
+    //skip certificate hostname verification
+    #[serde(default)]
+    pub insecure: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub db: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<Secret>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<Tls>,
+    #[serde(default)]
+    pub read_only: bool,
+    //destructive bulk operations ask before they run
+    #[serde(default)]
+    pub production: bool,
+    //tag shown on the prompt so a production server stands out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    //which decoder reads the values of which keys
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub decoders: Vec<DecoderRule>,
+}
+
+fn default_port() -> u16 {
+    6379
+}
+
+fn parse_flag(key: &str, value: &str) -> Result<bool, ProfileError> {
+    match value {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(ProfileError::BadSetting(format!("{}={}", key, value))),
+    }
+}
+
+impl Profile {
+    pub fn new(name: &str) -> Profile {
+        Profile {
+            name: name.to_string(),
+            host: "127.0.0.1".to_string(),
+            port: default_port(),
+            db: 0,
+            username: None,
+            password: None,
+            tls: None,
+            read_only: false,
+            production: false,
+            color: None,
+            decoders: vec![],
+        }
+    }
+
+    /// apply one key=value setting, an empty value clears optional settings.
+    /// password takes env:VAR or keyring, a literal password is refused,
+    /// decoder takes pattern=name and an empty name drops the pattern's rule
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ProfileError> {
+        let bad = || ProfileError::BadSetting(format!("{}={}", key, value));
+        let opt = |value: &str| Some(value.to_string()).filter(|v| !v.is_empty());
+        match key {
+            "host" => self.host = value.to_string(),
+            "port" => self.port = value.parse().map_err(|_| bad())?,
+            "db" => self.db = value.parse().map_err(|_| bad())?,
+            "user" | "username" => self.username = opt(value),
+            "password" => {
+                self.password = match value.split_once(':') {
+                    Some(("env", var)) if !var.is_empty() => Some(Secret::Env(var.to_string())),
+                    None if value == "keyring" => Some(Secret::Keyring),
+                    None if value.is_empty() => None,
+                    _ => return Err(bad()),
+                }
+            }
+            "tls" => self.tls = parse_flag(key, value)?.then(Tls::default),
+            "tls-insecure" => {
+                let insecure = parse_flag(key, value)?;
+                self.tls.get_or_insert_with(Tls::default).insecure = insecure;
+            }
+            "read-only" => self.read_only = parse_flag(key, value)?,
+            "production" => self.production = parse_flag(key, value)?,
+            "decoder" => {
+                let (pattern, decoder) = value.rsplit_once('=').ok_or_else(bad)?;
+                if pattern.is_empty() {
+                    return Err(bad());
+                }
+                self.decoders.retain(|r| r.pattern != pattern);
+                if !decoder.is_empty() {
+                    let rule = DecoderRule {
+                        pattern: pattern.to_string(),
+                        decoder: decoder.to_string(),
+                    };
+                    DecoderChain::builtin()
+                        .add_rule(rule.clone())
+                        .map_err(ProfileError::BadSetting)?;
+                    self.decoders.push(rule);
+                }
+            }
+            "color" => {
+                if !value.is_empty() && !COLORS.iter().any(|(name, _)| *name == value) {
+                    return Err(bad());
+                }
+                self.color = opt(value);
+            }
+            _ => return Err(bad()),
+        }
+        Ok(())
+    }
+
+    /// ansi sgr code of the color tag, eg 31 for red
+    pub fn ansi_color(&self) -> Option<&'static str> {
+        let color = self.color.as_deref()?;
+        COLORS
+            .iter()
+            .find(|(name, _)| *name == color)
+            .map(|(_, code)| *code)
+    }
+
+    pub fn resolve_password(&self) -> Result<Option<String>, ProfileError> {
+        match &self.password {
+            None => Ok(None),
+            Some(Secret::Env(var)) => std::env::var(var)
+                .map(Some)
+                .map_err(|_| ProfileError::Secret(format!("env var {} is not set", var))),
+            Some(Secret::Keyring) => keyring_password(&self.name).map(Some),
+        }
+    }
+
+    pub fn connection_info(&self) -> Result<ConnectionInfo, ProfileError> {
+        let addr = match &self.tls {
+            Some(tls) => ConnectionAddr::TcpTls {
+                host: self.host.clone(),
+                port: self.port,
+                insecure: tls.insecure,
+            },
+            None => ConnectionAddr::Tcp(self.host.clone(), self.port),
+        };
+        Ok(ConnectionInfo {
+            addr: Box::new(addr),
+            db: self.db,
+            username: self.username.clone(),
+            passwd: self.resolve_password()?,
+        })
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = if self.tls.is_some() {
+            "rediss"
+        } else {
+            "redis"
+        };
+        write!(f, "{}\t{}://", self.name, scheme)?;
+        if let Some(user) = &self.username {
+            write!(f, "{}@", user)?;
+        }
+        write!(f, "{}:{}/{}", self.host, self.port, self.db)?;
+        match &self.password {
+            Some(Secret::Env(var)) => write!(f, " password=env:{}", var)?,
+            Some(Secret::Keyring) => write!(f, " password=keyring")?,
+            None => {}
+        }
+        if self.tls.as_ref().is_some_and(|tls| tls.insecure) {
+            write!(f, " tls-insecure")?;
+        }
+        if self.read_only {
+            write!(f, " read-only")?;
+        }
+        if self.production {
+            write!(f, " production")?;
+        }
+        if let Some(color) = &self.color {
+            write!(f, " color={}", color)?;
+        }
+        for rule in &self.decoders {
+            write!(f, " decoder={}", rule)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_entry(name: &str) -> Result<keyring::Entry, ProfileError> {
+    keyring::Entry::new(KEYRING_SERVICE, name).map_err(|err| ProfileError::Secret(err.to_string()))
+}
+
+#[cfg(feature = "keyring")]
+pub fn keyring_password(name: &str) -> Result<String, ProfileError> {
+    keyring_entry(name)?
+        .get_password()
+        .map_err(|err| ProfileError::Secret(format!("keyring entry {}: {}", name, err)))
+}
+
+#[cfg(feature = "keyring")]
+pub fn store_keyring_password(name: &str, password: &str) -> Result<(), ProfileError> {
+    keyring_entry(name)?
+        .set_password(password)
+        .map_err(|err| ProfileError::Secret(err.to_string()))
+}
+
+#[cfg(feature = "keyring")]
+pub fn delete_keyring_password(name: &str) -> Result<(), ProfileError> {
+    match keyring_entry(name)?.delete_password() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(err) => Err(ProfileError::Secret(err.to_string())),
+    }
+}
+
+/// the entry follows a renamed profile, nothing to move when there is none
+#[cfg(feature = "keyring")]
+fn move_keyring_password(name: &str, new_name: &str) -> Result<(), ProfileError> {
+    let password = match keyring_entry(name)?.get_password() {
+        Ok(password) => password,
+        Err(keyring::Error::NoEntry) => return Ok(()),
+        Err(err) => return Err(ProfileError::Secret(err.to_string())),
+    };
+    store_keyring_password(new_name, &password)?;
+    delete_keyring_password(name)
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn keyring_password(_name: &str) -> Result<String, ProfileError> {
+    Err(ProfileError::Secret(
+        "built without the keyring feature".to_string(),
+    ))
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn store_keyring_password(name: &str, _password: &str) -> Result<(), ProfileError> {
+    keyring_password(name).map(|_| ())
+}
+
+//without the feature nothing can have been stored
+#[cfg(not(feature = "keyring"))]
+pub fn delete_keyring_password(_name: &str) -> Result<(), ProfileError> {
+    Ok(())
+}
+
+#[cfg(not(feature = "keyring"))]
+fn move_keyring_password(_name: &str, _new_name: &str) -> Result<(), ProfileError> {
+    Ok(())
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Profiles {
+    pub profiles: Vec<Profile>,
+}
+
+impl Profiles {
+    pub fn default_path() -> PathBuf {
+        data_dir().join("profiles.json")
+    }
+
+    /// a missing file is an empty profile list
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Profiles, ProfileError> {
+        match fs::read(path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .map_err(|err| ProfileError::Io(io::Error::new(io::ErrorKind::InvalidData, err))),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Profiles::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ProfileError> {
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(self).expect("profiles always serialize");
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    fn get_mut(&mut self, name: &str) -> Result<&mut Profile, ProfileError> {
+        self.profiles
+            .iter_mut()
+            .find(|p| p.name == name)
+            .ok_or_else(|| ProfileError::NotFound(name.to_string()))
+    }
+
+    pub fn add(&mut self, profile: Profile) -> Result<(), ProfileError> {
+        if self.get(&profile.name).is_some() {
+            return Err(ProfileError::Duplicate(profile.name));
+        }
+        self.profiles.push(profile);
+        Ok(())
+    }
+
+    /// apply key=value settings to a profile, nothing changes if one of them is bad
+    pub fn edit(&mut self, name: &str, settings: &[(&str, &str)]) -> Result<(), ProfileError> {
+        let mut edited = self.get_mut(name)?.clone();
+        for (key, value) in settings {
+            edited.set(key, value)?;
+        }
+        *self.get_mut(name)? = edited;
+        Ok(())
+    }
+
+    pub fn rename(&mut self, name: &str, new_name: &str) -> Result<(), ProfileError> {
+        if self.get(new_name).is_some() {
+            return Err(ProfileError::Duplicate(new_name.to_string()));
+        }
+        let profile = self.get_mut(name)?;
+        //the keyring entry is filed under the profile name
+        if profile.password == Some(Secret::Keyring) {
+            move_keyring_password(name, new_name)?;
+        }
+        profile.name = new_name.to_string();
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<Profile, ProfileError> {
+        let idx = self
+            .profiles
+            .iter()
+            .position(|p| p.name == name)
+            .ok_or_else(|| ProfileError::NotFound(name.to_string()))?;
+        if self.profiles[idx].password == Some(Secret::Keyring) {
+            delete_keyring_password(name)?;
+        }
+        Ok(self.profiles.remove(idx))
+    }
+}
+
+/// what a console connects to, resolved from a uri or a profile name
+pub struct Target {
+    //profile name, or the address for a plain uri
+    pub name: String,
+    pub info: ConnectionInfo,
+    pub color: Option<&'static str>,
+    pub decoders: Vec<DecoderRule>,
+    pub production: bool,
+    pub read_only: bool,
+}
+
+impl Target {
+    /// connect under name, in safe mode when the profile is read-only
+    pub fn open(&self, holder: &mut ConnectionHolder, name: &str) -> RedisResult<u32> {
+        let id = holder.put_named(name, self.info.clone())?;
+        if self.read_only {
+            holder.set_safe_mode(id, true)?;
+        }
+        Ok(id)
+    }
+}
+
+/// anything with :// is a uri, the rest are names in the default profiles file
+pub fn resolve_target(target: &str) -> Result<Target, ProfileError> {
+    if target.contains("://") {
+        let info = target
+            .into_connection_info()
+            .map_err(|err| ProfileError::BadSetting(format!("{}: {}", target, err)))?;
+        return Ok(Target {
+            name: info.addr.to_string(),
+            info,
+            color: None,
+            decoders: vec![],
+            production: false,
+            read_only: false,
+        });
+    }
+    let profiles = Profiles::load(Profiles::default_path())?;
+    let profile = profiles
+        .get(target)
+        .ok_or_else(|| ProfileError::NotFound(target.to_string()))?;
+    Ok(Target {
+        name: profile.name.clone(),
+        info: profile.connection_info()?,
+        color: profile.ansi_color(),
+        decoders: profile.decoders.clone(),
+        production: profile.production,
+        read_only: profile.read_only,
+    })
+}
+
+fn parse_settings(args: &[String]) -> Result<Vec<(&str, &str)>, ProfileError> {
+    args.iter()
+        .map(|arg| {
+            arg.split_once('=')
+                .ok_or_else(|| ProfileError::BadSetting(arg.clone()))
+        })
+        .collect()
+}
+
+/// profile add|edit|remove|list|rename, returns what to print.
+/// settings are key=value: host port db user password tls tls-insecure read-only production color
+pub fn profile_command(profiles: &mut Profiles, args: &[String]) -> Result<String, ProfileError> {
+    let usage = "usage: profile add|edit <name> [key=value]... | remove <name> | rename <name> <new name> | list";
+    let name = args.get(1).map(String::as_str);
+    match (args.first().map(String::as_str), name) {
+        (Some("add"), Some(name)) => {
+            let mut profile = Profile::new(name);
+            for (key, value) in parse_settings(&args[2..])? {
+                profile.set(key, value)?;
+            }
+            let line = profile.to_string();
+            profiles.add(profile)?;
+            Ok(format!("added {}", line))
+        }
+        (Some("edit"), Some(name)) => {
+            profiles.edit(name, &parse_settings(&args[2..])?)?;
+            Ok(format!("edited {}", profiles.get(name).unwrap()))
+        }
+        (Some("rename"), Some(name)) if args.len() == 3 => {
+            profiles.rename(name, &args[2])?;
+            Ok(format!("renamed {} to {}", name, args[2]))
+        }
+        (Some("remove"), Some(name)) => {
+            profiles.remove(name)?;
+            Ok(format!("removed {}", name))
+        }
+        (Some("list"), None) => Ok(profiles
+            .profiles
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<String>>()
+            .join("\n")),
+        _ => Err(ProfileError::BadSetting(usage.to_string())),
+    }
+}
+
+#[cfg(test)]
+fn args(line: &str) -> Vec<String> {
+    line.split(' ').map(String::from).collect()
+}
+
+#[test]
+fn test_profile_commands() {
+    let mut profiles = Profiles::default();
+    profile_command(
+        &mut profiles,
+        &args("add prod host=10.0.0.5 port=6380 db=2 user=ops password=env:PROD_REDIS_PASS tls=on read-only=yes production=yes color=red"),
+    )
+    .unwrap();
+    profile_command(&mut profiles, &args("add local")).unwrap();
+    assert!(matches!(
+        profile_command(&mut profiles, &args("add local")),
+        Err(ProfileError::Duplicate(_))
+    ));
+    assert!(matches!(
+        profile_command(&mut profiles, &args("edit local password=hunter2")),
+        Err(ProfileError::BadSetting(_))
+    ));
+    assert!(matches!(
+        profile_command(&mut profiles, &args("edit local db=1 port=abc")),
+        Err(ProfileError::BadSetting(_))
+    ));
+    assert_eq!(0, profiles.get("local").unwrap().db);
+
+    profile_command(&mut profiles, &args("edit local db=1 color=green")).unwrap();
+    assert_eq!(
+        "prod\trediss://ops@10.0.0.5:6380/2 password=env:PROD_REDIS_PASS read-only production color=red\nlocal\tredis://127.0.0.1:6379/1 color=green",
+        profile_command(&mut profiles, &args("list")).unwrap()
+    );
+    profile_command(&mut profiles, &args("rename local dev")).unwrap();
+    profile_command(&mut profiles, &args("remove dev")).unwrap();
+    assert!(matches!(
+        profile_command(&mut profiles, &args("remove dev")),
+        Err(ProfileError::NotFound(_))
+    ));
+    assert_eq!(Some("31"), profiles.get("prod").unwrap().ansi_color());
+}
+
+#[test]
+fn test_profiles_file_and_secrets() {
+    let mut profile = Profile::new("prod");
+    profile.set("password", "env:RUST_A_TEST_PASS").unwrap();
+    profile.set("tls-insecure", "true").unwrap();
+    profile.set("decoder", "session:*=hessian").unwrap();
+    profile.set("decoder", "legacy:*=gbk").unwrap();
+    profile.set("decoder", "session:*=").unwrap();
+    assert!(profile.set("decoder", "x:*=protobuf").is_err());
+    assert_eq!(1, profile.decoders.len());
+    assert!(profile.to_string().ends_with(" decoder=legacy:*=gbk"));
+    let mut profiles = Profiles::default();
+    profiles.add(profile).unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("conf").join("profiles.json");
+    profiles.save(&path).unwrap();
+    let text = fs::read_to_string(&path).unwrap();
+    assert!(text.contains(r#""env": "RUST_A_TEST_PASS""#));
+    let loaded = Profiles::load(&path).unwrap();
+    assert_eq!(profiles, loaded);
+    assert!(Profiles::load(dir.path().join("none.json"))
+        .unwrap()
+        .profiles
+        .is_empty());
+
+    let prod = loaded.get("prod").unwrap();
+    assert!(matches!(
+        prod.connection_info(),
+        Err(ProfileError::Secret(_))
+    ));
+    std::env::set_var("RUST_A_TEST_PASS", "s3cret");
+    let info = prod.connection_info().unwrap();
+    assert_eq!(Some("s3cret".to_string()), info.passwd);
+    assert_eq!("127.0.0.1:6379", info.addr.to_string());
+    assert!(matches!(
+        *info.addr,
+        ConnectionAddr::TcpTls { insecure: true, .. }
+    ));
+}
@@ -0,0 +1,171 @@
+use std::io::BufRead;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use redis::{RedisResult, Value};
+
+use crate::browser::{KeyBrowser, ScanFilter};
+use crate::redis_conn::ConnectionHolder;
+
+#[derive(Debug, Clone)]
+pub struct PurgeOptions {
+    //pattern and type, see ScanFilter
+    pub filter: ScanFilter,
+    //only keys not touched for at least this many seconds
+    pub min_idle: Option<u64>,
+    //keys per second, none deletes as fast as SCAN goes
+    pub rate: Option<u32>,
+    pub batch: usize,
+    //list what would go without deleting it
+    pub dry_run: bool,
+}
+
+impl Default for PurgeOptions {
+    fn default() -> Self {
+        PurgeOptions {
+            filter: ScanFilter::default(),
+            min_idle: None,
+            rate: None,
+            batch: 100,
+            dry_run: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PurgeStats {
+    pub scanned: u64,
+    //keys that passed the filters, deleted unless it is a dry run
+    pub matched: u64,
+    //what UNLINK reported, keys gone meanwhile are not counted
+    pub deleted: u64,
+}
+
+//keys idle long enough, OBJECT IDLETIME fails when an lfu policy is set
+fn idle_keys(
+    holder: &mut ConnectionHolder,
+    keys: Vec<Vec<u8>>,
+    min_idle: u64,
+) -> RedisResult<Vec<Vec<u8>>> {
+    let mut pipe = redis::pipe();
+    for key in &keys {
+        pipe.cmd("OBJECT").arg("IDLETIME").arg(key.as_slice());
+    }
+    let idle: Vec<Value> = holder.pipeline(&pipe)?;
+    Ok(keys
+        .into_iter()
+        .zip(idle)
+        .filter(|(_, idle)| matches!(idle, Value::Int(secs) if *secs as u64 >= min_idle))
+        .map(|(key, _)| key)
+        .collect())
+}
+
+//how long to wait so that done keys since start stay under rate per second
+fn throttle(start: Instant, done: u64, rate: u32) -> Duration {
+    let due = Duration::from_secs_f64(done as f64 / rate.max(1) as f64);
+    due.checked_sub(start.elapsed()).unwrap_or_default()
+}
+
+/// SCAN the filter and UNLINK what matches, each batch goes to on_batch
+/// before it is deleted, or instead of it on a dry run
+pub fn purge(
+    holder: &mut ConnectionHolder,
+    options: &PurgeOptions,
+    on_batch: &mut dyn FnMut(&[Vec<u8>], &PurgeStats),
+) -> RedisResult<PurgeStats> {
+    let mut stats = PurgeStats::default();
+    let mut browser = KeyBrowser::new(options.filter.clone());
+    //a batch over the rate would go out in one burst
+    let batch = match options.rate {
+        Some(rate) => options.batch.min(rate.max(1) as usize),
+        None => options.batch,
+    };
+    let start = Instant::now();
+    while !browser.is_done() {
+        let mut keys = browser.next_names(holder, batch)?;
+        stats.scanned += keys.len() as u64;
+        if let (Some(min_idle), false) = (options.min_idle, keys.is_empty()) {
+            keys = idle_keys(holder, keys, min_idle)?;
+        }
+        if keys.is_empty() {
+            continue;
+        }
+        stats.matched += keys.len() as u64;
+        on_batch(&keys, &stats);
+        if options.dry_run {
+            continue;
+        }
+        let removed: u64 = redis::from_redis_value(
+            &holder.execute(
+                &std::iter::once(&b"UNLINK"[..])
+                    .chain(keys.iter().map(|k| k.as_slice()))
+                    .collect::<Vec<_>>(),
+            )?,
+        )?;
+        stats.deleted += removed;
+        if let Some(rate) = options.rate {
+            sleep(throttle(start, stats.matched, rate));
+        }
+    }
+    Ok(stats)
+}
+
+/// a production connection asks for its name to be typed back before a real delete
+pub fn confirmed<R: BufRead>(name: &str, mut input: R) -> bool {
+    let mut line = String::new();
+    input.read_line(&mut line).is_ok() && line.trim() == name
+}
+
+#[test]
+fn test_purge_helpers() {
+    assert!(confirmed("prod", &b"prod\n"[..]));
+    assert!(!confirmed("prod", &b"y\n"[..]));
+    assert!(!confirmed("prod", &b""[..]));
+
+    let start = Instant::now();
+    assert!(throttle(start, 50, 100) > Duration::from_millis(400));
+    assert_eq!(
+        Duration::default(),
+        throttle(start - Duration::from_secs(2), 50, 100)
+    );
+}
+
+#[test]
+fn test_purge_dry_run_and_delete() -> RedisResult<()> {
+    let mut holder = ConnectionHolder::new();
+    holder.put("redis://127.0.0.1:6379/0")?;
+    for i in 0..5 {
+        holder.execute(&["SET", &format!("purge:s{}", i), "x"])?;
+    }
+    holder.execute(&["RPUSH", "purge:l", "x"])?;
+
+    let mut options = PurgeOptions {
+        filter: ScanFilter::matching("purge:*"),
+        dry_run: true,
+        ..Default::default()
+    };
+    options.filter.kind = Some("string".to_string());
+    let mut listed = vec![];
+    let stats = purge(&mut holder, &options, &mut |keys, _| {
+        listed.extend_from_slice(keys)
+    })?;
+    assert_eq!((5, 0), (stats.matched, stats.deleted));
+    assert_eq!(5, listed.len());
+
+    //nothing has been idle for an hour
+    options.dry_run = false;
+    options.min_idle = Some(3600);
+    assert_eq!(0, purge(&mut holder, &options, &mut |_, _| {})?.deleted);
+
+    options.min_idle = None;
+    options.rate = Some(10);
+    options.batch = 2;
+    let start = Instant::now();
+    let stats = purge(&mut holder, &options, &mut |_, _| {})?;
+    assert_eq!(5, stats.deleted);
+    assert!(start.elapsed() >= Duration::from_millis(400));
+    let left: i64 = redis::from_redis_value(&holder.execute(&["EXISTS", "purge:l"])?)?;
+    assert_eq!(1, left);
+    holder.execute(&["DEL", "purge:l"])?;
+    Ok(())
+}
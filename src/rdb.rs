@@ -0,0 +1,1186 @@
+use std::convert::TryInto;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+
+use crate::browser::{glob_match, KeyInfo, ScanFilter};
+use crate::transfer::{Record, RecordValue, TransferStats};
+use crate::viewer::{Element, Pairs, ValueKind, ValueViewer};
+
+const MIN_VERSION: u32 = 6;
+const MAX_VERSION: u32 = 11;
+
+#[derive(Debug)]
+pub enum RdbError {
+    Io(io::Error),
+    NotRdb,
+    Version(u32),
+    //offset in the file and what was wrong there
+    Corrupt(u64, String),
+}
+
+impl fmt::Display for RdbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RdbError::Io(err) => write!(f, "{}", err),
+            RdbError::NotRdb => write!(f, "not an rdb file"),
+            RdbError::Version(v) => write!(
+                f,
+                "rdb version {} is not supported, only {} to {}",
+                v, MIN_VERSION, MAX_VERSION
+            ),
+            RdbError::Corrupt(pos, msg) => write!(f, "corrupt at {}: {}", pos, msg),
+        }
+    }
+}
+
+impl From<io::Error> for RdbError {
+    fn from(err: io::Error) -> Self {
+        RdbError::Io(err)
+    }
+}
+
+/// a value as the file has it, encodings like ziplist or intset are unpacked
+#[derive(Debug, Clone, PartialEq)]
+pub enum RdbValue {
+    String(Vec<u8>),
+    List(Vec<Vec<u8>>),
+    Set(Vec<Vec<u8>>),
+    ZSet(Vec<(Vec<u8>, f64)>),
+    Hash(Pairs),
+    //entries only, consumer groups are read over
+    Stream(Vec<(String, Pairs)>),
+    //a module type by name, its data is skipped
+    Module(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RdbEntry {
+    pub db: u64,
+    pub key: Vec<u8>,
+    //unix milliseconds
+    pub expire_at: Option<i64>,
+    //seconds since last access with an lru policy, or the lfu counter
+    pub idle: Option<u64>,
+    pub freq: Option<u8>,
+    //bytes the value takes in the file
+    pub size: u64,
+    pub value: RdbValue,
+}
+
+impl RdbEntry {
+    pub fn kind(&self) -> ValueKind {
+        match &self.value {
+            RdbValue::String(data) if data.starts_with(b"HYLL") => ValueKind::HyperLogLog,
+            RdbValue::String(_) => ValueKind::String,
+            RdbValue::List(_) => ValueKind::List,
+            RdbValue::Set(_) => ValueKind::Set,
+            RdbValue::ZSet(_) => ValueKind::ZSet,
+            RdbValue::Hash(_) => ValueKind::Hash,
+            RdbValue::Stream(_) => ValueKind::Stream,
+            RdbValue::Module(name) if name == "ReJSON-RL" => ValueKind::Json,
+            RdbValue::Module(name) => ValueKind::Module(name.clone()),
+        }
+    }
+
+    //the redis TYPE name
+    fn type_name(&self) -> &str {
+        match &self.value {
+            RdbValue::String(_) => "string",
+            RdbValue::List(_) => "list",
+            RdbValue::Set(_) => "set",
+            RdbValue::ZSet(_) => "zset",
+            RdbValue::Hash(_) => "hash",
+            RdbValue::Stream(_) => "stream",
+            RdbValue::Module(name) => name,
+        }
+    }
+
+    pub fn value_len(&self) -> Option<u64> {
+        let len = match &self.value {
+            RdbValue::String(data) => data.len(),
+            RdbValue::List(items) | RdbValue::Set(items) => items.len(),
+            RdbValue::ZSet(items) => items.len(),
+            RdbValue::Hash(fields) => fields.len(),
+            RdbValue::Stream(entries) => entries.len(),
+            RdbValue::Module(_) => return None,
+        };
+        Some(len as u64)
+    }
+
+    /// milliseconds left at now, none without expire
+    pub fn ttl_at(&self, now_ms: i64) -> Option<i64> {
+        self.expire_at.map(|at| at - now_ms)
+    }
+
+    pub fn is_expired(&self, now_ms: i64) -> bool {
+        self.ttl_at(now_ms).is_some_and(|t| t <= 0)
+    }
+
+    /// what the key browser shows, memory is the size in the file
+    pub fn key_info(&self, now_ms: i64) -> KeyInfo {
+        let ttl = match self.ttl_at(now_ms) {
+            None => -1,
+            Some(t) if t <= 0 => -2,
+            Some(t) => (t + 999) / 1000,
+        };
+        KeyInfo {
+            key: self.key.clone(),
+            kind: self.type_name().to_string(),
+            ttl,
+            len: self.value_len(),
+            memory: Some(self.size),
+        }
+    }
+
+    pub fn elements(&self) -> Vec<Element> {
+        match &self.value {
+            RdbValue::String(data) => vec![Element::Item(data.clone())],
+            RdbValue::List(items) | RdbValue::Set(items) => {
+                items.iter().cloned().map(Element::Item).collect()
+            }
+            RdbValue::ZSet(items) => items
+                .iter()
+                .map(|(m, s)| Element::Scored(m.clone(), *s))
+                .collect(),
+            RdbValue::Hash(fields) => fields
+                .iter()
+                .map(|(f, v)| Element::Field(f.clone(), v.clone()))
+                .collect(),
+            RdbValue::Stream(entries) => entries
+                .iter()
+                .map(|(id, fields)| Element::Entry(id.clone(), fields.clone()))
+                .collect(),
+            RdbValue::Module(_) => vec![],
+        }
+    }
+
+    pub fn viewer(&self, now_ms: i64) -> ValueViewer {
+        let mut viewer = ValueViewer::from_elements(&self.key, self.kind(), self.elements());
+        viewer.ttl = self.key_info(now_ms).ttl;
+        viewer
+    }
+
+    /// the export line of this key, none for module types
+    pub fn record(&self, now_ms: i64) -> Option<Record> {
+        let blobs = |items: &[Vec<u8>]| items.iter().cloned().map(Into::into).collect();
+        let pairs = |fields: &Pairs| {
+            fields
+                .iter()
+                .map(|(f, v)| (f.clone().into(), v.clone().into()))
+                .collect()
+        };
+        let value = match &self.value {
+            RdbValue::String(data) => RecordValue::String(data.clone().into()),
+            RdbValue::List(items) => RecordValue::List(blobs(items)),
+            RdbValue::Set(items) => RecordValue::Set(blobs(items)),
+            RdbValue::ZSet(items) => RecordValue::Zset(
+                items
+                    .iter()
+                    .map(|(m, s)| (m.clone().into(), score_text(*s)))
+                    .collect(),
+            ),
+            RdbValue::Hash(fields) => RecordValue::Hash(pairs(fields)),
+            RdbValue::Stream(entries) => RecordValue::Stream(
+                entries
+                    .iter()
+                    .map(|(id, fields)| (id.clone(), pairs(fields)))
+                    .collect(),
+            ),
+            RdbValue::Module(_) => return None,
+        };
+        Some(Record {
+            key: self.key.clone().into(),
+            ttl: self.ttl_at(now_ms),
+            value,
+        })
+    }
+
+    /// commands that recreate the key, empty for module types
+    pub fn commands(&self) -> Vec<Vec<Vec<u8>>> {
+        let b = |s: &str| s.as_bytes().to_vec();
+        let cmd = match &self.value {
+            RdbValue::String(data) => vec![b("SET"), self.key.clone(), data.clone()],
+            RdbValue::List(items) | RdbValue::Set(items) => {
+                let name = if matches!(self.value, RdbValue::List(_)) { "RPUSH" } else { "SADD" };
+                let mut cmd = vec![b(name), self.key.clone()];
+                cmd.extend(items.iter().cloned());
+                cmd
+            }
+            RdbValue::ZSet(items) => {
+                let mut cmd = vec![b("ZADD"), self.key.clone()];
+                for (member, score) in items {
+                    cmd.push(score_text(*score).into_bytes());
+                    cmd.push(member.clone());
+                }
+                cmd
+            }
+            RdbValue::Hash(fields) => {
+                let mut cmd = vec![b("HSET"), self.key.clone()];
+                for (field, val) in fields {
+                    cmd.push(field.clone());
+                    cmd.push(val.clone());
+                }
+                cmd
+            }
+            RdbValue::Stream(entries) => {
+                let mut cmds: Vec<Vec<Vec<u8>>> = entries
+                    .iter()
+                    .map(|(id, fields)| {
+                        let mut cmd = vec![b("XADD"), self.key.clone(), b(id)];
+                        for (field, val) in fields {
+                            cmd.push(field.clone());
+                            cmd.push(val.clone());
+                        }
+                        cmd
+                    })
+                    .collect();
+                if let Some(at) = self.expire_at.filter(|_| !cmds.is_empty()) {
+                    cmds.push(vec![b("PEXPIREAT"), self.key.clone(), at.to_string().into_bytes()]);
+                }
+                return cmds;
+            }
+            RdbValue::Module(_) => return vec![],
+        };
+        //redis has no empty collections
+        if cmd.len() < 3 {
+            return vec![];
+        }
+        let mut cmds = vec![cmd];
+        if let Some(at) = self.expire_at {
+            cmds.push(vec![b("PEXPIREAT"), self.key.clone(), at.to_string().into_bytes()]);
+        }
+        cmds
+    }
+
+    pub fn matches(&self, filter: &ScanFilter) -> bool {
+        glob_match(filter.pattern.as_bytes(), &self.key)
+            && filter.kind.as_ref().is_none_or(|k| k == self.type_name())
+    }
+}
+
+//as redis prints a score
+fn score_text(score: f64) -> String {
+    if score.is_infinite() {
+        if score > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        score.to_string()
+    }
+}
+
+struct Input<R> {
+    inner: R,
+    pos: u64,
+}
+
+enum Length {
+    Len(u64),
+    //a string kept as an integer or lzf compressed
+    Encoded(u8),
+}
+
+impl<R: Read> Input<R> {
+    fn corrupt<T>(&self, msg: impl Into<String>) -> Result<T, RdbError> {
+        Err(RdbError::Corrupt(self.pos, msg.into()))
+    }
+
+    fn bytes(&mut self, n: u64) -> Result<Vec<u8>, RdbError> {
+        //read_to_end grows with what is there, a bad length can't make a huge allocation
+        let mut buf = Vec::new();
+        (&mut self.inner).take(n).read_to_end(&mut buf)?;
+        self.pos += buf.len() as u64;
+        if (buf.len() as u64) < n {
+            return self.corrupt("unexpected end of file");
+        }
+        Ok(buf)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], RdbError> {
+        let mut buf = [0; N];
+        match self.inner.read_exact(&mut buf) {
+            Ok(()) => {
+                self.pos += N as u64;
+                Ok(buf)
+            }
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                self.corrupt("unexpected end of file")
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, RdbError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn length(&mut self) -> Result<Length, RdbError> {
+        let first = self.u8()?;
+        Ok(match first >> 6 {
+            0 => Length::Len((first & 0x3f) as u64),
+            1 => Length::Len((((first & 0x3f) as u64) << 8) | self.u8()? as u64),
+            2 if first == 0x80 => Length::Len(u32::from_be_bytes(self.array()?) as u64),
+            2 if first == 0x81 => Length::Len(u64::from_be_bytes(self.array()?)),
+            2 => return self.corrupt(format!("bad length {:#x}", first)),
+            _ => Length::Encoded(first & 0x3f),
+        })
+    }
+
+    fn len(&mut self) -> Result<u64, RdbError> {
+        match self.length()? {
+            Length::Len(len) => Ok(len),
+            Length::Encoded(_) => self.corrupt("encoded string where a length belongs"),
+        }
+    }
+
+    fn string(&mut self) -> Result<Vec<u8>, RdbError> {
+        match self.length()? {
+            Length::Len(len) => self.bytes(len),
+            Length::Encoded(0) => Ok((self.u8()? as i8).to_string().into_bytes()),
+            Length::Encoded(1) => Ok(i16::from_le_bytes(self.array()?).to_string().into_bytes()),
+            Length::Encoded(2) => Ok(i32::from_le_bytes(self.array()?).to_string().into_bytes()),
+            Length::Encoded(3) => {
+                let clen = self.len()?;
+                let len = self.len()?;
+                let data = self.bytes(clen)?;
+                match lzf_decompress(&data, len as usize) {
+                    Ok(out) => Ok(out),
+                    Err(msg) => self.corrupt(msg),
+                }
+            }
+            Length::Encoded(other) => self.corrupt(format!("unknown string encoding {}", other)),
+        }
+    }
+
+    //zset scores of the first format, text with special lengths for nan and infinity
+    fn text_double(&mut self) -> Result<f64, RdbError> {
+        match self.u8()? {
+            253 => Ok(f64::NAN),
+            254 => Ok(f64::INFINITY),
+            255 => Ok(f64::NEG_INFINITY),
+            len => {
+                let text = self.bytes(len as u64)?;
+                match std::str::from_utf8(&text).ok().and_then(|t| t.parse().ok()) {
+                    Some(score) => Ok(score),
+                    None => self.corrupt("bad score"),
+                }
+            }
+        }
+    }
+
+    fn packed<T>(&mut self, unpack: fn(&[u8]) -> Result<T, String>) -> Result<T, RdbError> {
+        let data = self.string()?;
+        match unpack(&data) {
+            Ok(val) => Ok(val),
+            Err(msg) => self.corrupt(msg),
+        }
+    }
+}
+
+fn lzf_decompress(input: &[u8], len: usize) -> Result<Vec<u8>, String> {
+    //len comes from the file, what is allocated up front follows the compressed size
+    let mut out = Vec::with_capacity(len.min(input.len().saturating_mul(4)));
+    let mut ip = 0;
+    let bad = || "bad lzf data".to_string();
+    while ip < input.len() {
+        let ctrl = input[ip] as usize;
+        ip += 1;
+        if ctrl < 32 {
+            let run = input.get(ip..ip + ctrl + 1).ok_or_else(bad)?;
+            out.extend_from_slice(run);
+            ip += ctrl + 1;
+        } else {
+            let mut run = ctrl >> 5;
+            if run == 7 {
+                run += *input.get(ip).ok_or_else(bad)? as usize;
+                ip += 1;
+            }
+            let back = ((ctrl & 0x1f) << 8) + *input.get(ip).ok_or_else(bad)? as usize + 1;
+            ip += 1;
+            let start = out.len().checked_sub(back).ok_or_else(bad)?;
+            //the copy may overlap what it writes, so byte by byte
+            for i in start..start + run + 2 {
+                out.push(out[i]);
+            }
+        }
+        if out.len() > len {
+            break;
+        }
+    }
+    if out.len() != len {
+        return Err(format!("lzf gave {} bytes instead of {}", out.len(), len));
+    }
+    Ok(out)
+}
+
+//a cursor over a ziplist, listpack or intset blob
+struct Packed<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Packed<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Packed { data, pos: 0 }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let part = self
+            .data
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| format!("packed value ends early at {}", self.pos))?;
+        self.pos += n;
+        Ok(part)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn int_le(&mut self, n: usize) -> Result<i64, String> {
+        let bytes = self.take(n)?;
+        let mut buf = [0u8; 8];
+        buf[..n].copy_from_slice(bytes);
+        //shift up and back down to carry the sign of the top byte
+        let shift = 64 - 8 * n as u32;
+        Ok(i64::from_le_bytes(buf).wrapping_shl(shift) >> shift)
+    }
+}
+
+fn ziplist(data: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let mut p = Packed::new(data);
+    p.take(10)?;
+    let mut items = Vec::new();
+    loop {
+        let prevlen = p.u8()?;
+        if prevlen == 0xff {
+            return Ok(items);
+        }
+        if prevlen == 0xfe {
+            p.take(4)?;
+        }
+        let enc = p.u8()?;
+        let item = match enc >> 6 {
+            0 => p.take((enc & 0x3f) as usize)?.to_vec(),
+            1 => {
+                let len = (((enc & 0x3f) as usize) << 8) | p.u8()? as usize;
+                p.take(len)?.to_vec()
+            }
+            2 => {
+                let len = u32::from_be_bytes(p.take(4)?.try_into().unwrap());
+                p.take(len as usize)?.to_vec()
+            }
+            _ => {
+                let num = match enc {
+                    0xc0 => p.int_le(2)?,
+                    0xd0 => p.int_le(4)?,
+                    0xe0 => p.int_le(8)?,
+                    0xf0 => p.int_le(3)?,
+                    0xfe => p.int_le(1)?,
+                    0xf1..=0xfd => (enc & 0x0f) as i64 - 1,
+                    _ => return Err(format!("bad ziplist encoding {:#x}", enc)),
+                };
+                num.to_string().into_bytes()
+            }
+        };
+        items.push(item);
+    }
+}
+
+fn listpack(data: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let mut p = Packed::new(data);
+    p.take(6)?;
+    let mut items = Vec::new();
+    loop {
+        let start = p.pos;
+        let enc = p.u8()?;
+        let item = if enc == 0xff {
+            return Ok(items);
+        } else if enc & 0x80 == 0 {
+            (enc as i64).to_string().into_bytes()
+        } else if enc & 0xc0 == 0x80 {
+            p.take((enc & 0x3f) as usize)?.to_vec()
+        } else if enc & 0xe0 == 0xc0 {
+            let raw = (((enc & 0x1f) as i64) << 8) | p.u8()? as i64;
+            //13 bit two's complement
+            let num = if raw >= 1 << 12 { raw - (1 << 13) } else { raw };
+            num.to_string().into_bytes()
+        } else if enc & 0xf0 == 0xe0 {
+            let len = (((enc & 0x0f) as usize) << 8) | p.u8()? as usize;
+            p.take(len)?.to_vec()
+        } else {
+            let num = match enc {
+                0xf0 => {
+                    let len = u32::from_le_bytes(p.take(4)?.try_into().unwrap());
+                    let item = p.take(len as usize)?.to_vec();
+                    skip_backlen(&mut p, start)?;
+                    items.push(item);
+                    continue;
+                }
+                0xf1 => p.int_le(2)?,
+                0xf2 => p.int_le(3)?,
+                0xf3 => p.int_le(4)?,
+                0xf4 => p.int_le(8)?,
+                _ => return Err(format!("bad listpack encoding {:#x}", enc)),
+            };
+            num.to_string().into_bytes()
+        };
+        skip_backlen(&mut p, start)?;
+        items.push(item);
+    }
+}
+
+//each listpack entry ends with its own length, in 7 bit groups
+fn skip_backlen(p: &mut Packed<'_>, start: usize) -> Result<(), String> {
+    let len = p.pos - start;
+    let size = match len {
+        0..=127 => 1,
+        128..=16383 => 2,
+        16384..=2097151 => 3,
+        2097152..=268435455 => 4,
+        _ => 5,
+    };
+    p.take(size).map(|_| ())
+}
+
+fn intset(data: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let mut p = Packed::new(data);
+    let width = p.int_le(4)? as usize;
+    if ![2, 4, 8].contains(&width) {
+        return Err(format!("bad intset encoding {}", width));
+    }
+    let count = p.int_le(4)? as u32;
+    (0..count)
+        .map(|_| Ok(p.int_le(width)?.to_string().into_bytes()))
+        .collect()
+}
+
+//hashes of very old servers, still found in files converted up
+fn zipmap(data: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let mut p = Packed::new(data);
+    p.u8()?;
+    let mut items = Vec::new();
+    loop {
+        let len = match p.u8()? {
+            0xff => return Ok(items),
+            0xfe => u32::from_le_bytes(p.take(4)?.try_into().unwrap()) as usize,
+            len => len as usize,
+        };
+        //values have a count of free bytes in front and the free space after
+        let free = if items.len() % 2 == 1 { p.u8()? } else { 0 };
+        items.push(p.take(len)?.to_vec());
+        p.take(free as usize)?;
+    }
+}
+
+fn paired(items: Vec<Vec<u8>>) -> Result<Pairs, String> {
+    if !items.len().is_multiple_of(2) {
+        return Err("odd number of hash items".to_string());
+    }
+    let mut iter = items.into_iter();
+    let mut pairs = Vec::new();
+    while let (Some(f), Some(v)) = (iter.next(), iter.next()) {
+        pairs.push((f, v));
+    }
+    Ok(pairs)
+}
+
+fn scored(items: Vec<Vec<u8>>) -> Result<Vec<(Vec<u8>, f64)>, String> {
+    paired(items)?
+        .into_iter()
+        .map(|(member, score)| {
+            let score = std::str::from_utf8(&score)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| "bad score".to_string())?;
+            Ok((member, score))
+        })
+        .collect()
+}
+
+fn listpack_pairs(data: &[u8]) -> Result<Pairs, String> {
+    paired(listpack(data)?)
+}
+
+fn ziplist_pairs(data: &[u8]) -> Result<Pairs, String> {
+    paired(ziplist(data)?)
+}
+
+fn listpack_scored(data: &[u8]) -> Result<Vec<(Vec<u8>, f64)>, String> {
+    scored(listpack(data)?)
+}
+
+fn ziplist_scored(data: &[u8]) -> Result<Vec<(Vec<u8>, f64)>, String> {
+    scored(ziplist(data)?)
+}
+
+fn zipmap_pairs(data: &[u8]) -> Result<Pairs, String> {
+    paired(zipmap(data)?)
+}
+
+fn stream_int(item: Option<Vec<u8>>) -> Result<i64, String> {
+    item.and_then(|i| String::from_utf8(i).ok())
+        .and_then(|i| i.parse().ok())
+        .ok_or_else(|| "bad stream listpack".to_string())
+}
+
+//a stream node: a master entry with the shared fields, then entries relative to it
+fn stream_node(master: (u64, u64), data: &[u8]) -> Result<Vec<(String, Pairs)>, String> {
+    let mut items = listpack(data)?.into_iter();
+    let count = stream_int(items.next())?;
+    let deleted = stream_int(items.next())?;
+    let field_count = stream_int(items.next())?;
+    let fields: Vec<Vec<u8>> = (0..field_count)
+        .map(|_| {
+            items
+                .next()
+                .ok_or_else(|| "bad stream listpack".to_string())
+        })
+        .collect::<Result<_, _>>()?;
+    stream_int(items.next())?;
+
+    let overflow = || "stream entry id overflows".to_string();
+    let total = count.checked_add(deleted).ok_or_else(overflow)?;
+    let mut entries = Vec::new();
+    for _ in 0..total {
+        let flags = stream_int(items.next())?;
+        let ms = master.0.checked_add_signed(stream_int(items.next())?);
+        let seq = master.1.checked_add_signed(stream_int(items.next())?);
+        let (ms, seq) = ms.zip(seq).ok_or_else(overflow)?;
+        let mut pairs = Vec::new();
+        if flags & 2 != 0 {
+            for field in &fields {
+                let val = items
+                    .next()
+                    .ok_or_else(|| "bad stream listpack".to_string())?;
+                pairs.push((field.clone(), val));
+            }
+        } else {
+            for _ in 0..stream_int(items.next())? {
+                match (items.next(), items.next()) {
+                    (Some(f), Some(v)) => pairs.push((f, v)),
+                    _ => return Err("bad stream listpack".to_string()),
+                }
+            }
+        }
+        //count of listpack items of the entry, for walking backwards
+        stream_int(items.next())?;
+        if flags & 1 == 0 {
+            entries.push((format!("{}-{}", ms, seq), pairs));
+        }
+    }
+    Ok(entries)
+}
+
+const MODULE_CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+//the 64 bit module id holds a 9 char name and a 10 bit encoding version
+fn module_name(id: u64) -> String {
+    let mut id = id >> 10;
+    let mut name = [0u8; 9];
+    for slot in name.iter_mut().rev() {
+        *slot = MODULE_CHARSET[(id & 63) as usize];
+        id >>= 6;
+    }
+    String::from_utf8_lossy(&name).into_owned()
+}
+
+/// walks an rdb file key by key, aux fields are collected on the way
+pub struct RdbReader<R> {
+    input: Input<R>,
+    pub version: u32,
+    pub aux: Vec<(String, String)>,
+    //library code of redis functions
+    pub functions: Vec<String>,
+    db: u64,
+    expire_at: Option<i64>,
+    idle: Option<u64>,
+    freq: Option<u8>,
+    done: bool,
+}
+
+impl RdbReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, RdbError> {
+        RdbReader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> RdbReader<R> {
+    /// bytes read so far, the end of the file once the iterator is done
+    pub fn position(&self) -> u64 {
+        self.input.pos
+    }
+
+    pub fn new(inner: R) -> Result<Self, RdbError> {
+        let mut input = Input { inner, pos: 0 };
+        let header = input.array::<9>().map_err(|_| RdbError::NotRdb)?;
+        if &header[..5] != b"REDIS" {
+            return Err(RdbError::NotRdb);
+        }
+        let version = std::str::from_utf8(&header[5..])
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .ok_or(RdbError::NotRdb)?;
+        if !(MIN_VERSION..=MAX_VERSION).contains(&version) {
+            return Err(RdbError::Version(version));
+        }
+        Ok(RdbReader {
+            input,
+            version,
+            aux: vec![],
+            functions: vec![],
+            db: 0,
+            expire_at: None,
+            idle: None,
+            freq: None,
+            done: false,
+        })
+    }
+
+    //module data is a run of typed items up to an eof marker
+    fn skip_module(&mut self) -> Result<(), RdbError> {
+        loop {
+            match self.input.len()? {
+                0 => return Ok(()),
+                1 | 2 => {
+                    self.input.len()?;
+                }
+                3 => {
+                    self.input.bytes(4)?;
+                }
+                4 => {
+                    self.input.bytes(8)?;
+                }
+                5 => {
+                    self.input.string()?;
+                }
+                other => return self.input.corrupt(format!("bad module opcode {}", other)),
+            }
+        }
+    }
+
+    fn strings(&mut self) -> Result<Vec<Vec<u8>>, RdbError> {
+        let len = self.input.len()?;
+        (0..len).map(|_| self.input.string()).collect()
+    }
+
+    fn stream(&mut self, kind: u8) -> Result<Vec<(String, Pairs)>, RdbError> {
+        let mut entries = Vec::new();
+        for _ in 0..self.input.len()? {
+            let node = self.input.string()?;
+            if node.len() != 16 {
+                return self.input.corrupt("bad stream node id");
+            }
+            let ms = u64::from_be_bytes(node[..8].try_into().unwrap());
+            let seq = u64::from_be_bytes(node[8..].try_into().unwrap());
+            let data = self.input.string()?;
+            match stream_node((ms, seq), &data) {
+                Ok(node) => entries.extend(node),
+                Err(msg) => return self.input.corrupt(msg),
+            }
+        }
+        //length and last id, then first id, max deleted id and entries added since 7.0
+        let counters = if kind >= 19 { 8 } else { 3 };
+        for _ in 0..counters {
+            self.input.len()?;
+        }
+        for _ in 0..self.input.len()? {
+            self.input.string()?;
+            self.input.len()?;
+            self.input.len()?;
+            if kind >= 19 {
+                self.input.len()?;
+            }
+            //pending entries: id, delivery time and count
+            for _ in 0..self.input.len()? {
+                self.input.bytes(16 + 8)?;
+                self.input.len()?;
+            }
+            for _ in 0..self.input.len()? {
+                self.input.string()?;
+                self.input.bytes(if kind >= 21 { 16 } else { 8 })?;
+                for _ in 0..self.input.len()? {
+                    self.input.bytes(16)?;
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    fn value(&mut self, kind: u8) -> Result<RdbValue, RdbError> {
+        let input = &mut self.input;
+        Ok(match kind {
+            0 => RdbValue::String(input.string()?),
+            1 => RdbValue::List(self.strings()?),
+            2 => RdbValue::Set(self.strings()?),
+            3 | 5 => {
+                let mut items = Vec::new();
+                for _ in 0..input.len()? {
+                    let member = input.string()?;
+                    let score = if kind == 3 {
+                        input.text_double()?
+                    } else {
+                        f64::from_le_bytes(input.array()?)
+                    };
+                    items.push((member, score));
+                }
+                RdbValue::ZSet(items)
+            }
+            4 => {
+                let mut fields = Vec::new();
+                for _ in 0..input.len()? {
+                    fields.push((input.string()?, input.string()?));
+                }
+                RdbValue::Hash(fields)
+            }
+            7 => {
+                let name = module_name(input.len()?);
+                self.skip_module()?;
+                RdbValue::Module(name)
+            }
+            9 => RdbValue::Hash(input.packed(zipmap_pairs)?),
+            10 => RdbValue::List(input.packed(ziplist)?),
+            11 => RdbValue::Set(input.packed(intset)?),
+            12 => RdbValue::ZSet(input.packed(ziplist_scored)?),
+            13 => RdbValue::Hash(input.packed(ziplist_pairs)?),
+            14 => {
+                let mut items = Vec::new();
+                for _ in 0..input.len()? {
+                    items.extend(input.packed(ziplist)?);
+                }
+                RdbValue::List(items)
+            }
+            15 | 19 | 21 => RdbValue::Stream(self.stream(kind)?),
+            16 => RdbValue::Hash(input.packed(listpack_pairs)?),
+            17 => RdbValue::ZSet(input.packed(listpack_scored)?),
+            18 => {
+                let mut items = Vec::new();
+                for _ in 0..input.len()? {
+                    //plain nodes hold one big element as it is
+                    if input.len()? == 1 {
+                        items.push(input.string()?);
+                    } else {
+                        items.extend(input.packed(listpack)?);
+                    }
+                }
+                RdbValue::List(items)
+            }
+            20 => RdbValue::Set(input.packed(listpack)?),
+            other => return input.corrupt(format!("unknown value type {}", other)),
+        })
+    }
+
+    fn next_entry(&mut self) -> Result<Option<RdbEntry>, RdbError> {
+        loop {
+            match self.input.u8()? {
+                0xff => {
+                    //crc64 of the file, not checked
+                    self.input.bytes(8)?;
+                    return Ok(None);
+                }
+                0xfe => self.db = self.input.len()?,
+                0xfd => {
+                    let secs = u32::from_le_bytes(self.input.array()?);
+                    self.expire_at = Some(secs as i64 * 1000);
+                }
+                0xfc => self.expire_at = Some(i64::from_le_bytes(self.input.array()?)),
+                0xfb => {
+                    self.input.len()?;
+                    self.input.len()?;
+                }
+                0xfa => {
+                    let name = self.input.string()?;
+                    let val = self.input.string()?;
+                    self.aux.push((
+                        String::from_utf8_lossy(&name).into_owned(),
+                        String::from_utf8_lossy(&val).into_owned(),
+                    ));
+                }
+                0xf9 => self.freq = Some(self.input.u8()?),
+                0xf8 => self.idle = Some(self.input.len()?),
+                0xf7 => {
+                    //module id, then when it was saved as an opcode and value
+                    self.input.len()?;
+                    self.input.len()?;
+                    self.input.len()?;
+                    self.skip_module()?;
+                }
+                0xf6 => {
+                    let code = self.input.string()?;
+                    self.functions
+                        .push(String::from_utf8_lossy(&code).into_owned());
+                }
+                0xf5 => return self.input.corrupt("functions of a 7.0 release candidate"),
+                kind => {
+                    let key = self.input.string()?;
+                    let start = self.input.pos;
+                    let value = self.value(kind)?;
+                    return Ok(Some(RdbEntry {
+                        db: self.db,
+                        key,
+                        expire_at: self.expire_at.take(),
+                        idle: self.idle.take(),
+                        freq: self.freq.take(),
+                        size: self.input.pos - start,
+                        value,
+                    }));
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for RdbReader<R> {
+    type Item = Result<RdbEntry, RdbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = self.next_entry();
+        if !matches!(next, Ok(Some(_))) {
+            self.done = true;
+        }
+        next.transpose()
+    }
+}
+
+/// keys of one db the filter matches, for the key browser
+pub fn scan_rdb<R: Read>(
+    reader: RdbReader<R>,
+    db: u64,
+    filter: &ScanFilter,
+    now_ms: i64,
+) -> Result<Vec<KeyInfo>, RdbError> {
+    let mut keys = Vec::new();
+    for entry in reader {
+        let entry = entry?;
+        if entry.db == db && entry.matches(filter) {
+            keys.push(entry.key_info(now_ms));
+        }
+    }
+    Ok(keys)
+}
+
+/// the same lines export_keys writes, expired and module keys are skipped
+pub fn export_rdb<R: Read, W: Write>(
+    reader: RdbReader<R>,
+    db: u64,
+    filter: &ScanFilter,
+    now_ms: i64,
+    out: &mut W,
+) -> Result<TransferStats, RdbError> {
+    let mut stats = TransferStats::default();
+    for entry in reader {
+        let entry = entry?;
+        if entry.db != db || !entry.matches(filter) {
+            continue;
+        }
+        match entry.record(now_ms).filter(|_| !entry.is_expired(now_ms)) {
+            Some(record) => {
+                serde_json::to_writer(&mut *out, &record).map_err(io::Error::from)?;
+                out.write_all(b"\n")?;
+                stats.keys += 1;
+            }
+            None => stats.skipped += 1,
+        }
+    }
+    out.flush()?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+fn test_str(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![data.len() as u8];
+    out.extend_from_slice(data);
+    out
+}
+
+//small strings and small ints only
+#[cfg(test)]
+fn test_listpack(items: &[&[u8]]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for item in items {
+        match std::str::from_utf8(item)
+            .ok()
+            .and_then(|i| i.parse::<u8>().ok())
+        {
+            Some(num) if num < 128 => body.extend_from_slice(&[num, 1]),
+            _ => {
+                body.push(0x80 | item.len() as u8);
+                body.extend_from_slice(item);
+                body.push(item.len() as u8 + 1);
+            }
+        }
+    }
+    let mut out = ((body.len() + 7) as u32).to_le_bytes().to_vec();
+    out.extend_from_slice(&(items.len() as u16).to_le_bytes());
+    out.extend(body);
+    out.push(0xff);
+    test_str(&out)
+}
+
+#[test]
+fn test_read_rdb() {
+    let mut rdb = b"REDIS0011".to_vec();
+    rdb.push(0xfa);
+    rdb.extend(test_str(b"redis-ver"));
+    rdb.extend(test_str(b"7.2.4"));
+    rdb.extend(&[0xfe, 0, 0xfb, 6, 1]);
+    //a string with expire, idle time and an lzf value
+    rdb.push(0xfc);
+    rdb.extend(&1_000_000i64.to_le_bytes());
+    rdb.extend(&[0xf8, 30, 0]);
+    rdb.extend(test_str(b"cache:a"));
+    rdb.extend(&[0xc3, 5, 10, 0x00, b'a', 0xe0, 0x00, 0x00]);
+    //an int encoded string
+    rdb.push(0);
+    rdb.extend(test_str(b"counter"));
+    rdb.extend(&[0xc1, 0x2c, 0x01]);
+    //quicklist of one listpack node
+    rdb.push(18);
+    rdb.extend(test_str(b"queue"));
+    rdb.extend(&[1, 2]);
+    rdb.extend(test_listpack(&[b"job1", b"7"]));
+    //intset
+    rdb.push(11);
+    rdb.extend(test_str(b"ids"));
+    rdb.extend(test_str(&[2, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0xfd, 0xff]));
+    rdb.push(17);
+    rdb.extend(test_str(b"rank"));
+    rdb.extend(test_listpack(&[b"x", b"1.5", b"y", b"3"]));
+    //hash as an old ziplist, with an immediate int
+    rdb.push(13);
+    rdb.extend(test_str(b"user:1"));
+    rdb.extend(test_str(&[
+        0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 4, b'n', b'a', b'm', b'e', 6, 0xf6, 0xff,
+    ]));
+    rdb.push(5);
+    rdb.extend(test_str(b"board"));
+    rdb.push(1);
+    rdb.extend(test_str(b"top"));
+    rdb.extend(&f64::INFINITY.to_le_bytes());
+    //stream node 1-0 with field f shared, the second entry has its own field
+    rdb.push(15);
+    rdb.extend(test_str(b"events"));
+    rdb.push(1);
+    let mut node = 1u64.to_be_bytes().to_vec();
+    node.extend(&0u64.to_be_bytes());
+    rdb.extend(test_str(&node));
+    rdb.extend(test_listpack(&[
+        b"2", b"0", b"1", b"f", b"0", b"2", b"0", b"0", b"v1", b"4", b"0", b"1", b"1", b"1", b"g",
+        b"w", b"6",
+    ]));
+    rdb.extend(&[2, 2, 1, 0]);
+    //a module value is named and skipped
+    rdb.extend(&[0xfe, 1, 7]);
+    rdb.extend(test_str(b"doc"));
+    let id = (0..9).fold(0u64, |id, i| {
+        let c = b"ReJSON-RL"[i];
+        (id << 6) | MODULE_CHARSET.iter().position(|m| *m == c).unwrap() as u64
+    }) << 10
+        | 3;
+    rdb.push(0x81);
+    rdb.extend(&id.to_be_bytes());
+    rdb.extend(&[5, 2, b'{', b'}', 2, 9, 0]);
+    rdb.push(0xff);
+    rdb.extend(&[0; 8]);
+
+    let mut reader = RdbReader::new(&rdb[..]).unwrap();
+    assert_eq!(11, reader.version);
+    let entries: Vec<RdbEntry> = reader.by_ref().collect::<Result<_, _>>().unwrap();
+    assert_eq!(
+        vec![("redis-ver".to_string(), "7.2.4".to_string())],
+        reader.aux
+    );
+    let values: Vec<&RdbValue> = entries.iter().map(|e| &e.value).collect();
+    let b = |s: &str| s.as_bytes().to_vec();
+    assert_eq!(
+        vec![
+            &RdbValue::String(b("aaaaaaaaaa")),
+            &RdbValue::String(b("300")),
+            &RdbValue::List(vec![b("job1"), b("7")]),
+            &RdbValue::Set(vec![b("1"), b("-3")]),
+            &RdbValue::ZSet(vec![(b("x"), 1.5), (b("y"), 3.0)]),
+            &RdbValue::Hash(vec![(b("name"), b("5"))]),
+            &RdbValue::ZSet(vec![(b("top"), f64::INFINITY)]),
+            &RdbValue::Stream(vec![
+                ("1-0".to_string(), vec![(b("f"), b("v1"))]),
+                ("2-1".to_string(), vec![(b("g"), b("w"))]),
+            ]),
+            &RdbValue::Module("ReJSON-RL".to_string()),
+        ],
+        values
+    );
+    assert_eq!(ValueKind::Json, entries[8].kind());
+    assert_eq!(1, entries[8].db);
+
+    let cache = &entries[0];
+    assert_eq!(Some(30), cache.idle);
+    let info = cache.key_info(990_500);
+    assert_eq!((10, Some(10)), (info.ttl, info.len));
+    assert_eq!(-2, cache.key_info(1_000_000).ttl);
+    let viewer = cache.viewer(0);
+    assert_eq!((1000, 1), (viewer.ttl, viewer.elements.len()));
+    let line = serde_json::to_string(&entries[6].record(0).unwrap()).unwrap();
+    assert_eq!(
+        r#"{"key":"board","type":"zset","value":[["top","inf"]]}"#,
+        line
+    );
+
+    let mut filter = ScanFilter::matching("*a*");
+    filter.kind = Some("string".to_string());
+    let keys = scan_rdb(RdbReader::new(&rdb[..]).unwrap(), 0, &filter, 0).unwrap();
+    assert_eq!(
+        vec!["cache:a"],
+        keys.iter().map(|k| k.name()).collect::<Vec<_>>()
+    );
+
+    let mut out = Vec::new();
+    let stats = export_rdb(
+        RdbReader::new(&rdb[..]).unwrap(),
+        0,
+        &ScanFilter::default(),
+        2_000_000,
+        &mut out,
+    )
+    .unwrap();
+    assert_eq!((7, 1), (stats.keys, stats.skipped));
+}
+
+#[test]
+fn test_bad_rdb() {
+    assert!(matches!(
+        RdbReader::new(&b"REDIS0005"[..]),
+        Err(RdbError::Version(5))
+    ));
+    assert!(matches!(RdbReader::new(&b"RDB"[..]), Err(RdbError::NotRdb)));
+    let mut rdb = b"REDIS0009".to_vec();
+    rdb.extend(&[0, 3, b'k', b'e']);
+    let mut reader = RdbReader::new(&rdb[..]).unwrap();
+    assert!(matches!(reader.next(), Some(Err(RdbError::Corrupt(13, _)))));
+    assert!(reader.next().is_none());
+
+    //an lzf string claiming to unpack to u64::MAX bytes
+    let mut rdb = b"REDIS0009".to_vec();
+    rdb.extend(&[0, 1, b'k', 0xc3, 2, 0x81]);
+    rdb.extend(&[0xff; 8]);
+    rdb.extend(&[0, b'a']);
+    let mut reader = RdbReader::new(&rdb[..]).unwrap();
+    assert!(matches!(reader.next(), Some(Err(RdbError::Corrupt(..)))));
+
+    //a stream entry whose id delta runs past u64, and counts that overflow
+    let max = i64::MAX.to_string();
+    let node = |count: &[u8], ms: &[u8]| {
+        test_listpack(&[count, b"0", b"1", b"f", b"0", b"2", ms, b"0", b"v", b"4"])
+    };
+    let ok = node(b"1", b"5");
+    assert_eq!(
+        Ok(vec![("6-0".to_string(), vec![(b"f".to_vec(), b"v".to_vec())])]),
+        stream_node((1, 0), &ok[1..])
+    );
+    assert!(stream_node((u64::MAX, 0), &ok[1..]).is_err());
+    assert!(stream_node((1, 0), &node(b"1", b"-2")[1..]).is_err());
+    let many = test_listpack(&[max.as_bytes(), max.as_bytes(), b"0", b"0"]);
+    assert!(stream_node((0, 0), &many[1..]).is_err());
+}
@@ -1,17 +1,44 @@
 use std::{collections::HashMap, thread::current};
 
-use redis::{from_redis_value, Commands, ConnectionLike, FromRedisValue, RedisResult, ToRedisArgs};
+use redis::{from_redis_value, ConnectionLike, IntoConnectionInfo, FromRedisValue, RedisResult, ToRedisArgs};
 
+use crate::info::{Delta, Info};
+use crate::safemode::{CommandTable, SafeMode};
 use crate::*;
+use std::time::Instant;
 use std::fmt::Error;
 
 pub trait Connection {
     fn init_db_info(&mut self);
 }
 
-struct ConnectionHolder {
-    conns: Vec<redis::Connection>, //
-    current: i32,                  //current redis connection index within conns
+/// an open connection, the id never changes and is not reused after close
+pub struct ManagedConnection {
+    pub id: u32,
+    pub name: String,
+    info: redis::ConnectionInfo, //address and selected db
+    conn: redis::Connection,
+    safe: Option<SafeMode>,
+}
+
+impl ManagedConnection {
+    pub fn info(&self) -> &redis::ConnectionInfo {
+        &self.info
+    }
+
+    pub fn db(&self) -> i64 {
+        self.info.db
+    }
+
+    pub fn safe_mode(&self) -> Option<&SafeMode> {
+        self.safe.as_ref()
+    }
+}
+
+pub struct ConnectionHolder {
+    conns: Vec<ManagedConnection>,
+    next_id: u32,
+    current: Option<u32>, //id of the connection commands go to
 }
 
 impl Connection for redis::Connection {
@@ -20,77 +47,354 @@ impl Connection for redis::Connection {
     }
 }
 
-struct DashBorad {
-    dbs: u32, //how many dbs in
+/// server overview from INFO, keeps the last two refreshes for rates
+pub struct DashBorad {
+    pub dbs: u32, //how many dbs in
+    prev: Option<(Instant, Info)>,
+    cur: Option<(Instant, Info)>,
+}
+
+impl Default for DashBorad {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl DashBorad {}
+impl DashBorad {
+    pub fn new() -> DashBorad {
+        DashBorad {
+            dbs: 16,
+            prev: None,
+            cur: None,
+        }
+    }
+
+    /// INFO ALL from the current connection of the holder
+    pub fn refresh(&mut self, holder: &mut ConnectionHolder) -> RedisResult<&Info> {
+        let text: String = holder.query("info", vec!["all"])?;
+        let info = Info::parse(&text);
+        //CONFIG is often renamed away on hosted redis, fall back to the keyspace
+        self.dbs = match holder.list_db() {
+            Ok(dbs) => dbs as u32,
+            Err(_) => info.keyspace.last().map_or(16, |k| (k.db + 1).max(16)),
+        };
+        self.update(info, Instant::now());
+        Ok(self.info().unwrap())
+    }
+
+    pub fn update(&mut self, info: Info, at: Instant) {
+        self.prev = self.cur.take();
+        self.cur = Some((at, info));
+    }
+
+    pub fn info(&self) -> Option<&Info> {
+        self.cur.as_ref().map(|(_, info)| info)
+    }
+
+    /// ops/sec, hit ratio and other rates since the refresh before, None after the first one
+    pub fn delta(&self) -> Option<Delta> {
+        let (prev_at, prev) = self.prev.as_ref()?;
+        let (at, cur) = self.cur.as_ref()?;
+        Some(Delta::between(prev, cur, at.duration_since(*prev_at)))
+    }
+}
 
 type CliResult<T> = Result<T, Error>;
 
+impl Default for ConnectionHolder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn client_err(msg: String) -> redis::RedisError {
+    (redis::ErrorKind::ClientError, "connection", msg).into()
+}
+
 impl ConnectionHolder {
-    fn new() -> ConnectionHolder {
+    pub fn new() -> ConnectionHolder {
         Self {
-            conns: Vec::<redis::Connection>::new(),
-            current: -1,
+            conns: Vec::new(),
+            next_id: 1,
+            current: None,
         }
     }
 
-    fn put(&mut self, uri: &str) -> redis::RedisResult<()> {
-        let client = redis::Client::open(uri)?;
-        let mut conn = client.get_connection()?;
-        self.conns.push(conn);
-        self.current = (self.conns.len() - 1) as i32;
-        Ok(())
+    /// connect and switch to the new connection, named after its address
+    pub fn put(&mut self, uri: &str) -> redis::RedisResult<u32> {
+        self.put_info(uri.into_connection_info()?)
     }
 
-    fn size(&self) -> usize {
+    /// connect with resolved settings, eg from a named profile
+    pub fn put_info(&mut self, info: redis::ConnectionInfo) -> redis::RedisResult<u32> {
+        let name = info.addr.to_string();
+        self.put_named(&name, info)
+    }
+
+    /// a taken name gets the id appended, prod then prod#3
+    pub fn put_named(&mut self, name: &str, info: redis::ConnectionInfo) -> redis::RedisResult<u32> {
+        let client = redis::Client::open(info)?;
+        let conn = client.get_connection()?;
+        let id = self.next_id;
+        self.next_id += 1;
+        let name = if self.conns.iter().any(|c| c.name == name) {
+            format!("{}#{}", name, id)
+        } else {
+            name.to_string()
+        };
+        self.conns.push(ManagedConnection {
+            id,
+            name,
+            info: client.get_connection_info().clone(),
+            conn,
+            safe: None,
+        });
+        self.current = Some(id);
+        Ok(id)
+    }
+
+    pub fn size(&self) -> usize {
         self.conns.len()
     }
 
-    //TODO direct forward request to backend and read resp?
-    fn execute(&mut self, cmd: &str) {
-        let ret: Vec<String> = self.conns[0 as usize].keys("*").unwrap();
+    pub fn list(&self) -> &[ManagedConnection] {
+        &self.conns
+    }
+
+    /// id of the connection a target names, the target is an id or a name
+    pub fn find(&self, target: &str) -> Option<u32> {
+        self.conns
+            .iter()
+            .find(|c| c.name == target)
+            .or_else(|| {
+                let id: u32 = target.trim_start_matches('#').parse().ok()?;
+                self.conns.iter().find(|c| c.id == id)
+            })
+            .map(|c| c.id)
+    }
+
+    fn resolve(&self, target: &str) -> redis::RedisResult<u32> {
+        self.find(target)
+            .ok_or_else(|| client_err(format!("no connection {}", target)))
+    }
+
+    pub fn current(&self) -> Option<&ManagedConnection> {
+        let id = self.current?;
+        self.conns.iter().find(|c| c.id == id)
+    }
+
+    fn current_mut(&mut self) -> Option<&mut ManagedConnection> {
+        let id = self.current?;
+        self.conns.iter_mut().find(|c| c.id == id)
+    }
+
+    pub fn switch(&mut self, target: &str) -> redis::RedisResult<u32> {
+        let id = self.resolve(target)?;
+        self.current = Some(id);
+        Ok(id)
     }
 
-    fn close(&mut self, index: usize) {
-        let conn = &self.conns.remove(index);
-        self.current = self.conns.len() as i32;
+    /// SELECT on the current connection, reconnects pick the db up again
+    pub fn select_db(&mut self, db: i64) -> redis::RedisResult<()> {
+        let managed = self
+            .current_mut()
+            .ok_or_else(|| client_err("not connected".to_string()))?;
+        redis::cmd("SELECT").arg(db).query::<()>(&mut managed.conn)?;
+        managed.info.db = db;
+        Ok(())
     }
 
-    fn close_all(&mut self) {
-        for index in 0..self.conns.len() {
-            self.conns.remove(index as usize);
+    /// drop a connection, the first one left becomes current if it was current
+    pub fn close(&mut self, target: &str) -> redis::RedisResult<u32> {
+        let id = self.resolve(target)?;
+        self.conns.retain(|c| c.id != id);
+        if self.current == Some(id) {
+            self.current = self.conns.first().map(|c| c.id);
         }
-        self.current = -1;
+        Ok(id)
     }
 
-    fn cur_conn(&mut self) -> Option<&mut redis::Connection> {
-        if self.current > -1 {
-            let conn = &mut self.conns[self.current as usize];
-            Some(conn)
+    pub fn close_all(&mut self) {
+        self.conns.clear();
+        self.current = None;
+    }
+
+    /// open the connection again with its address, credentials and db,
+    /// id and name stay the same
+    pub fn reconnect(&mut self, target: &str) -> redis::RedisResult<u32> {
+        let id = self.resolve(target)?;
+        let managed = self.conns.iter_mut().find(|c| c.id == id).unwrap();
+        let client = redis::Client::open(managed.info.clone())?;
+        managed.conn = client.get_connection()?;
+        Ok(id)
+    }
+
+    /// safe mode on a connection, on turns it on with the command flags of the server,
+    /// or a built-in list of reads when COMMAND is not there
+    pub fn set_safe_mode(&mut self, id: u32, on: bool) -> RedisResult<()> {
+        let managed = self
+            .conns
+            .iter_mut()
+            .find(|c| c.id == id)
+            .ok_or_else(|| client_err(format!("no connection {}", id)))?;
+        managed.safe = if on {
+            let table = CommandTable::load(&mut managed.conn)
+                .unwrap_or_else(|_| CommandTable::builtin());
+            Some(SafeMode::new(table))
         } else {
             None
+        };
+        Ok(())
+    }
+
+    /// refuse and log a command safe mode does not let through on a connection
+    pub fn guard<T: AsRef<[u8]>>(&self, id: u32, args: &[T]) -> RedisResult<()> {
+        let managed = match self.conns.iter().find(|c| c.id == id) {
+            Some(managed) => managed,
+            None => return Ok(()),
+        };
+        if let Some(safe) = &managed.safe {
+            if let Err(blocked) = safe.check(args) {
+                safe.log(&managed.name, "blocked", args);
+                return Err(client_err(blocked.to_string()));
+            }
         }
+        Ok(())
     }
 
-    fn list_db(&mut self) -> usize {
-        let cur_conn = self.cur_conn().unwrap();
+    fn guard_cmd(&self, id: u32, cmd: &redis::Cmd) -> RedisResult<()> {
+        let args: Vec<&[u8]> = cmd
+            .args_iter()
+            .filter_map(|arg| match arg {
+                redis::Arg::Simple(arg) => Some(arg),
+                redis::Arg::Cursor => None,
+            })
+            .collect();
+        self.guard(id, &args)
+    }
+
+    /// guard every command of a pipeline before it goes to a connection
+    pub fn guard_pipeline(&self, id: u32, pipe: &redis::Pipeline) -> RedisResult<()> {
+        for cmd in pipe.cmd_iter() {
+            self.guard_cmd(id, cmd)?;
+        }
+        Ok(())
+    }
+
+    fn conn_mut(&mut self, id: u32) -> RedisResult<&mut redis::Connection> {
+        self.conns
+            .iter_mut()
+            .find(|c| c.id == id)
+            .map(|c| &mut c.conn)
+            .ok_or_else(|| client_err(format!("no connection {}", id)))
+    }
+
+    fn current_id(&self) -> RedisResult<u32> {
+        self.current
+            .ok_or_else(|| (redis::ErrorKind::ClientError, "not connected").into())
+    }
+
+    /// a pipeline on connection id, refused as a whole when safe mode refuses one command
+    pub fn pipeline_on<T: FromRedisValue>(
+        &mut self,
+        id: u32,
+        pipe: &redis::Pipeline,
+    ) -> RedisResult<T> {
+        self.guard_pipeline(id, pipe)?;
+        pipe.query(self.conn_mut(id)?)
+    }
+
+    /// a pipeline on the current connection
+    pub fn pipeline<T: FromRedisValue>(&mut self, pipe: &redis::Pipeline) -> RedisResult<T> {
+        let id = self.current_id()?;
+        self.pipeline_on(id, pipe)
+    }
+
+    /// one command on connection id, through safe mode
+    pub fn cmd_on<T: FromRedisValue>(&mut self, id: u32, cmd: &redis::Cmd) -> RedisResult<T> {
+        self.guard_cmd(id, cmd)?;
+        cmd.query(self.conn_mut(id)?)
+    }
+
+    /// send a command as typed through the current connection and return the raw reply
+    pub fn execute<T: AsRef<[u8]>>(&mut self, args: &[T]) -> RedisResult<redis::Value> {
+        if args.is_empty() {
+            return Err((redis::ErrorKind::ClientError, "empty command").into());
+        }
+        if let Some(id) = self.current {
+            self.guard(id, args)?;
+        }
+        self.execute_unguarded(args)
+    }
+
+    /// run a command safe mode would refuse, logged as forced
+    pub fn execute_forced<T: AsRef<[u8]>>(&mut self, args: &[T]) -> RedisResult<redis::Value> {
+        if args.is_empty() {
+            return Err((redis::ErrorKind::ClientError, "empty command").into());
+        }
+        if let Some(managed) = self.current() {
+            if let Some(safe) = managed.safe.as_ref().filter(|safe| safe.check(args).is_err()) {
+                safe.log(&managed.name, "forced", args);
+            }
+        }
+        self.execute_unguarded(args)
+    }
+
+    fn execute_unguarded<T: AsRef<[u8]>>(&mut self, args: &[T]) -> RedisResult<redis::Value> {
+        //SELECT goes through select_db so the prompt and reconnects follow it
+        if args[0].as_ref().eq_ignore_ascii_case(b"select") && args.len() == 2 {
+            if let Some(db) = std::str::from_utf8(args[1].as_ref())
+                .ok()
+                .and_then(|db| db.parse().ok())
+            {
+                return self.select_db(db).map(|_| redis::Value::Okay);
+            }
+        }
+        let mut cmd = redis::Cmd::new();
+        for arg in args {
+            cmd.arg(arg.as_ref());
+        }
+        let cur_conn = match self.cur_conn() {
+            Some(conn) => conn,
+            None => return Err((redis::ErrorKind::ClientError, "not connected").into()),
+        };
+        cmd.query(cur_conn)
+    }
+
+    pub fn cur_info(&self) -> Option<&redis::ConnectionInfo> {
+        self.current().map(|c| &c.info)
+    }
+
+    //commands from outside go through pipeline, cmd_on or execute so safe mode sees them
+    fn cur_conn(&mut self) -> Option<&mut redis::Connection> {
+        self.current_mut().map(|c| &mut c.conn)
+    }
+
+    pub fn list_db(&mut self) -> RedisResult<usize> {
+        let cur_conn = self
+            .cur_conn()
+            .ok_or_else(|| client_err("not connected".to_string()))?;
         let dbs: HashMap<String, usize> = redis::cmd("config")
             .arg("GET")
             .arg("databases")
-            .query(cur_conn)
-            .unwrap();
-        *dbs.get("databases").unwrap()
+            .query(cur_conn)?;
+        dbs.get("databases")
+            .copied()
+            .ok_or_else(|| client_err("no databases in config".to_string()))
     }
 
-    fn query<RV: FromRedisValue>(&mut self, cmd_name: &str, p: Vec<&str>) -> RedisResult<RV> {
+    /// args are binary safe, anything from split_args can be passed along
+    pub fn query<RV: FromRedisValue>(
+        &mut self,
+        cmd_name: &str,
+        p: Vec<impl AsRef<[u8]>>,
+    ) -> RedisResult<RV> {
         let mut cmd = redis::cmd(cmd_name);
-        let cur_conn = self.cur_conn().unwrap();
-        for i in 0..p.len() {
-            cmd.arg(p[i]);
+        for arg in &p {
+            cmd.arg(arg.as_ref());
         }
-        cmd.query(cur_conn)
+        let id = self.current_id()?;
+        self.cmd_on(id, &cmd)
     }
 
     fn get_cfg<T: FromRedisValue>(&mut self, key: &str) -> HashMap<String, T> {
@@ -125,15 +429,86 @@ fn test_create_connectholder() {
     // let a = holder.cmd::<&str, String>("set", vec!["a", "aa"]);
 }
 
-fn detect_java_ver(bin: &[u8]) -> (&str, u16) {
-    let magic = &bin[0..2];
-    if magic[0] == 0xac && magic[1] == 0xed {
-        let minor = &bin[2..4];
-        let ver: u16 = u16::from_be_bytes([minor[0], minor[1]]);
-        ("java searialize format", ver)
-    } else {
-        ("other format", 999)
-    }
+#[test]
+fn test_switch_select_close_connections() -> RedisResult<()> {
+    let mut holder = ConnectionHolder::new();
+    let first = holder.put("redis://127.0.0.1:6379/0")?;
+    let info = "redis://127.0.0.1:6379/0".into_connection_info()?;
+    let second = holder.put_named("local", info.clone())?;
+    let third = holder.put_named("local", info)?;
+    assert_eq!(3, holder.size());
+    assert_eq!(format!("local#{}", third), holder.current().unwrap().name);
+
+    assert_eq!(first, holder.switch("127.0.0.1:6379")?);
+    assert_eq!(second, holder.switch("local")?);
+    assert_eq!(third, holder.switch(&format!("#{}", third))?);
+    assert!(holder.switch("nope").is_err());
+
+    holder.execute(&["SELECT", "3"])?;
+    holder.query::<()>("set", vec!["holder_db_probe", "3"])?;
+    assert_eq!(3, holder.current().unwrap().db());
+    holder.reconnect(&third.to_string())?;
+    //still on db 3 after reconnecting
+    let probe: String = holder.query("get", vec!["holder_db_probe"])?;
+    assert_eq!("3", probe);
+    holder.query::<()>("del", vec!["holder_db_probe"])?;
+
+    //closing the current one falls back to the first left, ids are never reused
+    holder.close("local")?;
+    holder.close(&third.to_string())?;
+    assert_eq!(Some(first), holder.current().map(|c| c.id));
+    let fourth = holder.put("redis://127.0.0.1:6379/1")?;
+    assert_eq!(third + 1, fourth);
+    assert_eq!(1, holder.current().unwrap().db());
+
+    holder.close_all();
+    assert_eq!(0, holder.size());
+    assert!(holder.execute(&["PING"]).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_not_connected() {
+    let mut holder = ConnectionHolder::new();
+    let err = holder.query::<String>("ping", Vec::<&str>::new()).unwrap_err();
+    assert_eq!("not connected", err.to_string());
+    assert!(holder.pipeline::<()>(redis::pipe().cmd("PING")).is_err());
+    assert!(holder.cmd_on::<()>(1, &redis::cmd("PING")).is_err());
+}
+
+#[test]
+fn test_dashboard_deltas() {
+    use std::time::Duration;
+    let mut board = DashBorad::new();
+    let start = Instant::now();
+    board.update(
+        Info::parse(&std::fs::read_to_string("testdata/info_1.txt").unwrap()),
+        start,
+    );
+    assert!(board.delta().is_none());
+    board.update(
+        Info::parse(&std::fs::read_to_string("testdata/info_2.txt").unwrap()),
+        start + Duration::from_secs(4),
+    );
+    let delta = board.delta().unwrap();
+    assert!((delta.ops_per_sec - 250.0).abs() < 1e-9);
+    assert_eq!(Some(0.75), delta.hit_ratio);
+    assert_eq!(3602, board.info().unwrap().server.uptime_in_seconds);
+}
+
+#[test]
+fn test_dashboard_refresh() -> RedisResult<()> {
+    let mut holder = ConnectionHolder::new();
+    holder.put("redis://127.0.0.1:6379/0")?;
+    let mut board = DashBorad::new();
+    let version = board.refresh(&mut holder)?.server.redis_version.clone();
+    assert!(!version.is_empty());
+    holder.query::<String>("ping", Vec::<&str>::new())?;
+    board.refresh(&mut holder)?;
+    let delta = board.delta().unwrap();
+    println!("{} dbs, {:.1} ops/sec, hit ratio {:?}", board.dbs, delta.ops_per_sec, delta.hit_ratio);
+    assert!(board.dbs > 0);
+    Ok(())
 }
 
 fn write_file(data: &[u8]) {
@@ -145,6 +520,9 @@ fn write_file(data: &[u8]) {
 
 #[test]
 fn test_any_cmd_with_strargs() -> RedisResult<()> {
+    use crate::browser::{KeyBrowser, ScanFilter};
+    use crate::value::DecoderChain;
+    use crate::viewer::ValueViewer;
     let mut holder = ConnectionHolder::new();
     holder.put("redis://127.0.0.1:6379/0")?;
     holder.query("set", vec!["a", "1"])?;
@@ -167,36 +545,24 @@ fn test_any_cmd_with_strargs() -> RedisResult<()> {
     println!("hset val is {}", hset_val);
     let _: u8 = holder.query("hdel", vec!["myset", "name"])?;
 
-    let keys: Vec<String> = holder.query("keys", vec!["*"])?;
-    for i in 0..keys.len() {
-        let val_type: String = holder.query("type", vec![&keys[i]])?;
-        println!("{} = {}", keys[i], val_type);
-
-        match val_type.as_str() {
-            "set" => {
-                let set_size: usize = holder.query("scard", vec![&keys[i]])?;
-                println!("set {}'s size is {}", keys[i], set_size);
-                let members: Vec<String> = holder.query("smembers", vec![&keys[i]])?;
-                for j in 0..members.len() {
-                    println!("\t member {} of key {} ", members[j], keys[i]);
-                }
-            }
-            "hash" => {
-                let hash_len: usize = holder.query("hlen", vec![&keys[i]])?;
-                println!("hash {}'s size is {}", keys[i], hash_len);
-                let map: HashMap<String, Vec<u8>> = holder.query("hgetall", vec![&keys[i]])?;
-                map.into_iter().for_each(|f| {
-                    // println!("\t {} = {:?}", f.0, f.1);
-                    let (format, ver) = detect_java_ver(&f.1);
-                    if format.starts_with("java") {
-                        println!("\tjava objectserialize stream, ver {}", ver);
-                    } else {
-                        // write_file(&f.1);
-                        println!("\t other format maybe str");
-                    }
-                });
+    let args = crate::cli::split_args(r#"hset myset "full name" "\xe5\x95\xa7 'q'""#.as_bytes())
+        .expect("split args failed!");
+    let _: u8 = holder.query(&String::from_utf8_lossy(&args[0]), args[1..].to_vec())?;
+    let hset_val: String = holder.query("hget", vec!["myset", "full name"])?;
+    assert_eq!("啧 'q'", hset_val);
+    let _: u8 = holder.query("hdel", vec!["myset", "full name"])?;
+
+    let decoders = DecoderChain::builtin();
+    let mut browser = KeyBrowser::new(ScanFilter::default());
+    while !browser.is_done() {
+        for key in browser.next_page(&mut holder, 100)? {
+            let name = key.name();
+            println!("{} = {} ttl {} len {:?}", name, key.kind, key.ttl, key.len);
+
+            let view = ValueViewer::open(&mut holder, &key.key)?;
+            for line in view.decoded_lines(&decoders) {
+                println!("\t{}", line);
             }
-            _ => (),
         }
     }
     Ok(())
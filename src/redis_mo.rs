@@ -57,7 +57,8 @@ fn do_basic_set_get(con: &mut redis::Connection) -> redis::RedisResult<()> {
        println!("get name {}", name);
    }
 
-   let all_keys : Vec<String> = con.keys("*")?;
+   //SCAN in steps, KEYS * would block the server on a big keyspace
+   let all_keys : Vec<String> = con.scan()?.collect();
    all_keys.into_iter().for_each(|k| {
        let type_code: String = redis::cmd("type").arg(&k).query(con).unwrap();
        println!("get key === {} type is {}", &k, &type_code);
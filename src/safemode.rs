@@ -0,0 +1,457 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use redis::{RedisResult, Value};
+
+use crate::cli::{data_dir, secret_from};
+use crate::viewer::edit_arg;
+
+/// what COMMAND INFO says a command does, the strongest one wins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CommandClass {
+    Read,
+    //in the @dangerous acl category
+    Dangerous,
+    Admin,
+    Write,
+}
+
+impl fmt::Display for CommandClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CommandClass::Read => "read",
+            CommandClass::Dangerous => "dangerous",
+            CommandClass::Admin => "admin",
+            CommandClass::Write => "write",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+//refused whatever their flags say, scripts because they may write without saying so
+const DENIED: [&str; 8] = [
+    "flushdb",
+    "flushall",
+    "keys",
+    "debug",
+    "config|set",
+    "eval",
+    "evalsha",
+    "fcall",
+];
+
+//admin and dangerous commands that only look, the rest of them is refused like writes
+const ALLOWED: [&str; 24] = [
+    "info",
+    "config|get",
+    "client|list",
+    "client|info",
+    "client|id",
+    "client|getname",
+    "slowlog|get",
+    "slowlog|len",
+    "memory|usage",
+    "memory|stats",
+    "memory|doctor",
+    "latency|latest",
+    "latency|history",
+    "latency|doctor",
+    "cluster|info",
+    "cluster|nodes",
+    "cluster|slots",
+    "cluster|shards",
+    "acl|whoami",
+    "module|list",
+    "role",
+    "lastsave",
+    "dbsize",
+    "command",
+];
+
+//for servers with COMMAND renamed away, anything not listed is refused
+const BUILTIN_READS: [&str; 41] = [
+    "get",
+    "mget",
+    "strlen",
+    "getrange",
+    "exists",
+    "type",
+    "ttl",
+    "pttl",
+    "scan",
+    "dbsize",
+    "info",
+    "ping",
+    "echo",
+    "select",
+    "time",
+    "lrange",
+    "llen",
+    "lindex",
+    "smembers",
+    "scard",
+    "sismember",
+    "sscan",
+    "zrange",
+    "zcard",
+    "zscore",
+    "zrank",
+    "zscan",
+    "hget",
+    "hgetall",
+    "hmget",
+    "hlen",
+    "hscan",
+    "hexists",
+    "xrange",
+    "xlen",
+    "xinfo",
+    "memory",
+    "object",
+    "pfcount",
+    "bitcount",
+    "dump",
+];
+
+/// command classes by lowercase name, subcommands as config|set like redis 7 names them
+#[derive(Debug, Clone, Default)]
+pub struct CommandTable {
+    classes: HashMap<String, CommandClass>,
+}
+
+fn as_text(val: &Value) -> Option<String> {
+    match val {
+        Value::Data(data) => Some(String::from_utf8_lossy(data).to_lowercase()),
+        Value::Status(text) => Some(text.to_lowercase()),
+        _ => None,
+    }
+}
+
+fn texts(val: Option<&Value>) -> Vec<String> {
+    match val {
+        Some(Value::Bulk(items)) => items.iter().filter_map(as_text).collect(),
+        _ => vec![],
+    }
+}
+
+impl CommandTable {
+    /// from the reply of COMMAND or COMMAND INFO, acl categories need redis 6
+    pub fn parse(reply: &Value) -> CommandTable {
+        let mut table = CommandTable::default();
+        if let Value::Bulk(entries) = reply {
+            for entry in entries {
+                table.add_entry(entry);
+            }
+        }
+        table
+    }
+
+    fn add_entry(&mut self, entry: &Value) {
+        let fields = match entry {
+            Value::Bulk(fields) => fields,
+            _ => return,
+        };
+        let name = match fields.first().and_then(as_text) {
+            Some(name) => name,
+            None => return,
+        };
+        let flags = texts(fields.get(2));
+        let categories = texts(fields.get(6));
+        let class = if flags.iter().any(|f| f == "write") {
+            CommandClass::Write
+        } else if flags.iter().any(|f| f == "admin") {
+            CommandClass::Admin
+        } else if categories.iter().any(|c| c == "@dangerous") {
+            CommandClass::Dangerous
+        } else {
+            CommandClass::Read
+        };
+        self.classes.insert(name, class);
+        //subcommands of redis 7 come with their own flags
+        if let Some(Value::Bulk(subs)) = fields.get(9) {
+            for sub in subs {
+                self.add_entry(sub);
+            }
+        }
+    }
+
+    pub fn builtin() -> CommandTable {
+        CommandTable {
+            classes: BUILTIN_READS
+                .iter()
+                .map(|name| (name.to_string(), CommandClass::Read))
+                .collect(),
+        }
+    }
+
+    /// COMMAND INFO without names lists everything since redis 7, before that COMMAND does
+    pub fn load(conn: &mut redis::Connection) -> RedisResult<CommandTable> {
+        let reply = redis::cmd("COMMAND")
+            .arg("INFO")
+            .query(conn)
+            .ok()
+            .filter(|r| matches!(r, Value::Bulk(items) if !items.is_empty()));
+        let reply = match reply {
+            Some(reply) => reply,
+            None => redis::cmd("COMMAND").query(conn)?,
+        };
+        Ok(CommandTable::parse(&reply))
+    }
+
+    pub fn len(&self) -> usize {
+        self.classes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+
+    //config|set when the table knows the subcommand, else config
+    fn lookup<T: AsRef<[u8]>>(&self, args: &[T]) -> (String, Option<CommandClass>) {
+        let name = String::from_utf8_lossy(args[0].as_ref()).to_lowercase();
+        if let Some(sub) = args.get(1) {
+            let full = format!(
+                "{}|{}",
+                name,
+                String::from_utf8_lossy(sub.as_ref()).to_lowercase()
+            );
+            if let Some(class) = self.classes.get(&full) {
+                return (full, Some(*class));
+            }
+            if DENIED.contains(&full.as_str()) || ALLOWED.contains(&full.as_str()) {
+                return (full, self.classes.get(&name).copied());
+            }
+        }
+        let class = self.classes.get(&name).copied();
+        (name, class)
+    }
+
+    /// none for a command the server does not know
+    pub fn classify<T: AsRef<[u8]>>(&self, args: &[T]) -> Option<CommandClass> {
+        if args.is_empty() {
+            return None;
+        }
+        self.lookup(args).1
+    }
+}
+
+/// a command safe mode refused
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blocked {
+    //as config|set
+    pub command: String,
+    pub class: Option<CommandClass>,
+}
+
+impl fmt::Display for Blocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let command = self.command.replace('|', " ").to_uppercase();
+        match self.class {
+            Some(class) => write!(f, "{} ({}) is refused in safe mode", command, class),
+            None => write!(f, "{} is unknown and refused in safe mode", command),
+        }
+    }
+}
+
+/// refuses writes, the denied commands and admin or dangerous ones not in ALLOWED,
+/// :force runs one anyway
+#[derive(Debug, Clone)]
+pub struct SafeMode {
+    pub table: CommandTable,
+    //where blocked and forced commands are appended, none keeps no log
+    pub log: Option<PathBuf>,
+}
+
+impl SafeMode {
+    pub fn new(table: CommandTable) -> SafeMode {
+        SafeMode {
+            table,
+            log: Some(data_dir().join("blocked.log")),
+        }
+    }
+
+    pub fn check<T: AsRef<[u8]>>(&self, args: &[T]) -> Result<(), Blocked> {
+        if args.is_empty() {
+            return Ok(());
+        }
+        let (command, class) = self.table.lookup(args);
+        let denied = DENIED.contains(&command.as_str());
+        let allowed = ALLOWED.contains(&command.as_str());
+        match class {
+            _ if denied => Err(Blocked { command, class }),
+            Some(CommandClass::Read) => Ok(()),
+            Some(CommandClass::Admin) | Some(CommandClass::Dangerous) if allowed => Ok(()),
+            _ => Err(Blocked { command, class }),
+        }
+    }
+
+    /// one line per attempt: time, connection, what happened and the command
+    pub fn log<T: AsRef<[u8]>>(&self, conn: &str, what: &str, args: &[T]) {
+        let path = match &self.log {
+            Some(path) => path,
+            None => return,
+        };
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let line = format!(
+            "{}\t{}\t{}\t{}\n",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+            conn,
+            what,
+            redacted(args)
+        );
+        let logged = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(line.as_bytes()));
+        if let Err(err) = logged {
+            println!("write {} failed: {}", path.display(), err);
+        }
+    }
+}
+
+/// the command as the log shows it, passwords and the arguments after them hidden
+fn redacted<T: AsRef<[u8]>>(args: &[T]) -> String {
+    let secret = secret_from(args).unwrap_or(args.len());
+    let mut shown: Vec<String> = args[..secret].iter().map(|a| edit_arg(a.as_ref())).collect();
+    if secret < args.len() {
+        shown.push("(redacted)".to_string());
+    }
+    shown.join(" ")
+}
+
+#[cfg(test)]
+fn test_entry(name: &str, flags: &[&str], categories: &[&str], subs: Vec<Value>) -> Value {
+    let status =
+        |items: &[&str]| Value::Bulk(items.iter().map(|i| Value::Status(i.to_string())).collect());
+    let mut fields = vec![
+        Value::Data(name.as_bytes().to_vec()),
+        Value::Int(-1),
+        status(flags),
+        Value::Int(1),
+        Value::Int(1),
+        Value::Int(1),
+        status(categories),
+    ];
+    if !subs.is_empty() {
+        fields.extend(vec![
+            Value::Bulk(vec![]),
+            Value::Bulk(vec![]),
+            Value::Bulk(subs),
+        ]);
+    }
+    Value::Bulk(fields)
+}
+
+#[test]
+fn test_safe_mode() {
+    let reply = Value::Bulk(vec![
+        test_entry("get", &["readonly", "fast"], &["@read", "@string"], vec![]),
+        test_entry("set", &["write", "denyoom"], &["@write"], vec![]),
+        test_entry("keys", &["readonly"], &["@keyspace", "@dangerous"], vec![]),
+        test_entry(
+            "info",
+            &["loading", "stale"],
+            &["@slow", "@dangerous"],
+            vec![],
+        ),
+        test_entry("flushall", &["write"], &["@dangerous"], vec![]),
+        test_entry(
+            "config",
+            &[],
+            &["@slow"],
+            vec![
+                test_entry(
+                    "config|get",
+                    &["admin", "noscript"],
+                    &["@dangerous"],
+                    vec![],
+                ),
+                test_entry(
+                    "config|set",
+                    &["admin", "noscript"],
+                    &["@dangerous"],
+                    vec![],
+                ),
+            ],
+        ),
+        //redis 6 has no subcommand entries
+        test_entry("debug", &["admin", "noscript"], &["@dangerous"], vec![]),
+        test_entry(
+            "client",
+            &[],
+            &["@slow"],
+            vec![
+                test_entry("client|list", &["admin"], &["@dangerous"], vec![]),
+                test_entry("client|kill", &["admin"], &["@dangerous"], vec![]),
+            ],
+        ),
+        test_entry("shutdown", &["admin", "noscript"], &["@dangerous"], vec![]),
+        test_entry("replicaof", &["admin", "noscript"], &["@dangerous"], vec![]),
+        test_entry(
+            "eval",
+            &["noscript", "movablekeys"],
+            &["@scripting"],
+            vec![],
+        ),
+    ]);
+    let table = CommandTable::parse(&reply);
+    assert_eq!(15, table.len());
+    assert_eq!(
+        Some(CommandClass::Admin),
+        table.classify(&["CONFIG", "GET", "x"])
+    );
+    assert_eq!(Some(CommandClass::Dangerous), table.classify(&["info"]));
+    assert_eq!(None, table.classify(&["hset", "k", "f", "v"]));
+
+    let mut safe = SafeMode::new(table);
+    let dir = tempfile::tempdir().unwrap();
+    safe.log = Some(dir.path().join("blocked.log"));
+    assert!(safe.check(&["GET", "k"]).is_ok());
+    assert!(safe.check(&["INFO"]).is_ok());
+    assert!(safe.check(&["config", "get", "maxmemory"]).is_ok());
+    assert!(safe.check(&["CLIENT", "LIST"]).is_ok());
+    let refused: Vec<String> = [
+        &["SET", "k", "v"][..],
+        &["keys", "*"],
+        &["FLUSHALL"],
+        &["config", "set", "maxmemory", "1"],
+        &["DEBUG", "SLEEP", "0"],
+        &["eval", "return 1", "0"],
+        &["hset", "k", "f", "v"],
+        &["SHUTDOWN", "NOSAVE"],
+        &["client", "kill", "127.0.0.1:6000"],
+        &["REPLICAOF", "10.0.0.1", "6379"],
+    ]
+    .iter()
+    .map(|args| safe.check(args).unwrap_err().to_string())
+    .collect();
+    assert_eq!("SET (write) is refused in safe mode", refused[0]);
+    assert_eq!("CONFIG SET (admin) is refused in safe mode", refused[3]);
+    assert_eq!("HSET is unknown and refused in safe mode", refused[6]);
+    assert_eq!("SHUTDOWN (admin) is refused in safe mode", refused[7]);
+    assert_eq!("CLIENT KILL (admin) is refused in safe mode", refused[8]);
+    assert_eq!("REPLICAOF (admin) is refused in safe mode", refused[9]);
+
+    safe.log("prod", "blocked", &["FLUSHALL"]);
+    safe.log("prod", "forced", &["SET", "a b", "v"]);
+    safe.log("prod", "blocked", &["config", "set", "requirepass", "hunter2"]);
+    safe.log("prod", "blocked", &["ACL", "SETUSER", "bob", ">hunter2"]);
+    let log = fs::read_to_string(dir.path().join("blocked.log")).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+    assert_eq!(4, lines.len());
+    assert!(lines[0].ends_with("\tprod\tblocked\tFLUSHALL"));
+    assert!(lines[1].ends_with("\tprod\tforced\tSET \"a b\" v"));
+    assert!(lines[2].ends_with("\tblocked\tconfig set (redacted)"));
+    assert!(lines[3].ends_with("\tblocked\tACL SETUSER bob (redacted)"));
+    assert!(!log.contains("hunter2"));
+
+    let builtin = SafeMode::new(CommandTable::builtin());
+    assert!(builtin.check(&["HGETALL", "k"]).is_ok());
+    assert!(builtin.check(&["HSET", "k", "f", "v"]).is_err());
+}
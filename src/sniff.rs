@@ -0,0 +1,441 @@
+use encoding::{all::GBK, DecoderTrap, Encoding};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use crate::javaser;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    JavaSerialization,
+    Hessian2,
+    Hessian1,
+    Json,
+    Xml,
+    Utf8Text,
+    GbkText,
+    Gzip,
+    Zstd,
+    Snappy,
+    Lz4,
+    MsgPack,
+    Protobuf,
+    JavaClass,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Detection {
+    pub format: Format,
+    //0.0 ~ 1.0
+    pub confidence: f32,
+    //what matched, eg "magic aced0005"
+    pub signals: Vec<String>,
+}
+
+impl Detection {
+    fn new(format: Format, confidence: f32, signals: Vec<String>) -> Self {
+        Self {
+            format,
+            confidence,
+            signals,
+        }
+    }
+}
+
+type Detector = fn(&[u8]) -> Option<Detection>;
+
+const DETECTORS: [Detector; 12] = [
+    detect_java_serialization,
+    detect_java_class,
+    detect_compressed,
+    detect_hessian2,
+    detect_hessian1,
+    detect_json,
+    detect_xml,
+    detect_utf8,
+    detect_gbk,
+    detect_msgpack,
+    detect_protobuf,
+    detect_snappy_framed,
+];
+
+/// every format the value may be in, most likely first
+pub fn sniff(data: &[u8]) -> Vec<Detection> {
+    let mut found: Vec<Detection> = DETECTORS.iter().filter_map(|d| d(data)).collect();
+    found.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap());
+    found
+}
+
+/// the most likely format, Unknown when nothing matched
+pub fn detect(data: &[u8]) -> Detection {
+    sniff(data)
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| Detection::new(Format::Unknown, 0.0, vec![]))
+}
+
+fn detect_java_serialization(data: &[u8]) -> Option<Detection> {
+    if !data.starts_with(&[0xac, 0xed]) {
+        return None;
+    }
+    let mut signals = vec![String::from("magic aced")];
+    let mut confidence = 0.6;
+    if data.len() >= 4 && u16::from_be_bytes([data[2], data[3]]) == javaser::STREAM_VERSION {
+        signals.push(String::from("stream version 5"));
+        confidence = 0.9;
+        if javaser::parse(data).is_ok() {
+            signals.push(String::from("stream parsed"));
+            confidence = 1.0;
+        }
+    }
+    Some(Detection::new(Format::JavaSerialization, confidence, signals))
+}
+
+fn detect_java_class(data: &[u8]) -> Option<Detection> {
+    if data.len() < 10 || !data.starts_with(&[0xca, 0xfe, 0xba, 0xbe]) {
+        return None;
+    }
+    let major = u16::from_be_bytes([data[6], data[7]]);
+    let mut signals = vec![String::from("magic cafebabe")];
+    let confidence = if (45..=70).contains(&major) {
+        signals.push(format!("class file major version {}", major));
+        0.95
+    } else {
+        0.5
+    };
+    Some(Detection::new(Format::JavaClass, confidence, signals))
+}
+
+fn detect_compressed(data: &[u8]) -> Option<Detection> {
+    let (format, signal) = if data.starts_with(&[0x1f, 0x8b, 0x08]) {
+        (Format::Gzip, "gzip magic 1f8b deflate")
+    } else if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        (Format::Zstd, "zstd frame magic 28b52ffd")
+    } else if data.starts_with(&[0x04, 0x22, 0x4d, 0x18]) {
+        (Format::Lz4, "lz4 frame magic 04224d18")
+    } else if data.starts_with(&[0x02, 0x21, 0x4c, 0x18]) {
+        (Format::Lz4, "lz4 legacy frame magic 02214c18")
+    } else {
+        return None;
+    };
+    Some(Detection::new(format, 0.95, vec![String::from(signal)]))
+}
+
+fn detect_snappy_framed(data: &[u8]) -> Option<Detection> {
+    if data.starts_with(b"\xff\x06\x00\x00sNaPpY") {
+        Some(Detection::new(
+            Format::Snappy,
+            1.0,
+            vec![String::from("snappy stream identifier chunk")],
+        ))
+    } else {
+        None
+    }
+}
+
+/// compact hessian string, length in the tag byte
+fn compact_string(data: &[u8]) -> Option<&[u8]> {
+    let len = *data.first()? as usize;
+    if len > 0x1f {
+        return None;
+    }
+    data.get(1..1 + len)
+}
+
+fn is_class_name(name: &[u8]) -> bool {
+    !name.is_empty()
+        && name.iter().all(|c| c.is_ascii_alphanumeric() || b"._$".contains(c))
+        && name[0].is_ascii_alphabetic()
+}
+
+fn detect_hessian2(data: &[u8]) -> Option<Detection> {
+    let tag = *data.first()?;
+    let (confidence, signal) = match tag {
+        b'H' if data.starts_with(&[b'H', 0x02, 0x00]) => (0.9, String::from("hessian 2.0 header")),
+        b'C' => {
+            let name = compact_string(&data[1..])?;
+            if !is_class_name(name) {
+                return None;
+            }
+            (
+                0.85,
+                format!("class definition {}", String::from_utf8_lossy(name)),
+            )
+        }
+        b'M' => {
+            let name = compact_string(&data[1..])?;
+            if !is_class_name(name) {
+                return None;
+            }
+            (0.6, format!("typed map {}", String::from_utf8_lossy(name)))
+        }
+        //compact list, fixed length typed list, object instance
+        0x60..=0x77 if data.len() > 1 => (0.3, format!("compact tag {:#x}", tag)),
+        _ => return None,
+    };
+    Some(Detection::new(Format::Hessian2, confidence, vec![signal]))
+}
+
+fn detect_hessian1(data: &[u8]) -> Option<Detection> {
+    if data.len() < 3 {
+        return None;
+    }
+    let (confidence, signal) = match (data[0], data[1], data[2]) {
+        (b'c', 0x01, 0x00) => (0.9, String::from("hessian 1.0 call")),
+        (b'r', 0x01, 0x00) => (0.9, String::from("hessian 1.0 reply")),
+        (b'M', b't', _) | (b'V', b't', _) => {
+            let len = u16::from_be_bytes([data[2], *data.get(3)?]) as usize;
+            let name = data.get(4..4 + len)?;
+            if !name.is_empty() && !is_class_name(name) {
+                return None;
+            }
+            (
+                0.85,
+                format!("typed container '{}'", String::from_utf8_lossy(name)),
+            )
+        }
+        _ => return None,
+    };
+    Some(Detection::new(Format::Hessian1, confidence, vec![signal]))
+}
+
+fn trim_ascii(data: &[u8]) -> &[u8] {
+    let start = data
+        .iter()
+        .position(|c| !c.is_ascii_whitespace())
+        .unwrap_or(data.len());
+    let end = data
+        .iter()
+        .rposition(|c| !c.is_ascii_whitespace())
+        .map_or(start, |p| p + 1);
+    &data[start..end]
+}
+
+fn detect_json(data: &[u8]) -> Option<Detection> {
+    let trimmed = trim_ascii(data);
+    let (first, last) = (*trimmed.first()?, *trimmed.last()?);
+    if !((first == b'{' && last == b'}') || (first == b'[' && last == b']')) {
+        return None;
+    }
+    let mut signals = vec![String::from("bracketed document")];
+    let confidence = match serde_json::from_slice::<serde_json::Value>(trimmed) {
+        Ok(_) => {
+            signals.push(String::from("json parsed"));
+            0.95
+        }
+        Err(_) => 0.4,
+    };
+    Some(Detection::new(Format::Json, confidence, signals))
+}
+
+fn detect_xml(data: &[u8]) -> Option<Detection> {
+    let trimmed = trim_ascii(data);
+    if !trimmed.starts_with(b"<") || !trimmed.ends_with(b">") {
+        return None;
+    }
+    let mut signals = vec![];
+    let mut confidence = 0.4;
+    if trimmed.starts_with(b"<?xml") {
+        signals.push(String::from("xml declaration"));
+        confidence = 0.8;
+    }
+    let mut reader = Reader::from_reader(trimmed);
+    let mut buf = Vec::new();
+    let mut elements = 0;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(_)) | Ok(Event::Empty(_)) => elements += 1,
+            Ok(Event::Eof) => break,
+            Err(_) => return Some(Detection::new(Format::Xml, 0.3, signals)),
+            _ => (),
+        }
+        buf.clear();
+    }
+    if elements > 0 {
+        signals.push(format!("{} elements parsed", elements));
+        confidence = 0.95;
+    }
+    Some(Detection::new(Format::Xml, confidence, signals))
+}
+
+fn is_text_char(c: char) -> bool {
+    !c.is_control() || c == '\n' || c == '\r' || c == '\t'
+}
+
+fn detect_utf8(data: &[u8]) -> Option<Detection> {
+    let text = std::str::from_utf8(data).ok()?;
+    if text.is_empty() || !text.chars().all(is_text_char) {
+        return None;
+    }
+    let mut signals = vec![String::from("valid utf-8")];
+    if text.chars().any(|c| ('\u{4e00}'..='\u{9fff}').contains(&c)) {
+        signals.push(String::from("cjk characters"));
+    }
+    Some(Detection::new(Format::Utf8Text, 0.7, signals))
+}
+
+fn detect_gbk(data: &[u8]) -> Option<Detection> {
+    if data.is_ascii() || std::str::from_utf8(data).is_ok() {
+        return None;
+    }
+    let text = GBK.decode(data, DecoderTrap::Strict).ok()?;
+    if !text.chars().all(is_text_char) {
+        return None;
+    }
+    Some(Detection::new(
+        Format::GbkText,
+        0.6,
+        vec![String::from("invalid utf-8 but strict gbk decoded")],
+    ))
+}
+
+fn read_varint(data: &[u8]) -> Option<(u64, &[u8])> {
+    let mut val = 0u64;
+    for (idx, b) in data.iter().enumerate().take(10) {
+        val |= u64::from(b & 0x7f) << (7 * idx);
+        if b & 0x80 == 0 {
+            return Some((val, &data[idx + 1..]));
+        }
+    }
+    None
+}
+
+/// walk the protobuf wire format, returns how many fields were read
+fn protobuf_fields(mut data: &[u8]) -> Option<usize> {
+    let mut count = 0;
+    while !data.is_empty() {
+        let (key, rest) = read_varint(data)?;
+        if key >> 3 == 0 {
+            return None;
+        }
+        data = match key & 0x07 {
+            0 => read_varint(rest)?.1,
+            1 => rest.get(8..)?,
+            2 => {
+                let (len, rest) = read_varint(rest)?;
+                rest.get(len as usize..)?
+            }
+            5 => rest.get(4..)?,
+            _ => return None,
+        };
+        count += 1;
+    }
+    Some(count)
+}
+
+fn detect_protobuf(data: &[u8]) -> Option<Detection> {
+    let count = protobuf_fields(data)?;
+    if count == 0 {
+        return None;
+    }
+    let confidence = if count > 1 { 0.5 } else { 0.25 };
+    Some(Detection::new(
+        Format::Protobuf,
+        confidence,
+        vec![format!("{} varint keyed fields", count)],
+    ))
+}
+
+fn skip_msgpack_items(mut data: &[u8], items: usize, depth: usize) -> Option<&[u8]> {
+    for _ in 0..items {
+        data = skip_msgpack(data, depth)?;
+    }
+    Some(data)
+}
+
+/// skip a single msgpack value, returns the rest of the input
+fn skip_msgpack(data: &[u8], depth: usize) -> Option<&[u8]> {
+    if depth > 64 {
+        return None;
+    }
+    let (tag, rest) = data.split_first()?;
+    let be = |len: usize| -> Option<usize> {
+        let bytes = rest.get(..len)?;
+        Some(bytes.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize))
+    };
+    let skip_items = |rest, items| skip_msgpack_items(rest, items, depth + 1);
+    match tag {
+        0x00..=0x7f | 0xe0..=0xff | 0xc0 | 0xc2 | 0xc3 => Some(rest),
+        0x80..=0x8f => skip_items(rest, (*tag as usize & 0x0f) * 2),
+        0x90..=0x9f => skip_items(rest, *tag as usize & 0x0f),
+        0xa0..=0xbf => rest.get(*tag as usize & 0x1f..),
+        0xcc | 0xd0 => rest.get(1..),
+        0xcd | 0xd1 => rest.get(2..),
+        0xca | 0xce | 0xd2 => rest.get(4..),
+        0xcb | 0xcf | 0xd3 => rest.get(8..),
+        0xd4..=0xd8 => rest.get(1 + (1 << (tag - 0xd4))..),
+        0xc4 | 0xd9 => rest.get(1 + be(1)?..),
+        0xc5 | 0xda => rest.get(2 + be(2)?..),
+        0xc6 | 0xdb => rest.get(4 + be(4)?..),
+        0xc7 => rest.get(2 + be(1)?..),
+        0xc8 => rest.get(3 + be(2)?..),
+        0xc9 => rest.get(5 + be(4)?..),
+        0xdc => skip_items(rest.get(2..)?, be(2)?),
+        0xdd => skip_items(rest.get(4..)?, be(4)?),
+        0xde => skip_items(rest.get(2..)?, be(2)? * 2),
+        0xdf => skip_items(rest.get(4..)?, be(4)? * 2),
+        _ => None,
+    }
+}
+
+fn detect_msgpack(data: &[u8]) -> Option<Detection> {
+    let tag = *data.first()?;
+    //only containers, scalars are too easy to hit by chance
+    let container = matches!(tag, 0x80..=0x9f | 0xdc..=0xdf);
+    if !container || !skip_msgpack(data, 0)?.is_empty() {
+        return None;
+    }
+    Some(Detection::new(
+        Format::MsgPack,
+        0.75,
+        vec![format!("container tag {:#x} spans the whole value", tag)],
+    ))
+}
+
+#[test]
+fn test_sniff_java_serialization() {
+    let buf = std::fs::read("testdata/hashmap.ser").unwrap();
+    let found = detect(&buf);
+    assert_eq!(Format::JavaSerialization, found.format);
+    assert_eq!(1.0, found.confidence);
+    assert!(found.signals.contains(&String::from("stream parsed")));
+}
+
+#[test]
+fn test_sniff_text_formats() {
+    assert_eq!(Format::Json, detect(br#"{"name": "alex", "tel": [10086]}"#).format);
+    assert_eq!(
+        Format::Xml,
+        detect(b"<xml><info name='alex'><age>1</age></info></xml>").format
+    );
+    let utf8 = detect("啧啧啧".as_bytes());
+    assert_eq!(Format::Utf8Text, utf8.format);
+    assert!(utf8.signals.contains(&String::from("cjk characters")));
+    let gbk = GBK.encode("张三丰", encoding::EncoderTrap::Strict).unwrap();
+    assert_eq!(Format::GbkText, detect(&gbk).format);
+}
+
+#[test]
+fn test_sniff_binary_formats() {
+    assert_eq!(Format::Gzip, detect(&[0x1f, 0x8b, 0x08, 0x00, 0x00]).format);
+    assert_eq!(Format::Zstd, detect(&[0x28, 0xb5, 0x2f, 0xfd, 0x00]).format);
+    assert_eq!(Format::Lz4, detect(&[0x04, 0x22, 0x4d, 0x18, 0x64]).format);
+    assert_eq!(Format::Snappy, detect(b"\xff\x06\x00\x00sNaPpY").format);
+    assert_eq!(
+        Format::JavaClass,
+        detect(&[0xca, 0xfe, 0xba, 0xbe, 0x00, 0x00, 0x00, 0x34, 0x00, 0x10]).format
+    );
+    assert_eq!(Format::Hessian2, detect(b"\x43\x0bcom.example\x92").format);
+    assert_eq!(Format::Hessian1, detect(b"Mt\x00\x06Person").format);
+    //{"a": 1, "b": [true]}
+    assert_eq!(
+        Format::MsgPack,
+        detect(&[0x82, 0xa1, b'a', 0x01, 0xa1, b'b', 0x91, 0xc3]).format
+    );
+    //field 1 varint 150, field 2 string "hi"
+    assert_eq!(
+        Format::Protobuf,
+        detect(&[0x08, 0x96, 0x01, 0x12, 0x02, b'h', b'i']).format
+    );
+    assert_eq!(Format::Unknown, detect(&[]).format);
+}
@@ -0,0 +1,597 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use redis::{RedisError, RedisResult, Value};
+use serde::{Deserialize, Serialize};
+
+use crate::browser::{KeyBrowser, ScanFilter};
+use crate::redis_conn::ConnectionHolder;
+use crate::viewer::{next_stream_id, parse_entries, Element};
+
+//keys per pipeline, on export and import alike
+const BATCH: usize = 200;
+//members per read of a collection, longer ones take more round trips instead of one huge reply
+const CHUNK: usize = 1000;
+
+#[derive(Debug)]
+pub enum TransferError {
+    Io(io::Error),
+    Redis(RedisError),
+    //line number and what was wrong with it
+    Json(usize, serde_json::Error),
+    Base64(base64::DecodeError),
+    //the fail policy met a key that is already there
+    Exists(String),
+}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransferError::Io(err) => write!(f, "{}", err),
+            TransferError::Redis(err) => write!(f, "{}", err),
+            TransferError::Json(line, err) => write!(f, "line {}: {}", line, err),
+            TransferError::Base64(err) => write!(f, "bad base64: {}", err),
+            TransferError::Exists(key) => write!(f, "key {} already exists", key),
+        }
+    }
+}
+
+impl From<io::Error> for TransferError {
+    fn from(err: io::Error) -> Self {
+        TransferError::Io(err)
+    }
+}
+
+impl From<RedisError> for TransferError {
+    fn from(err: RedisError) -> Self {
+        TransferError::Redis(err)
+    }
+}
+
+impl From<base64::DecodeError> for TransferError {
+    fn from(err: base64::DecodeError) -> Self {
+        TransferError::Base64(err)
+    }
+}
+
+/// utf-8 stays readable, anything else goes as {"base64": "..."}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Blob {
+    Text(String),
+    Binary { base64: String },
+}
+
+impl From<Vec<u8>> for Blob {
+    fn from(data: Vec<u8>) -> Self {
+        match String::from_utf8(data) {
+            Ok(text) => Blob::Text(text),
+            Err(err) => Blob::Binary {
+                base64: base64::encode(err.as_bytes()),
+            },
+        }
+    }
+}
+
+impl Blob {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, base64::DecodeError> {
+        match self {
+            Blob::Text(text) => Ok(text.as_bytes().to_vec()),
+            Blob::Binary { base64 } => base64::decode(base64),
+        }
+    }
+}
+
+/// scores are kept as redis prints them, json numbers can't hold inf
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum RecordValue {
+    String(Blob),
+    List(Vec<Blob>),
+    Set(Vec<Blob>),
+    Zset(Vec<(Blob, String)>),
+    Hash(Vec<(Blob, Blob)>),
+    //entry ids with their fields, consumer groups are not carried over
+    Stream(Vec<(String, Vec<(Blob, Blob)>)>),
+}
+
+/// one line of an export file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub key: Blob,
+    //milliseconds left when exported, absent without expire
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<i64>,
+    #[serde(flatten)]
+    pub value: RecordValue,
+}
+
+/// what to do with a key the target already has
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Conflict {
+    Skip,
+    Replace,
+    Fail,
+}
+
+impl std::str::FromStr for Conflict {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(Conflict::Skip),
+            "replace" => Ok(Conflict::Replace),
+            "fail" => Ok(Conflict::Fail),
+            other => Err(format!(
+                "conflict policy is skip, replace or fail, not {}",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TransferStats {
+    pub keys: u64,
+    //on export keys that vanished or have a module type, on import existing ones
+    pub skipped: u64,
+    pub replaced: u64,
+}
+
+impl fmt::Display for TransferStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} keys, {} skipped, {} replaced",
+            self.keys, self.skipped, self.replaced
+        )
+    }
+}
+
+fn blobs(val: &Value) -> RedisResult<Vec<Blob>> {
+    let items: Vec<Vec<u8>> = redis::from_redis_value(val)?;
+    Ok(items.into_iter().map(Blob::from).collect())
+}
+
+fn pairs(val: &Value) -> RedisResult<Vec<(Blob, Blob)>> {
+    let items: Vec<(Vec<u8>, Vec<u8>)> = redis::from_redis_value(val)?;
+    Ok(items
+        .into_iter()
+        .map(|(a, b)| (Blob::from(a), Blob::from(b)))
+        .collect())
+}
+
+fn to_record(kind: &str, pttl: i64, val: &Value) -> RedisResult<Option<RecordValue>> {
+    if pttl == -2 || *val == Value::Nil {
+        return Ok(None);
+    }
+    let value = match kind {
+        "string" => {
+            let data: Vec<u8> = redis::from_redis_value(val)?;
+            RecordValue::String(data.into())
+        }
+        "list" => RecordValue::List(blobs(val)?),
+        "set" => RecordValue::Set(blobs(val)?),
+        "zset" => {
+            let items: Vec<(Vec<u8>, String)> = redis::from_redis_value(val)?;
+            RecordValue::Zset(items.into_iter().map(|(m, s)| (m.into(), s)).collect())
+        }
+        "hash" => RecordValue::Hash(pairs(val)?),
+        "stream" => {
+            let entries = parse_entries(val)?
+                .into_iter()
+                .filter_map(|e| match e {
+                    Element::Entry(id, fields) => Some((
+                        id,
+                        fields
+                            .into_iter()
+                            .map(|(f, v)| (f.into(), v.into()))
+                            .collect(),
+                    )),
+                    _ => None,
+                })
+                .collect();
+            RecordValue::Stream(entries)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(value))
+}
+
+fn items(val: Value) -> RedisResult<Vec<Value>> {
+    redis::from_redis_value(&val)
+}
+
+/// the first chunk of every value in one pipeline, strings whole
+fn first_chunk(pipe: &mut redis::Pipeline, kind: &str, key: &[u8]) {
+    let last = CHUNK as i64 - 1;
+    match kind {
+        "string" => pipe.cmd("GET").arg(key),
+        "list" => pipe.cmd("LRANGE").arg(key).arg(0).arg(last),
+        "set" => pipe.cmd("SSCAN").arg(key).arg(0).arg("COUNT").arg(CHUNK),
+        "zset" => pipe.cmd("ZRANGE").arg(key).arg(0).arg(last).arg("WITHSCORES"),
+        "hash" => pipe.cmd("HSCAN").arg(key).arg(0).arg("COUNT").arg(CHUNK),
+        "stream" => pipe.cmd("XRANGE").arg(key).arg("-").arg("+").arg("COUNT").arg(CHUNK),
+        //keeps replies lined up with keys, the value is not exported
+        _ => pipe.cmd("EXISTS").arg(key),
+    };
+}
+
+/// reads on from the first chunk till the value is complete, shaped like a whole value read.
+/// writes in between may show up or not, a scan can repeat members so those are dropped
+fn read_rest(
+    holder: &mut ConnectionHolder,
+    kind: &str,
+    key: &[u8],
+    first: Value,
+) -> RedisResult<Value> {
+    let mut all = vec![];
+    match kind {
+        "list" | "zset" => {
+            let width = if kind == "zset" { 2 } else { 1 };
+            let mut chunk = items(first)?;
+            while chunk.len() == CHUNK * width {
+                all.append(&mut chunk);
+                let start = all.len() / width;
+                let mut pipe = redis::pipe();
+                let end = start + CHUNK - 1;
+                match kind {
+                    "zset" => pipe.cmd("ZRANGE").arg(key).arg(start).arg(end).arg("WITHSCORES"),
+                    _ => pipe.cmd("LRANGE").arg(key).arg(start).arg(end),
+                };
+                let (next,): (Value,) = holder.pipeline(&pipe)?;
+                chunk = items(next)?;
+            }
+            all.append(&mut chunk);
+        }
+        "set" | "hash" => {
+            let width = if kind == "hash" { 2 } else { 1 };
+            let (mut cursor, mut chunk): (u64, Vec<Value>) = redis::from_redis_value(&first)?;
+            let mut seen = HashSet::new();
+            loop {
+                for item in chunk.chunks(width) {
+                    let name: Vec<u8> = redis::from_redis_value(&item[0])?;
+                    if seen.insert(name) {
+                        all.extend_from_slice(item);
+                    }
+                }
+                if cursor == 0 {
+                    break;
+                }
+                let mut pipe = redis::pipe();
+                let scan = if kind == "hash" { "HSCAN" } else { "SSCAN" };
+                pipe.cmd(scan).arg(key).arg(cursor).arg("COUNT").arg(CHUNK);
+                let (next,): ((u64, Vec<Value>),) = holder.pipeline(&pipe)?;
+                cursor = next.0;
+                chunk = next.1;
+            }
+        }
+        "stream" => {
+            let mut chunk = items(first)?;
+            while chunk.len() == CHUNK {
+                let (last, _): (String, Value) = redis::from_redis_value(&chunk[CHUNK - 1])?;
+                all.append(&mut chunk);
+                let mut pipe = redis::pipe();
+                pipe.cmd("XRANGE")
+                    .arg(key)
+                    .arg(next_stream_id(&last))
+                    .arg("+")
+                    .arg("COUNT")
+                    .arg(CHUNK);
+                let (next,): (Value,) = holder.pipeline(&pipe)?;
+                chunk = items(next)?;
+            }
+            all.append(&mut chunk);
+        }
+        _ => return Ok(first),
+    }
+    Ok(Value::Bulk(all))
+}
+
+/// type and ttl, then the values, each for a batch of keys in one pipeline.
+/// long collections are read on in chunks of their own
+pub(crate) fn fetch_records(
+    holder: &mut ConnectionHolder,
+    keys: &[Vec<u8>],
+) -> RedisResult<Vec<Option<Record>>> {
+    if keys.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut pipe = redis::pipe();
+    for key in keys {
+        pipe.cmd("TYPE")
+            .arg(key.as_slice())
+            .cmd("PTTL")
+            .arg(key.as_slice());
+    }
+    let meta: Vec<(String, i64)> = holder.pipeline(&pipe)?;
+
+    let mut pipe = redis::pipe();
+    for (key, (kind, _)) in keys.iter().zip(&meta) {
+        first_chunk(&mut pipe, kind, key);
+    }
+    let values: Vec<Value> = holder.pipeline(&pipe)?;
+
+    let mut records = Vec::with_capacity(keys.len());
+    for ((key, (kind, pttl)), val) in keys.iter().zip(meta).zip(values) {
+        if pttl == -2 || val == Value::Nil {
+            records.push(None);
+            continue;
+        }
+        let val = read_rest(holder, &kind, key, val)?;
+        records.push(to_record(&kind, pttl, &val)?.map(|value| Record {
+            key: key.clone().into(),
+            ttl: Some(pttl).filter(|t| *t > 0),
+            value,
+        }));
+    }
+    Ok(records)
+}
+
+/// every key the filter matches as a line of json
+pub fn export_keys<W: Write>(
+    holder: &mut ConnectionHolder,
+    filter: ScanFilter,
+    out: &mut W,
+) -> Result<TransferStats, TransferError> {
+    let mut stats = TransferStats::default();
+    let mut browser = KeyBrowser::new(filter);
+    while !browser.is_done() {
+        let keys = browser.next_names(holder, BATCH)?;
+        for record in fetch_records(holder, &keys)? {
+            match record {
+                Some(record) => {
+                    serde_json::to_writer(&mut *out, &record).map_err(io::Error::from)?;
+                    out.write_all(b"\n")?;
+                    stats.keys += 1;
+                }
+                None => stats.skipped += 1,
+            }
+        }
+    }
+    out.flush()?;
+    Ok(stats)
+}
+
+pub(crate) fn add_commands(
+    pipe: &mut redis::Pipeline,
+    key: &[u8],
+    record: &Record,
+) -> Result<(), TransferError> {
+    let bytes = |items: &[Blob]| -> Result<Vec<Vec<u8>>, base64::DecodeError> {
+        items.iter().map(|b| b.to_bytes()).collect()
+    };
+    match &record.value {
+        RecordValue::String(val) => {
+            pipe.cmd("SET").arg(key).arg(val.to_bytes()?).ignore();
+        }
+        RecordValue::List(items) if !items.is_empty() => {
+            pipe.cmd("RPUSH").arg(key).arg(bytes(items)?).ignore();
+        }
+        RecordValue::Set(items) if !items.is_empty() => {
+            pipe.cmd("SADD").arg(key).arg(bytes(items)?).ignore();
+        }
+        RecordValue::Zset(items) if !items.is_empty() => {
+            let cmd = pipe.cmd("ZADD").arg(key);
+            for (member, score) in items {
+                cmd.arg(score).arg(member.to_bytes()?);
+            }
+            cmd.ignore();
+        }
+        RecordValue::Hash(fields) if !fields.is_empty() => {
+            let cmd = pipe.cmd("HSET").arg(key);
+            for (field, val) in fields {
+                cmd.arg(field.to_bytes()?).arg(val.to_bytes()?);
+            }
+            cmd.ignore();
+        }
+        RecordValue::Stream(entries) => {
+            for (id, fields) in entries {
+                let cmd = pipe.cmd("XADD").arg(key).arg(id);
+                for (field, val) in fields {
+                    cmd.arg(field.to_bytes()?).arg(val.to_bytes()?);
+                }
+                cmd.ignore();
+            }
+        }
+        //redis has no empty collections
+        _ => return Ok(()),
+    }
+    if let Some(ttl) = record.ttl.filter(|t| *t > 0) {
+        pipe.cmd("PEXPIRE").arg(key).arg(ttl).ignore();
+    }
+    Ok(())
+}
+
+fn write_batch(
+    holder: &mut ConnectionHolder,
+    records: &[Record],
+    policy: Conflict,
+    stats: &mut TransferStats,
+) -> Result<(), TransferError> {
+    let keys = records
+        .iter()
+        .map(|r| r.key.to_bytes())
+        .collect::<Result<Vec<Vec<u8>>, _>>()?;
+    let mut pipe = redis::pipe();
+    for key in &keys {
+        pipe.cmd("EXISTS").arg(key.as_slice());
+    }
+    let exists: Vec<bool> = holder.pipeline(&pipe)?;
+    if policy == Conflict::Fail {
+        if let Some(pos) = exists.iter().position(|e| *e) {
+            return Err(TransferError::Exists(
+                String::from_utf8_lossy(&keys[pos]).into_owned(),
+            ));
+        }
+    }
+
+    let mut pipe = redis::pipe();
+    for ((record, key), exists) in records.iter().zip(&keys).zip(exists) {
+        if exists {
+            if policy == Conflict::Skip {
+                stats.skipped += 1;
+                continue;
+            }
+            pipe.cmd("DEL").arg(key.as_slice()).ignore();
+            stats.replaced += 1;
+        }
+        add_commands(&mut pipe, key, record)?;
+        stats.keys += 1;
+    }
+    holder.pipeline::<()>(&pipe)?;
+    Ok(())
+}
+
+/// recreate the keys of an export, a batch of keys goes out in one pipeline.
+/// with the fail policy batches before the conflicting one are already written
+pub fn import_keys<R: BufRead>(
+    holder: &mut ConnectionHolder,
+    input: R,
+    policy: Conflict,
+) -> Result<TransferStats, TransferError> {
+    let mut stats = TransferStats::default();
+    let mut batch = Vec::with_capacity(BATCH);
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Record =
+            serde_json::from_str(&line).map_err(|err| TransferError::Json(i + 1, err))?;
+        batch.push(record);
+        if batch.len() == BATCH {
+            write_batch(holder, &batch, policy, &mut stats)?;
+            batch.clear();
+        }
+    }
+    if !batch.is_empty() {
+        write_batch(holder, &batch, policy, &mut stats)?;
+    }
+    Ok(stats)
+}
+
+#[test]
+fn test_record_lines() {
+    let record = Record {
+        key: Blob::from(b"user:1".to_vec()),
+        ttl: Some(5000),
+        value: RecordValue::Hash(vec![(
+            Blob::from(b"avatar".to_vec()),
+            Blob::from(vec![0xff, 0xd8, 0xff]),
+        )]),
+    };
+    let line = serde_json::to_string(&record).unwrap();
+    assert_eq!(
+        r#"{"key":"user:1","ttl":5000,"type":"hash","value":[["avatar",{"base64":"/9j/"}]]}"#,
+        line
+    );
+    assert_eq!(record, serde_json::from_str(&line).unwrap());
+
+    let zset: Record =
+        serde_json::from_str(r#"{"key":"rank","type":"zset","value":[["a","inf"]]}"#).unwrap();
+    assert_eq!(None, zset.ttl);
+    assert_eq!(
+        RecordValue::Zset(vec![(Blob::Text("a".to_string()), "inf".to_string())]),
+        zset.value
+    );
+    assert!(serde_json::from_str::<Record>(r#"{"key":"k","type":"bitfield","value":1}"#).is_err());
+    assert_eq!(
+        vec![0xff, 0xd8, 0xff],
+        Blob::from(vec![0xff, 0xd8, 0xff]).to_bytes().unwrap()
+    );
+    assert_eq!(Ok(Conflict::Replace), "replace".parse());
+    assert!("overwrite".parse::<Conflict>().is_err());
+}
+
+#[test]
+fn test_import_bad_line() {
+    let mut holder = ConnectionHolder::new();
+    let input = "\n{\"key\": \"a\", \"type\": \"string\"\n";
+    match import_keys(&mut holder, input.as_bytes(), Conflict::Skip) {
+        Err(TransferError::Json(line, _)) => assert_eq!(2, line),
+        other => panic!("expected a json error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_export_import_roundtrip() -> Result<(), TransferError> {
+    let mut holder = ConnectionHolder::new();
+    holder.put("redis://127.0.0.1:6379/0")?;
+    holder.execute(&["DEL", "xfer:s", "xfer:l", "xfer:z", "xfer:h", "xfer:x"])?;
+    holder.execute(&[&b"SET"[..], &b"xfer:s"[..], &[0xff, 0x00][..]])?;
+    holder.execute(&["PEXPIRE", "xfer:s", "100000"])?;
+    holder.execute(&["RPUSH", "xfer:l", "a", "b"])?;
+    holder.execute(&["ZADD", "xfer:z", "inf", "top", "1.5", "mid"])?;
+    holder.execute(&["HSET", "xfer:h", "f", "v"])?;
+    holder.execute(&["XADD", "xfer:x", "1-1", "f", "v"])?;
+
+    let mut out = Vec::new();
+    let stats = export_keys(&mut holder, ScanFilter::matching("xfer:*"), &mut out)?;
+    assert_eq!(5, stats.keys);
+
+    assert!(matches!(
+        import_keys(&mut holder, &out[..], Conflict::Fail),
+        Err(TransferError::Exists(_))
+    ));
+    let stats = import_keys(&mut holder, &out[..], Conflict::Skip)?;
+    assert_eq!(5, stats.skipped);
+
+    holder.execute(&["DEL", "xfer:s", "xfer:l", "xfer:z", "xfer:h", "xfer:x"])?;
+    holder.execute(&["RPUSH", "xfer:l", "old"])?;
+    let stats = import_keys(&mut holder, &out[..], Conflict::Replace)?;
+    assert_eq!((5, 1), (stats.keys, stats.replaced));
+    let list: Vec<String> =
+        redis::from_redis_value(&holder.execute(&["LRANGE", "xfer:l", "0", "-1"])?)?;
+    assert_eq!(vec!["a", "b"], list);
+    let raw: Vec<u8> = redis::from_redis_value(&holder.execute(&["GET", "xfer:s"])?)?;
+    assert_eq!(vec![0xff, 0x00], raw);
+    let pttl: i64 = redis::from_redis_value(&holder.execute(&["PTTL", "xfer:s"])?)?;
+    assert!(pttl > 0);
+    let score: String = redis::from_redis_value(&holder.execute(&["ZSCORE", "xfer:z", "top"])?)?;
+    assert_eq!("inf", score);
+
+    holder.execute(&["DEL", "xfer:s", "xfer:l", "xfer:z", "xfer:h", "xfer:x"])?;
+    Ok(())
+}
+
+#[test]
+fn test_fetch_long_collections() -> RedisResult<()> {
+    let mut holder = ConnectionHolder::new();
+    holder.put("redis://127.0.0.1:6379/0")?;
+    let keys: Vec<Vec<u8>> = ["big:l", "big:s", "big:z", "big:h", "big:x"]
+        .iter()
+        .map(|k| k.as_bytes().to_vec())
+        .collect();
+    let mut pipe = redis::pipe();
+    pipe.cmd("DEL").arg(keys.clone()).ignore();
+    let n = CHUNK * 2 + 5;
+    for i in 0..n {
+        pipe.cmd("RPUSH").arg("big:l").arg(i).ignore();
+        pipe.cmd("SADD").arg("big:s").arg(i).ignore();
+        pipe.cmd("ZADD").arg("big:z").arg(i).arg(i).ignore();
+        pipe.cmd("HSET").arg("big:h").arg(i).arg(i).ignore();
+        pipe.cmd("XADD").arg("big:x").arg(format!("1-{}", i + 1)).arg("f").arg(i).ignore();
+    }
+    holder.pipeline::<()>(&pipe)?;
+
+    let records = fetch_records(&mut holder, &keys)?;
+    let lens: Vec<usize> = records
+        .iter()
+        .map(|r| match &r.as_ref().unwrap().value {
+            RecordValue::List(items) | RecordValue::Set(items) => items.len(),
+            RecordValue::Zset(items) => items.len(),
+            RecordValue::Hash(items) => items.len(),
+            RecordValue::Stream(entries) => entries.len(),
+            RecordValue::String(_) => 0,
+        })
+        .collect();
+    assert_eq!(vec![n; 5], lens);
+    match &records[0].as_ref().unwrap().value {
+        RecordValue::List(items) => assert_eq!(Blob::Text((n - 1).to_string()), items[n - 1]),
+        other => panic!("not a list {:?}", other),
+    }
+    holder.pipeline::<()>(redis::pipe().cmd("DEL").arg(keys).ignore())?;
+    Ok(())
+}
@@ -1,5 +1,770 @@
 extern crate hyper;
 
+use std::collections::HashMap;
+use std::io;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use redis::{RedisResult, Value};
+use tui::backend::{Backend, CrosstermBackend};
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use tui::{Frame, Terminal};
+
+use crate::cli::{format_error, format_reply};
+use crate::highlight::CodeHighlighter;
+use crate::info::{Delta, Info};
+use crate::keytree::{KeyTree, Row, TreeNode};
+use crate::redis_conn::{ConnectionHolder, DashBorad};
+use crate::sniff::Format;
+use crate::value::{Decoded, DecoderChain};
+use crate::viewer::{Element, ValueKind, ValueViewer};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Panel {
+    Connections,
+    Keys,
+    Value,
+}
+
+impl Panel {
+    fn next(self) -> Panel {
+        match self {
+            Panel::Connections => Panel::Keys,
+            Panel::Keys => Panel::Value,
+            Panel::Value => Panel::Connections,
+        }
+    }
+
+    fn prev(self) -> Panel {
+        self.next().next()
+    }
+}
+
+/// the selected key as the value panel shows it, strings are highlighted once when loaded
+pub struct ValueView {
+    pub viewer: ValueViewer,
+    //what decoding found, eg "Json" or "[gzip] JavaSerialization"
+    pub format: String,
+    lines: Vec<Spans<'static>>,
+    //the selected element of a collection, or the line a string is scrolled to
+    state: ListState,
+}
+
+impl ValueView {
+    fn new(viewer: ValueViewer, h: &CodeHighlighter, chain: &DecoderChain) -> ValueView {
+        let mut view = ValueView {
+            viewer,
+            format: String::new(),
+            lines: vec![],
+            state: ListState::default(),
+        };
+        view.render(h, chain);
+        view.state.select(Some(0));
+        view
+    }
+
+    fn render(&mut self, h: &CodeHighlighter, chain: &DecoderChain) {
+        let viewer = &self.viewer;
+        self.format.clear();
+        self.lines = match (&viewer.kind, viewer.elements.first()) {
+            (ValueKind::String, Some(Element::Item(raw))) => {
+                let val = chain.decode(&viewer.key, raw);
+                self.format = format!("{:?}", val.detection.format);
+                if !val.layers.is_empty() {
+                    self.format = format!("[{}] {}", val.layers_desc(), self.format);
+                }
+                let (text, extension) = match &val.decoded {
+                    Decoded::Json(json) => (
+                        serde_json::to_string_pretty(json).unwrap_or_default(),
+                        "json",
+                    ),
+                    Decoded::Text(text) if val.detection.format == Format::Xml => {
+                        (text.clone(), "xml")
+                    }
+                    Decoded::Text(text) => (text.clone(), "txt"),
+                    Decoded::Java(contents) => (format!("{:#?}", contents), "txt"),
+                    Decoded::Hessian(obj) => (format!("{:#?}", obj), "txt"),
+                    Decoded::Raw(_) => (format_reply(&Value::Data(raw.clone())), "txt"),
+                };
+                highlighted(h, &text, extension)
+            }
+            (ValueKind::Json, Some(Element::Item(raw))) => {
+                let text = serde_json::from_slice::<serde_json::Value>(raw)
+                    .and_then(|json| serde_json::to_string_pretty(&json))
+                    .unwrap_or_else(|_| String::from_utf8_lossy(raw).into_owned());
+                highlighted(h, &text, "json")
+            }
+            (ValueKind::HyperLogLog, _) => vec![Spans::from(format!(
+                "about {} distinct elements",
+                viewer.count.unwrap_or(0)
+            ))],
+            (ValueKind::Module(name), _) => vec![Spans::from(format!(
+                "{} is a module type without a generic view",
+                name
+            ))],
+            _ => {
+                let mut lines: Vec<Spans> = viewer
+                    .decoded_lines(chain)
+                    .into_iter()
+                    .map(Spans::from)
+                    .collect();
+                if viewer.has_more() {
+                    lines.push(Spans::from("... n loads more"));
+                }
+                lines
+            }
+        };
+        let last = self.lines.len().saturating_sub(1);
+        if self.state.selected().is_some_and(|i| i > last) {
+            self.state.select(Some(last));
+        }
+    }
+
+    /// the element an edit or delete works on
+    fn selected(&self) -> Option<usize> {
+        match self.viewer.kind {
+            ValueKind::String | ValueKind::Json => Some(0),
+            _ => self
+                .state
+                .selected()
+                .filter(|&i| i < self.viewer.elements.len()),
+        }
+    }
+
+    fn title(&self) -> String {
+        let v = &self.viewer;
+        let count = v.count.map_or("-".to_string(), |c| c.to_string());
+        format!(
+            "{} {} ttl {} len {} {}",
+            String::from_utf8_lossy(&v.key),
+            v.kind,
+            v.ttl,
+            count,
+            self.format
+        )
+    }
+}
+
+/// what the status line is asking for
+#[derive(Debug, Clone, PartialEq)]
+enum Input {
+    Edit(usize),
+    Add,
+    Delete(usize),
+}
+
+pub struct App {
+    holder: ConnectionHolder,
+    dashboard: DashBorad,
+    highlighter: CodeHighlighter,
+    pub focus: Panel,
+    conn_state: ListState,
+    tree: KeyTree,
+    rows: Vec<Row>,
+    key_state: ListState,
+    value: Option<ValueView>,
+    input: Option<(Input, String)>,
+    //by connection id, the builtin chain sits under 0
+    decoders: HashMap<u32, DecoderChain>,
+    status: String,
+    quit: bool,
+}
+
+fn move_selection(state: &mut ListState, len: usize, step: isize) {
+    if len == 0 {
+        state.select(None);
+        return;
+    }
+    let cur = state.selected().unwrap_or(0) as isize;
+    let next = (cur + step).max(0).min(len as isize - 1);
+    state.select(Some(next as usize));
+}
+
+fn human_bytes(n: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "K", "M", "G", "T"];
+    let mut n = n;
+    let mut unit = 0;
+    while n.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+        n /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}{}", n, UNITS[unit])
+    } else {
+        format!("{:.1}{}", n, UNITS[unit])
+    }
+}
+
+fn human_duration(secs: u64) -> String {
+    match secs {
+        s if s >= 86400 => format!("{}d{}h", s / 86400, s % 86400 / 3600),
+        s if s >= 3600 => format!("{}h{}m", s / 3600, s % 3600 / 60),
+        s if s >= 60 => format!("{}m{}s", s / 60, s % 60),
+        s => format!("{}s", s),
+    }
+}
+
+/// the metrics panel text, rates need two INFO samples so they show up after the first refresh
+pub fn metric_lines(info: Option<&Info>, delta: Option<&Delta>) -> Vec<String> {
+    let info = match info {
+        Some(info) => info,
+        None => return vec!["no INFO yet".to_string()],
+    };
+    let mut lines = vec![
+        format!(
+            "redis {} {}  role {}  up {}  clients {} blocked {}",
+            info.server.redis_version,
+            info.server.redis_mode,
+            info.replication.role,
+            human_duration(info.server.uptime_in_seconds),
+            info.clients.connected_clients,
+            info.clients.blocked_clients
+        ),
+        format!(
+            "memory {} peak {} rss {} frag {:.2}  keys {}",
+            human_bytes(info.memory.used_memory as f64),
+            human_bytes(info.memory.used_memory_peak as f64),
+            human_bytes(info.memory.used_memory_rss as f64),
+            info.memory.mem_fragmentation_ratio,
+            info.total_keys()
+        ),
+    ];
+    match delta {
+        Some(d) => {
+            lines.push(format!(
+                "ops/s {:.1}  hit {}  in {}/s out {}/s  cpu {:.1}%",
+                d.ops_per_sec,
+                d.hit_ratio
+                    .map(|r| format!("{:.1}%", r * 100.0))
+                    .unwrap_or_else(|| "-".to_string()),
+                human_bytes(d.net_input_per_sec),
+                human_bytes(d.net_output_per_sec),
+                d.cpu_percent
+            ));
+            lines.push(format!(
+                "conns/s {:.1}  expired/s {:.1}  evicted/s {:.1}  memory {}{}",
+                d.connections_per_sec,
+                d.expired_per_sec,
+                d.evicted_per_sec,
+                if d.used_memory_change >= 0 { "+" } else { "-" },
+                human_bytes(d.used_memory_change.abs() as f64)
+            ));
+        }
+        None => lines.push(format!(
+            "ops/s {}  (rates after the next refresh)",
+            info.stats.instantaneous_ops_per_sec
+        )),
+    }
+    lines
+}
+
+impl App {
+    pub fn new(holder: ConnectionHolder) -> App {
+        let mut conn_state = ListState::default();
+        if let Some(pos) = holder
+            .current()
+            .and_then(|cur| holder.list().iter().position(|c| c.id == cur.id))
+        {
+            conn_state.select(Some(pos));
+        }
+        App {
+            holder,
+            dashboard: DashBorad::new(),
+            highlighter: CodeHighlighter::default(),
+            focus: Panel::Connections,
+            conn_state,
+            tree: KeyTree::default(),
+            rows: Vec::new(),
+            key_state: ListState::default(),
+            value: None,
+            input: None,
+            decoders: vec![(0, DecoderChain::builtin())].into_iter().collect(),
+            status: String::new(),
+            quit: false,
+        }
+    }
+
+    pub fn holder(&mut self) -> &mut ConnectionHolder {
+        &mut self.holder
+    }
+
+    fn selected_node(&self) -> Option<(&Row, &TreeNode)> {
+        let row = self.rows.get(self.key_state.selected()?)?;
+        Some((row, self.tree.node(&row.path)?))
+    }
+
+    /// the key under the cursor, none when it is on a group
+    pub fn selected_key(&self) -> Option<&[u8]> {
+        self.selected_node()
+            .and_then(|(_, node)| node.key.as_ref())
+            .map(|k| k.key.as_slice())
+    }
+
+    fn report<T>(&mut self, res: RedisResult<T>) -> Option<T> {
+        match res {
+            Ok(v) => Some(v),
+            Err(err) => {
+                self.status = format_error(&err);
+                None
+            }
+        }
+    }
+
+    pub fn refresh_metrics(&mut self) {
+        let res = self.dashboard.refresh(&mut self.holder).map(|_| ());
+        self.report(res);
+    }
+
+    /// forget the tree and scan the top level of the current db again
+    pub fn reload_keys(&mut self) {
+        self.tree.reset();
+        let res = self.tree.expand(&mut self.holder, &[]);
+        self.report(res);
+        self.rows = self.tree.rows();
+        self.key_state
+            .select(if self.rows.is_empty() { None } else { Some(0) });
+        self.value = None;
+    }
+
+    /// expand or collapse the group under the cursor, the cursor stays on it
+    fn toggle_group(&mut self) {
+        let path = match self.selected_node() {
+            Some((row, node)) if node.is_group() => row.path.clone(),
+            _ => return,
+        };
+        let res = self.tree.toggle(&mut self.holder, &path);
+        self.report(res);
+        self.rows = self.tree.rows();
+        self.key_state
+            .select(self.rows.iter().position(|r| r.path == path));
+    }
+
+    pub fn load_value(&mut self) {
+        let key = match self.selected_key() {
+            Some(key) => key.to_vec(),
+            None => return,
+        };
+        let res = ValueViewer::open(&mut self.holder, &key);
+        if let Some(viewer) = self.report(res) {
+            let chain = chain_for(&self.decoders, &self.holder);
+            self.value = Some(ValueView::new(viewer, &self.highlighter, chain));
+        }
+    }
+
+    /// decoder rules for a connection, eg from its profile, others use the builtin chain
+    pub fn set_decoders(&mut self, id: u32, chain: DecoderChain) {
+        self.decoders.insert(id, chain);
+    }
+
+    /// run a viewer call on the open value and draw it again
+    fn with_value<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut ValueViewer, &mut ConnectionHolder) -> RedisResult<()>,
+    {
+        let view = match &mut self.value {
+            Some(view) => view,
+            None => return,
+        };
+        let res = f(&mut view.viewer, &mut self.holder);
+        view.render(&self.highlighter, chain_for(&self.decoders, &self.holder));
+        if self.report(res).is_some() {
+            self.status.clear();
+        }
+    }
+
+    /// strings flip between text and their bits
+    fn toggle_bitmap(&mut self) {
+        let (key, kind) = match &self.value {
+            Some(view) if view.viewer.kind == ValueKind::String => {
+                (view.viewer.key.clone(), ValueKind::Bitmap)
+            }
+            Some(view) if view.viewer.kind == ValueKind::Bitmap => {
+                (view.viewer.key.clone(), ValueKind::String)
+            }
+            _ => return,
+        };
+        let res = ValueViewer::open_as(&mut self.holder, &key, kind);
+        if let Some(viewer) = self.report(res) {
+            let chain = chain_for(&self.decoders, &self.holder);
+            self.value = Some(ValueView::new(viewer, &self.highlighter, chain));
+        }
+    }
+
+    fn start_input(&mut self, input: Input) {
+        let view = match &self.value {
+            Some(view) => view,
+            None => return,
+        };
+        let text = match &input {
+            Input::Edit(i) => view.viewer.elements[*i].edit_text(&view.viewer.kind),
+            _ => String::new(),
+        };
+        self.input = Some((input, text));
+    }
+
+    fn submit_input(&mut self, input: Input, text: String) {
+        let kind = match &self.value {
+            Some(view) => view.viewer.kind.clone(),
+            None => return,
+        };
+        let element = match input {
+            Input::Delete(_) => None,
+            _ => match Element::parse(&kind, &text) {
+                Ok(element) => Some(element),
+                Err(err) => {
+                    self.status = err;
+                    return;
+                }
+            },
+        };
+        match (input, element) {
+            (Input::Edit(i), Some(element)) => self.with_value(|v, h| v.update(h, i, element)),
+            (Input::Add, Some(element)) => self.with_value(|v, h| v.add(h, element)),
+            (Input::Delete(i), _) if text == "y" => self.with_value(|v, h| v.delete(h, i)),
+            _ => {}
+        }
+    }
+
+    fn input_key(&mut self, key: KeyEvent) {
+        let (input, mut text) = match self.input.take() {
+            Some(input) => input,
+            None => return,
+        };
+        match (&input, key.code) {
+            (Input::Delete(_), KeyCode::Char(c)) => self.submit_input(input, c.to_string()),
+            (_, KeyCode::Esc) | (Input::Delete(_), _) => {}
+            (_, KeyCode::Enter) => self.submit_input(input, text),
+            (_, KeyCode::Backspace) => {
+                text.pop();
+                self.input = Some((input, text));
+            }
+            (_, KeyCode::Char(c)) => {
+                text.push(c);
+                self.input = Some((input, text));
+            }
+            _ => self.input = Some((input, text)),
+        }
+    }
+
+    fn value_key(&mut self, code: KeyCode) {
+        let selected = self.value.as_ref().and_then(|v| v.selected());
+        match (code, selected) {
+            (KeyCode::Char('e'), Some(i)) => self.start_input(Input::Edit(i)),
+            (KeyCode::Char('d'), Some(i)) => self.start_input(Input::Delete(i)),
+            (KeyCode::Char('a'), _) => self.start_input(Input::Add),
+            (KeyCode::Char('n'), _) => self.with_value(|v, h| v.next_page(h).map(|_| ())),
+            (KeyCode::Char('b'), _) => self.toggle_bitmap(),
+            _ => {}
+        }
+    }
+
+    fn switch_connection(&mut self) {
+        let id = match self
+            .conn_state
+            .selected()
+            .and_then(|i| self.holder.list().get(i))
+        {
+            Some(c) => c.id,
+            None => return,
+        };
+        let res = self.holder.switch(&id.to_string());
+        if self.report(res).is_some() {
+            //rates from another server make no sense
+            self.dashboard = DashBorad::new();
+            self.status.clear();
+            self.refresh_metrics();
+            self.reload_keys();
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+        if self.input.is_some() {
+            self.input_key(key);
+            return;
+        }
+        if self.focus == Panel::Value
+            && matches!(key.code, KeyCode::Char('e' | 'a' | 'd' | 'n' | 'b'))
+        {
+            self.value_key(key.code);
+            return;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Tab | KeyCode::Right => self.focus = self.focus.next(),
+            KeyCode::BackTab | KeyCode::Left => self.focus = self.focus.prev(),
+            KeyCode::Char('r') => {
+                self.status.clear();
+                self.refresh_metrics();
+                self.reload_keys();
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.step(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.step(1),
+            KeyCode::PageUp => self.step(-20),
+            KeyCode::PageDown => self.step(20),
+            KeyCode::Home | KeyCode::Char('g') => self.step(-(u16::MAX as isize)),
+            KeyCode::End | KeyCode::Char('G') => self.step(u16::MAX as isize),
+            KeyCode::Enter => match self.focus {
+                Panel::Connections => self.switch_connection(),
+                Panel::Keys if self.selected_key().is_none() => self.toggle_group(),
+                Panel::Keys => {
+                    self.load_value();
+                    self.focus = Panel::Value;
+                }
+                Panel::Value => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn step(&mut self, step: isize) {
+        match self.focus {
+            Panel::Connections => {
+                let len = self.holder.size();
+                move_selection(&mut self.conn_state, len, step)
+            }
+            Panel::Keys => move_selection(&mut self.key_state, self.rows.len(), step),
+            Panel::Value => {
+                let view = match &mut self.value {
+                    Some(view) => view,
+                    None => return,
+                };
+                let at_end = view.state.selected() == Some(view.lines.len().saturating_sub(1));
+                //walking off the last line of a collection loads the next page
+                if step > 0 && at_end && view.viewer.has_more() {
+                    self.with_value(|v, h| v.next_page(h).map(|_| ()));
+                }
+                if let Some(view) = &mut self.value {
+                    move_selection(&mut view.state, view.lines.len(), step);
+                }
+            }
+        }
+    }
+
+    fn block(&self, title: String, panel: Option<Panel>) -> Block<'static> {
+        let border = if panel == Some(self.focus) {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+        Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(border)
+    }
+
+    pub fn draw<B: Backend>(&mut self, f: &mut Frame<B>) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Min(5),
+                    Constraint::Length(6),
+                    Constraint::Length(1),
+                ]
+                .as_ref(),
+            )
+            .split(f.size());
+        let cols = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(
+                [
+                    Constraint::Percentage(20),
+                    Constraint::Percentage(30),
+                    Constraint::Percentage(50),
+                ]
+                .as_ref(),
+            )
+            .split(rows[0]);
+        self.draw_connections(f, cols[0]);
+        self.draw_keys(f, cols[1]);
+        self.draw_value(f, cols[2]);
+        self.draw_metrics(f, rows[1]);
+
+        let help = match &self.input {
+            Some((Input::Edit(_), text)) => format!("edit> {}", text),
+            Some((Input::Add, text)) => format!("add> {}", text),
+            Some((Input::Delete(i), _)) => {
+                let element = self.value.as_ref().map(|v| &v.viewer.elements[*i]);
+                format!(
+                    "delete {}? y/n",
+                    element.map_or(String::new(), |e| e.to_string())
+                )
+            }
+            None if !self.status.is_empty() => self.status.clone(),
+            None if self.focus == Panel::Value => {
+                "e edit  a add  d delete  n more  b bitmap  Tab panel  q quit".to_string()
+            }
+            None => "Tab panel  j/k move  Enter open  r refresh  q quit".to_string(),
+        };
+        f.render_widget(
+            Paragraph::new(help).style(Style::default().fg(Color::DarkGray)),
+            rows[2],
+        );
+    }
+
+    fn draw_connections<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        let cur = self.holder.current().map(|c| c.id);
+        let items: Vec<ListItem> = self
+            .holder
+            .list()
+            .iter()
+            .map(|c| {
+                let mark = if Some(c.id) == cur { "*" } else { " " };
+                ListItem::new(format!("{}{} [{}]", mark, c.name, c.db()))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(self.block("Connections".to_string(), Some(Panel::Connections)))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(list, area, &mut self.conn_state);
+    }
+
+    fn draw_keys<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        let items: Vec<ListItem> = self
+            .rows
+            .iter()
+            .filter_map(|row| self.tree.node(&row.path).map(|n| (row, n)))
+            .map(|(row, node)| ListItem::new(tree_label(node, row.depth)))
+            .collect();
+        let root = self.tree.root();
+        let more = if root.partial { "+" } else { "" };
+        let title = format!("Keys ({}{})", root.stats.keys, more);
+        let list = List::new(items)
+            .block(self.block(title, Some(Panel::Keys)))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(list, area, &mut self.key_state);
+    }
+
+    fn draw_value<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        let block = self.block(
+            self.value
+                .as_ref()
+                .map_or("Value".to_string(), |v| v.title()),
+            Some(Panel::Value),
+        );
+        let view = match &mut self.value {
+            Some(view) => view,
+            None => return f.render_widget(block, area),
+        };
+        let items: Vec<ListItem> = view.lines.iter().cloned().map(ListItem::new).collect();
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(list, area, &mut view.state);
+    }
+
+    fn draw_metrics<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        let delta = self.dashboard.delta();
+        let lines: Vec<Spans> = metric_lines(self.dashboard.info(), delta.as_ref())
+            .into_iter()
+            .map(Spans::from)
+            .collect();
+        f.render_widget(
+            Paragraph::new(lines).block(self.block("Metrics".to_string(), None)),
+            area,
+        );
+    }
+}
+
+fn chain_for<'a>(
+    decoders: &'a HashMap<u32, DecoderChain>,
+    holder: &ConnectionHolder,
+) -> &'a DecoderChain {
+    holder
+        .current()
+        .and_then(|c| decoders.get(&c.id))
+        .unwrap_or(&decoders[&0])
+}
+
+fn tree_label(node: &TreeNode, depth: usize) -> String {
+    let indent = "  ".repeat(depth);
+    match &node.key {
+        Some(key) => format!("{}  {} {}", indent, node.name, key.kind),
+        None => {
+            let more = if node.partial { "+" } else { "" };
+            format!(
+                "{}{} {} ({}{} keys, {}, {} ttl)",
+                indent,
+                if node.expanded { "v" } else { ">" },
+                node.name,
+                node.stats.keys,
+                more,
+                human_bytes(node.stats.memory as f64),
+                node.stats.expiring
+            )
+        }
+    }
+}
+
+fn highlighted(h: &CodeHighlighter, text: &str, extension: &str) -> Vec<Spans<'static>> {
+    h.highlight(text, extension)
+        .into_iter()
+        .map(|line| {
+            let spans: Vec<Span<'static>> = line
+                .into_iter()
+                .map(|(style, piece)| {
+                    let fg = style.foreground;
+                    Span::styled(piece, Style::default().fg(Color::Rgb(fg.r, fg.g, fg.b)))
+                })
+                .collect();
+            Spans::from(spans)
+        })
+        .collect()
+}
+
+/// full screen dashboard over the app's connections, INFO is sampled every interval
+pub fn run_dashboard(mut app: App, interval: Duration) -> crossterm::Result<()> {
+    app.refresh_metrics();
+    app.reload_keys();
+
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+    let res = event_loop(&mut terminal, &mut app, interval);
+
+    //put the terminal back even when drawing failed
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    res
+}
+
+fn event_loop<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    interval: Duration,
+) -> crossterm::Result<()> {
+    let mut last_refresh = Instant::now();
+    while !app.quit {
+        terminal.draw(|f| app.draw(f))?;
+        let timeout = interval
+            .checked_sub(last_refresh.elapsed())
+            .unwrap_or_else(|| Duration::from_secs(0));
+        if event::poll(timeout)? {
+            if let Event::Key(key) = event::read()? {
+                app.handle_key(key);
+            }
+        }
+        if last_refresh.elapsed() >= interval {
+            app.refresh_metrics();
+            last_refresh = Instant::now();
+        }
+    }
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_basic_httpclient() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     use hyper::body::HttpBody as _;
@@ -15,3 +780,132 @@ async fn test_basic_httpclient() -> Result<(), Box<dyn std::error::Error + Send
     }
     Ok(())
 }
+
+#[cfg(test)]
+fn test_key(name: &str) -> crate::browser::KeyInfo {
+    crate::browser::KeyInfo {
+        key: name.as_bytes().to_vec(),
+        kind: "string".to_string(),
+        ttl: -1,
+        len: Some(3),
+        memory: Some(32),
+    }
+}
+
+#[test]
+fn test_dashboard_navigation() {
+    let mut app = App::new(ConnectionHolder::new());
+    assert_eq!(Panel::Connections, app.focus);
+    app.handle_key(KeyEvent::from(KeyCode::Tab));
+    assert_eq!(Panel::Keys, app.focus);
+    app.handle_key(KeyEvent::from(KeyCode::BackTab));
+    app.handle_key(KeyEvent::from(KeyCode::BackTab));
+    assert_eq!(Panel::Value, app.focus);
+
+    app.focus = Panel::Keys;
+    app.tree
+        .set_keys(vec![test_key("a:1"), test_key("b"), test_key("c")]);
+    app.rows = app.tree.rows();
+    app.key_state.select(Some(0));
+    assert_eq!(None, app.selected_key());
+    app.handle_key(KeyEvent::from(KeyCode::Char('j')));
+    app.handle_key(KeyEvent::from(KeyCode::Down));
+    app.handle_key(KeyEvent::from(KeyCode::Down));
+    assert_eq!(Some(&b"c"[..]), app.selected_key());
+    app.handle_key(KeyEvent::from(KeyCode::Home));
+    assert_eq!("a:", app.selected_node().unwrap().1.name);
+    //expanding scans redis, without a connection it only reports why
+    app.handle_key(KeyEvent::from(KeyCode::Enter));
+    assert_eq!(Panel::Keys, app.focus);
+    assert!(app.status.contains("not connected"));
+
+    assert!(!app.quit);
+    app.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL));
+    assert!(app.quit);
+}
+
+#[test]
+fn test_value_input() {
+    let mut app = App::new(ConnectionHolder::new());
+    let viewer = ValueViewer::from_elements(
+        b"scores",
+        ValueKind::ZSet,
+        vec![
+            Element::Scored(b"a".to_vec(), 1.0),
+            Element::Scored(b"b c".to_vec(), 2.0),
+        ],
+    );
+    app.value = Some(ValueView::new(viewer, &app.highlighter, &app.decoders[&0]));
+    app.focus = Panel::Value;
+    app.handle_key(KeyEvent::from(KeyCode::Down));
+    app.handle_key(KeyEvent::from(KeyCode::Char('e')));
+    assert_eq!(Some((Input::Edit(1), "2 \"b c\"".to_string())), app.input);
+    //typing goes to the input, not to the panels
+    app.handle_key(KeyEvent::from(KeyCode::Char('q')));
+    assert!(!app.quit);
+    app.handle_key(KeyEvent::from(KeyCode::Esc));
+    assert_eq!(None, app.input);
+
+    app.handle_key(KeyEvent::from(KeyCode::Char('a')));
+    for c in "x member".chars() {
+        app.handle_key(KeyEvent::from(KeyCode::Char(c)));
+    }
+    app.handle_key(KeyEvent::from(KeyCode::Enter));
+    assert_eq!("bad score x", app.status);
+
+    app.handle_key(KeyEvent::from(KeyCode::Char('d')));
+    app.handle_key(KeyEvent::from(KeyCode::Char('n')));
+    assert_eq!(None, app.input);
+    assert_eq!(2, app.value.as_ref().unwrap().viewer.elements.len());
+}
+
+#[test]
+fn test_metric_lines() {
+    let prev = Info::parse(&std::fs::read_to_string("testdata/info_1.txt").unwrap());
+    let cur = Info::parse(&std::fs::read_to_string("testdata/info_2.txt").unwrap());
+    assert_eq!(vec!["no INFO yet"], metric_lines(None, None));
+    let lines = metric_lines(Some(&cur), None);
+    assert!(lines[0].starts_with(&format!("redis {}", cur.server.redis_version)));
+    assert!(lines[2].contains("rates after"));
+
+    let delta = Delta::between(&prev, &cur, Duration::from_secs(10));
+    let lines = metric_lines(Some(&cur), Some(&delta));
+    assert_eq!(4, lines.len());
+    assert!(lines[2].starts_with("ops/s "));
+
+    assert_eq!("512B", human_bytes(512.0));
+    assert_eq!("1.5K", human_bytes(1536.0));
+    assert_eq!("1d2h", human_duration(93600));
+}
+
+#[test]
+fn test_draw_dashboard() {
+    let mut app = App::new(ConnectionHolder::new());
+    app.tree.set_keys(vec![
+        test_key("user:1"),
+        test_key("user:2"),
+        test_key("order"),
+    ]);
+    app.rows = app.tree.rows();
+    let viewer = ValueViewer::from_elements(
+        b"user:1",
+        ValueKind::String,
+        vec![Element::Item(b"{\"id\": 1}".to_vec())],
+    );
+    app.value = Some(ValueView::new(viewer, &app.highlighter, &app.decoders[&0]));
+    let mut terminal = Terminal::new(tui::backend::TestBackend::new(100, 20)).unwrap();
+    terminal.draw(|f| app.draw(f)).unwrap();
+    let buffer = terminal.backend().buffer();
+    let screen: String = buffer.content.iter().map(|c| c.symbol.as_str()).collect();
+    for text in &[
+        "Connections",
+        "Keys (3)",
+        "> user: (2 keys, 64B, 0 ttl)",
+        "  order string",
+        "user:1 string ttl -1 len 1 Json",
+        "  \"id\": 1",
+        "Metrics",
+    ] {
+        assert!(screen.contains(text), "{} missing", text);
+    }
+}
@@ -0,0 +1,516 @@
+use std::fmt;
+
+use encoding::{all::GBK, DecoderTrap, Encoding};
+
+use quick_xml::events::Event;
+use quick_xml::{Reader, Writer};
+use serde::{Deserialize, Serialize};
+
+use crate::browser::glob_match;
+use crate::compress::{self, Compression};
+use crate::hessian;
+use crate::javaser;
+use crate::sniff::{self, Detection, Format};
+
+#[derive(Debug)]
+pub enum Decoded {
+    Java(Vec<javaser::Content>),
+    Hessian(hessian::Object),
+    Json(serde_json::Value),
+    Text(String),
+    Raw(Vec<u8>),
+}
+
+/// a redis value after compression layers were peeled off and the payload decoded
+#[derive(Debug)]
+pub struct DecodedValue {
+    //compression layers, outermost first
+    pub layers: Vec<Compression>,
+    pub detection: Detection,
+    //name of the decoder that produced decoded
+    pub decoder: Option<String>,
+    pub decoded: Decoded,
+    //why decompression or decoding stopped early, if it did
+    pub error: Option<String>,
+}
+
+impl DecodedValue {
+    /// the payload on one line for lists of elements
+    pub fn summary(&self) -> String {
+        let payload = match &self.decoded {
+            Decoded::Java(contents) => format!("{:?}", contents),
+            Decoded::Hessian(obj) => format!("{:?}", obj),
+            Decoded::Json(json) => json.to_string(),
+            Decoded::Text(text) => text.clone(),
+            Decoded::Raw(raw) => String::from_utf8_lossy(raw).into_owned(),
+        };
+        if self.layers.is_empty() {
+            payload
+        } else {
+            format!("[{}] {}", self.layers_desc(), payload)
+        }
+    }
+
+    pub fn layers_desc(&self) -> String {
+        self.layers
+            .iter()
+            .map(|l| l.to_string())
+            .collect::<Vec<String>>()
+            .join(" > ")
+    }
+}
+
+impl fmt::Display for DecodedValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.layers.is_empty() {
+            write!(f, "[{}] ", self.layers_desc())?;
+        }
+        write!(f, "{:?} ", self.detection.format)?;
+        match &self.decoded {
+            Decoded::Java(contents) => write!(f, "{:?}", contents)?,
+            Decoded::Hessian(obj) => write!(f, "{:?}", obj)?,
+            Decoded::Json(json) => write!(f, "{}", json)?,
+            Decoded::Text(text) => write!(f, "{}", text)?,
+            Decoded::Raw(raw) => write!(f, "{} bytes", raw.len())?,
+        }
+        if let Some(err) = &self.error {
+            write!(f, " ({})", err)?;
+        }
+        Ok(())
+    }
+}
+
+/// one way of reading a payload, a chain picks it by key pattern or by the sniffed format
+pub trait Decoder {
+    /// what rules call it by, eg hessian
+    fn name(&self) -> &str;
+    /// tried on values sniffed as this format when no rule names a decoder
+    fn accepts(&self, format: Format) -> bool;
+    fn decode(&self, data: &[u8]) -> Result<Decoded, String>;
+}
+
+pub struct JavaDecoder;
+pub struct HessianDecoder;
+pub struct JsonDecoder;
+pub struct XmlDecoder;
+pub struct GbkDecoder;
+pub struct TextDecoder;
+//leaves bytes alone, for keys whose values only look like something
+pub struct RawDecoder;
+
+impl Decoder for JavaDecoder {
+    fn name(&self) -> &str {
+        "java"
+    }
+
+    fn accepts(&self, format: Format) -> bool {
+        format == Format::JavaSerialization
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Decoded, String> {
+        javaser::parse(data)
+            .map(Decoded::Java)
+            .map_err(|err| format!("{:?}", err.map_input(|i| i.len())))
+    }
+}
+
+impl Decoder for HessianDecoder {
+    fn name(&self) -> &str {
+        "hessian"
+    }
+
+    fn accepts(&self, format: Format) -> bool {
+        matches!(format, Format::Hessian2 | Format::Hessian1)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Decoded, String> {
+        hessian::decode(data)
+            .map(Decoded::Hessian)
+            .map_err(|err| format!("{:?}", err))
+    }
+}
+
+impl Decoder for JsonDecoder {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn accepts(&self, format: Format) -> bool {
+        format == Format::Json
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Decoded, String> {
+        serde_json::from_slice(data)
+            .map(Decoded::Json)
+            .map_err(|err| err.to_string())
+    }
+}
+
+impl Decoder for XmlDecoder {
+    fn name(&self) -> &str {
+        "xml"
+    }
+
+    fn accepts(&self, format: Format) -> bool {
+        format == Format::Xml
+    }
+
+    /// well formed xml comes back indented
+    fn decode(&self, data: &[u8]) -> Result<Decoded, String> {
+        let text = std::str::from_utf8(data).map_err(|err| err.to_string())?;
+        let mut reader = Reader::from_str(text);
+        reader.trim_text(true);
+        let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+        let mut buf = Vec::new();
+        let (mut open, mut elements) = (0usize, 0);
+        loop {
+            let event = reader
+                .read_event(&mut buf)
+                .map_err(|err| format!("at {}: {}", reader.buffer_position(), err))?;
+            match &event {
+                Event::Start(_) => {
+                    open += 1;
+                    elements += 1;
+                }
+                Event::End(_) => open = open.saturating_sub(1),
+                Event::Empty(_) => elements += 1,
+                Event::Eof => break,
+                _ => (),
+            }
+            writer.write_event(event).map_err(|err| err.to_string())?;
+            buf.clear();
+        }
+        if elements == 0 || open > 0 {
+            return Err("not xml, no element or an unclosed one".to_string());
+        }
+        String::from_utf8(writer.into_inner())
+            .map(Decoded::Text)
+            .map_err(|err| err.to_string())
+    }
+}
+
+impl Decoder for GbkDecoder {
+    fn name(&self) -> &str {
+        "gbk"
+    }
+
+    fn accepts(&self, format: Format) -> bool {
+        format == Format::GbkText
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Decoded, String> {
+        GBK.decode(data, DecoderTrap::Strict)
+            .map(Decoded::Text)
+            .map_err(|err| err.into_owned())
+    }
+}
+
+impl Decoder for TextDecoder {
+    fn name(&self) -> &str {
+        "text"
+    }
+
+    fn accepts(&self, format: Format) -> bool {
+        format == Format::Utf8Text
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Decoded, String> {
+        String::from_utf8(data.to_vec())
+            .map(Decoded::Text)
+            .map_err(|err| err.to_string())
+    }
+}
+
+impl Decoder for RawDecoder {
+    fn name(&self) -> &str {
+        "raw"
+    }
+
+    fn accepts(&self, _: Format) -> bool {
+        false
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Decoded, String> {
+        Ok(Decoded::Raw(data.to_vec()))
+    }
+}
+
+/// keys matching the glob pattern are read with the named decoder
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecoderRule {
+    pub pattern: String,
+    pub decoder: String,
+}
+
+impl fmt::Display for DecoderRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.pattern, self.decoder)
+    }
+}
+
+/// decoders tried in order plus rules that pin one to a key pattern
+pub struct DecoderChain {
+    decoders: Vec<Box<dyn Decoder>>,
+    rules: Vec<DecoderRule>,
+    //decompression size cap
+    pub cap: usize,
+}
+
+impl Default for DecoderChain {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl DecoderChain {
+    pub fn builtin() -> DecoderChain {
+        DecoderChain {
+            decoders: vec![
+                Box::new(JavaDecoder),
+                Box::new(HessianDecoder),
+                Box::new(JsonDecoder),
+                Box::new(XmlDecoder),
+                Box::new(GbkDecoder),
+                Box::new(TextDecoder),
+                Box::new(RawDecoder),
+            ],
+            rules: vec![],
+            cap: compress::DEFAULT_SIZE_CAP,
+        }
+    }
+
+    /// the builtin decoders with rules, eg from a profile
+    pub fn with_rules(rules: &[DecoderRule]) -> Result<DecoderChain, String> {
+        let mut chain = Self::builtin();
+        for rule in rules {
+            chain.add_rule(rule.clone())?;
+        }
+        Ok(chain)
+    }
+
+    /// a decoder of the same name is replaced, a new one is tried before the builtin ones
+    pub fn register(&mut self, decoder: Box<dyn Decoder>) {
+        match self
+            .decoders
+            .iter()
+            .position(|d| d.name() == decoder.name())
+        {
+            Some(pos) => self.decoders[pos] = decoder,
+            None => self.decoders.insert(0, decoder),
+        }
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.decoders.iter().map(|d| d.name()).collect()
+    }
+
+    /// rules are checked in the order they were added, the first matching one wins
+    pub fn add_rule(&mut self, rule: DecoderRule) -> Result<(), String> {
+        if !self.names().contains(&rule.decoder.as_str()) {
+            return Err(format!(
+                "no decoder {}, known are {}",
+                rule.decoder,
+                self.names().join(", ")
+            ));
+        }
+        self.rules.push(rule);
+        Ok(())
+    }
+
+    pub fn rules(&self) -> &[DecoderRule] {
+        &self.rules
+    }
+
+    fn find(&self, name: &str) -> Option<&dyn Decoder> {
+        self.decoders
+            .iter()
+            .find(|d| d.name() == name)
+            .map(|d| d.as_ref())
+    }
+
+    /// the decoder a rule pins for the key, if any
+    pub fn pinned(&self, key: &[u8]) -> Option<&dyn Decoder> {
+        self.rules
+            .iter()
+            .find(|r| glob_match(r.pattern.as_bytes(), key))
+            .and_then(|r| self.find(&r.decoder))
+    }
+
+    /// peel compression, then decode with the pinned decoder or else the ones that take
+    /// the sniffed format, in order till one works. failures keep the bytes and say why
+    pub fn decode(&self, key: &[u8], raw: &[u8]) -> DecodedValue {
+        let (layers, data, error) = match compress::peel(raw, self.cap) {
+            Ok(peeled) => (peeled.layers, peeled.data, peeled.error),
+            Err(err) => (vec![], raw.to_vec(), Some(err.to_string())),
+        };
+        let detection = sniff::detect(&data);
+        let pinned = self.pinned(key);
+        let sniffed = self
+            .decoders
+            .iter()
+            .map(|d| d.as_ref())
+            .filter(|d| d.accepts(detection.format) && pinned.map(|p| p.name()) != Some(d.name()));
+        let mut decode_err = None;
+        for decoder in pinned.into_iter().chain(sniffed) {
+            match decoder.decode(&data) {
+                Ok(decoded) => {
+                    return DecodedValue {
+                        layers,
+                        detection,
+                        decoder: Some(decoder.name().to_string()),
+                        decoded,
+                        error,
+                    }
+                }
+                Err(err) => {
+                    decode_err.get_or_insert((decoder.name().to_string(), err));
+                }
+            }
+        }
+        DecodedValue {
+            layers,
+            detection,
+            decoder: decode_err.as_ref().map(|(name, _)| name.clone()),
+            decoded: Decoded::Raw(data),
+            error: error.or(decode_err.map(|(name, err)| format!("{}: {}", name, err))),
+        }
+    }
+}
+
+/// decode a raw value with the default decompression size cap
+pub fn decode_value(raw: &[u8]) -> DecodedValue {
+    decode_value_capped(raw, compress::DEFAULT_SIZE_CAP)
+}
+
+pub fn decode_value_capped(raw: &[u8], cap: usize) -> DecodedValue {
+    let chain = DecoderChain {
+        cap,
+        ..DecoderChain::builtin()
+    };
+    chain.decode(b"", raw)
+}
+
+#[test]
+fn test_decode_compressed_java_value() {
+    use std::io::Write;
+    let ser = std::fs::read("testdata/arraylist.ser").unwrap();
+    let mut snappy = snap::write::FrameEncoder::new(Vec::new());
+    snappy.write_all(&ser).unwrap();
+    let packed = snappy.into_inner().unwrap();
+
+    let val = decode_value(&packed);
+    assert_eq!(vec![Compression::Snappy], val.layers);
+    assert_eq!(Format::JavaSerialization, val.detection.format);
+    assert!(val.error.is_none());
+    assert!(val.to_string().starts_with("[snappy] JavaSerialization"));
+    match val.decoded {
+        Decoded::Java(contents) => assert_eq!(1, contents.len()),
+        other => panic!("not decoded as java {:?}", other),
+    }
+}
+
+#[test]
+fn test_decode_compressed_json_value() {
+    let packed = zstd::encode_all(&br#"{"tel": ["10086", "10010"]}"#[..], 3).unwrap();
+    let val = decode_value(&packed);
+    assert_eq!("zstd", val.layers_desc());
+    match val.decoded {
+        Decoded::Json(json) => assert_eq!("10010", json["tel"][1]),
+        other => panic!("not decoded as json {:?}", other),
+    }
+}
+
+#[test]
+fn test_decoder_chain_rules() {
+    let mut chain = DecoderChain::builtin();
+    chain
+        .add_rule(DecoderRule {
+            pattern: "legacy:*".to_string(),
+            decoder: "gbk".to_string(),
+        })
+        .unwrap();
+    chain
+        .add_rule(DecoderRule {
+            pattern: "blob:*".to_string(),
+            decoder: "raw".to_string(),
+        })
+        .unwrap();
+    assert!(chain
+        .add_rule(DecoderRule {
+            pattern: "*".to_string(),
+            decoder: "protobuf".to_string(),
+        })
+        .is_err());
+
+    let json = br#"{"a": 1}"#;
+    let val = chain.decode(b"user:1", json);
+    assert_eq!(Some("json"), val.decoder.as_deref());
+    assert_eq!(r#"{"a":1}"#, val.summary());
+    let val = chain.decode(b"blob:1", json);
+    assert_eq!(Some("raw"), val.decoder.as_deref());
+    assert!(matches!(val.decoded, Decoded::Raw(_)));
+
+    //"中文" in gbk
+    let gbk = [0xd6, 0xd0, 0xce, 0xc4];
+    assert_eq!("中文", chain.decode(b"legacy:name", &gbk).summary());
+    let val = chain.decode(b"legacy:name", &[0xff, 0xff]);
+    assert!(val.error.is_some());
+
+    struct Upper;
+    impl Decoder for Upper {
+        fn name(&self) -> &str {
+            "upper"
+        }
+        fn accepts(&self, format: Format) -> bool {
+            format == Format::Utf8Text
+        }
+        fn decode(&self, data: &[u8]) -> Result<Decoded, String> {
+            Ok(Decoded::Text(String::from_utf8_lossy(data).to_uppercase()))
+        }
+    }
+    chain.register(Box::new(Upper));
+    assert_eq!("upper", chain.names()[0]);
+    assert_eq!(
+        "HELLO WORLD, PLAIN TEXT",
+        chain.decode(b"k", b"hello world, plain text").summary()
+    );
+
+    //a failing decoder hands over to the next one that takes the format
+    struct Picky;
+    impl Decoder for Picky {
+        fn name(&self) -> &str {
+            "picky"
+        }
+        fn accepts(&self, format: Format) -> bool {
+            format == Format::Utf8Text
+        }
+        fn decode(&self, _: &[u8]) -> Result<Decoded, String> {
+            Err("never".to_string())
+        }
+    }
+    chain.register(Box::new(Picky));
+    let val = chain.decode(b"k", b"hello world, plain text");
+    assert_eq!(Some("upper"), val.decoder.as_deref());
+    assert!(val.error.is_none());
+}
+
+#[test]
+fn test_decode_xml() {
+    let rules = [DecoderRule {
+        pattern: "x:*".to_string(),
+        decoder: "xml".to_string(),
+    }];
+    let chain = DecoderChain::with_rules(&rules).unwrap();
+    let val = chain.decode(b"k", br#"<?xml version="1.0"?><a x="1"><b>text</b><c/></a>"#);
+    assert_eq!(Some("xml"), val.decoder.as_deref());
+    assert_eq!(
+        "<?xml version=\"1.0\"?>\n<a x=\"1\">\n  <b>text</b>\n  <c/>\n</a>",
+        val.summary()
+    );
+    //the pinned decoder fails, the sniffed one takes over
+    let val = chain.decode(b"x:1", b"<a><b>text</a>");
+    assert_eq!(Some("text"), val.decoder.as_deref());
+    let val = chain.decode(b"x:2", &[0xff, 0x00, 0x3c]);
+    assert!(matches!(val.decoded, Decoded::Raw(_)));
+    assert!(val.error.unwrap().starts_with("xml: "));
+}
@@ -0,0 +1,808 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use redis::{RedisResult, Value};
+
+use crate::cli::{quote_bytes, split_args};
+use crate::redis_conn::ConnectionHolder;
+use crate::value::DecoderChain;
+
+/// how a key is shown, strings split into HyperLogLog and bitmap views
+/// and module types like ReJSON get their own
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueKind {
+    String,
+    Bitmap,
+    HyperLogLog,
+    List,
+    Set,
+    ZSet,
+    Hash,
+    Stream,
+    Json,
+    Module(String),
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueKind::String => write!(f, "string"),
+            ValueKind::Bitmap => write!(f, "bitmap"),
+            ValueKind::HyperLogLog => write!(f, "hyperloglog"),
+            ValueKind::List => write!(f, "list"),
+            ValueKind::Set => write!(f, "set"),
+            ValueKind::ZSet => write!(f, "zset"),
+            ValueKind::Hash => write!(f, "hash"),
+            ValueKind::Stream => write!(f, "stream"),
+            ValueKind::Json => write!(f, "json"),
+            ValueKind::Module(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+pub type Pairs = Vec<(Vec<u8>, Vec<u8>)>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Element {
+    //a string value, list element or set member
+    Item(Vec<u8>),
+    Field(Vec<u8>, Vec<u8>),
+    Scored(Vec<u8>, f64),
+    Entry(String, Pairs),
+    //offset of a set bit
+    Bit(u64),
+}
+
+//quoted only when split_args would not give the bytes back as they are
+pub(crate) fn edit_arg(data: &[u8]) -> String {
+    match std::str::from_utf8(data) {
+        Ok(text)
+            if !text.is_empty()
+                && !text.chars().any(|c| {
+                    c.is_whitespace() || c.is_control() || matches!(c, '"' | '\'' | '\\')
+                }) =>
+        {
+            text.to_string()
+        }
+        Ok(text)
+            if !text
+                .chars()
+                .any(|c| c.is_control() && !"\n\r\t".contains(c)) =>
+        {
+            let mut out = String::from("\"");
+            for c in text.chars() {
+                match c {
+                    '\\' => out.push_str("\\\\"),
+                    '"' => out.push_str("\\\""),
+                    '\n' => out.push_str("\\n"),
+                    '\r' => out.push_str("\\r"),
+                    '\t' => out.push_str("\\t"),
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+            out
+        }
+        _ => quote_bytes(data),
+    }
+}
+
+fn lossy(data: &[u8]) -> std::borrow::Cow<'_, str> {
+    String::from_utf8_lossy(data)
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Element::Item(data) => write!(f, "{}", lossy(data)),
+            Element::Field(field, val) => write!(f, "{} => {}", lossy(field), lossy(val)),
+            Element::Scored(member, score) => write!(f, "({}) {}", score, lossy(member)),
+            Element::Entry(id, fields) => {
+                write!(f, "{}", id)?;
+                for (field, val) in fields {
+                    write!(f, " {}={}", lossy(field), lossy(val))?;
+                }
+                Ok(())
+            }
+            Element::Bit(offset) => write!(f, "bit {}", offset),
+        }
+    }
+}
+
+impl Element {
+    /// like Display, with every payload run through the key's decoder chain
+    pub fn decoded(&self, key: &[u8], chain: &DecoderChain) -> String {
+        let d = |data: &[u8]| chain.decode(key, data).summary();
+        match self {
+            Element::Item(data) => d(data),
+            Element::Field(field, val) => format!("{} => {}", lossy(field), d(val)),
+            Element::Scored(member, score) => format!("({}) {}", score, d(member)),
+            Element::Entry(id, fields) => {
+                let mut out = id.clone();
+                for (field, val) in fields {
+                    out.push_str(&format!(" {}={}", lossy(field), d(val)));
+                }
+                out
+            }
+            Element::Bit(offset) => format!("bit {}", offset),
+        }
+    }
+
+    /// the text an edit starts from, parse turns it back into the element
+    pub fn edit_text(&self, kind: &ValueKind) -> String {
+        match (self, kind) {
+            (Element::Item(data), ValueKind::String) | (Element::Item(data), ValueKind::Json) => {
+                match std::str::from_utf8(data) {
+                    Ok(text) if !text.starts_with('"') => text.to_string(),
+                    //binary or starting with a quote, quoted so parse gives the bytes back
+                    _ => quote_bytes(data),
+                }
+            }
+            (Element::Item(data), _) => edit_arg(data),
+            (Element::Field(field, val), _) => format!("{} {}", edit_arg(field), edit_arg(val)),
+            (Element::Scored(member, score), _) => format!("{} {}", score, edit_arg(member)),
+            (Element::Entry(id, fields), _) => {
+                let mut out = id.clone();
+                for (field, val) in fields {
+                    out.push_str(&format!(" {} {}", edit_arg(field), edit_arg(val)));
+                }
+                out
+            }
+            (Element::Bit(offset), _) => offset.to_string(),
+        }
+    }
+
+    /// what the user typed, strings and json are taken whole unless they start with a double
+    /// quote, members use redis-cli quoting:
+    /// `field value` for hashes, `score member` for zsets, `id|* field value...` for streams
+    pub fn parse(kind: &ValueKind, text: &str) -> Result<Element, String> {
+        let whole = matches!(kind, ValueKind::String | ValueKind::Json);
+        if whole && !text.starts_with('"') {
+            return Ok(Element::Item(text.as_bytes().to_vec()));
+        }
+        let mut args = split_args(text.as_bytes()).map_err(|e| e.to_string())?;
+        if whole {
+            return match args.len() {
+                1 => Ok(Element::Item(args.remove(0))),
+                _ => Err("expected one quoted value".to_string()),
+            };
+        }
+        let arity = |n: usize, usage: &str| {
+            if args.len() == n {
+                Ok(())
+            } else {
+                Err(format!("expected {}", usage))
+            }
+        };
+        match kind {
+            ValueKind::List | ValueKind::Set => {
+                arity(1, "one value")?;
+                Ok(Element::Item(args.remove(0)))
+            }
+            ValueKind::Hash => {
+                arity(2, "field value")?;
+                let val = args.pop().unwrap();
+                Ok(Element::Field(args.pop().unwrap(), val))
+            }
+            ValueKind::ZSet => {
+                arity(2, "score member")?;
+                let score = lossy(&args[0])
+                    .parse()
+                    .map_err(|_| format!("bad score {}", lossy(&args[0])))?;
+                Ok(Element::Scored(args.pop().unwrap(), score))
+            }
+            ValueKind::Stream => {
+                if args.len() < 3 || args.len() % 2 == 0 {
+                    return Err("expected id|* field value [field value ...]".to_string());
+                }
+                let id = lossy(&args[0]).into_owned();
+                let fields = args[1..]
+                    .chunks(2)
+                    .map(|pair| (pair[0].clone(), pair[1].clone()))
+                    .collect();
+                Ok(Element::Entry(id, fields))
+            }
+            ValueKind::Bitmap => {
+                arity(1, "a bit offset")?;
+                lossy(&args[0])
+                    .parse()
+                    .map(Element::Bit)
+                    .map_err(|_| format!("bad offset {}", lossy(&args[0])))
+            }
+            other => Err(format!("{} values can't be edited here", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PageCursor {
+    //next index of a list or zset, next byte of a bitmap
+    Index(u64),
+    Scan(u64),
+    //id of the last stream entry seen
+    After(Option<String>),
+    Done,
+}
+
+/// one key's value, loaded a page at a time
+#[derive(Debug, Clone)]
+pub struct ValueViewer {
+    pub key: Vec<u8>,
+    pub kind: ValueKind,
+    //seconds, -1 without expire
+    pub ttl: i64,
+    //length, member count or PFCOUNT estimate, none for module types
+    pub count: Option<u64>,
+    pub page_size: usize,
+    pub elements: Vec<Element>,
+    cursor: PageCursor,
+    //SSCAN and HSCAN may return a member twice
+    seen: HashSet<Vec<u8>>,
+}
+
+fn client_err(msg: String) -> redis::RedisError {
+    (redis::ErrorKind::ClientError, "viewer", msg).into()
+}
+
+fn key_cmd<T: AsRef<[u8]>>(
+    holder: &mut ConnectionHolder,
+    cmd: &str,
+    key: &[u8],
+    rest: &[T],
+) -> RedisResult<Value> {
+    let mut args = vec![cmd.as_bytes(), key];
+    args.extend(rest.iter().map(|a| a.as_ref()));
+    holder.execute(&args)
+}
+
+const NO_ARGS: [&str; 0] = [];
+
+/// stream ids only grow, the smallest id after ms-seq is ms-(seq+1)
+pub(crate) fn next_stream_id(id: &str) -> String {
+    match id.split_once('-') {
+        Some((ms, seq)) => match seq.parse::<u64>() {
+            Ok(seq) if seq < u64::MAX => format!("{}-{}", ms, seq + 1),
+            _ => format!("{}-0", ms.parse::<u64>().unwrap_or(0) + 1),
+        },
+        None => format!("{}-1", id),
+    }
+}
+
+/// offsets of the set bits, redis counts bits from the most significant one
+pub fn set_bits(start: u64, bytes: &[u8]) -> Vec<u64> {
+    let mut bits = Vec::new();
+    for (i, byte) in bytes.iter().enumerate() {
+        for bit in 0..8 {
+            if byte & (0x80 >> bit) != 0 {
+                bits.push((start + i as u64) * 8 + bit);
+            }
+        }
+    }
+    bits
+}
+
+pub(crate) fn parse_entries(val: &Value) -> RedisResult<Vec<Element>> {
+    //each entry is a nested [id, [field, value...]], Vec<(..)> would read them flat
+    let entries: Vec<Value> = redis::from_redis_value(val)?;
+    entries
+        .iter()
+        .map(|entry| {
+            let (id, flat): (String, Vec<Vec<u8>>) = redis::from_redis_value(entry)?;
+            let fields = flat
+                .chunks(2)
+                .filter(|pair| pair.len() == 2)
+                .map(|pair| (pair[0].clone(), pair[1].clone()))
+                .collect();
+            Ok(Element::Entry(id, fields))
+        })
+        .collect()
+}
+
+impl ValueViewer {
+    /// look the type up and load the first page
+    pub fn open(holder: &mut ConnectionHolder, key: &[u8]) -> RedisResult<ValueViewer> {
+        let kind: String = redis::from_redis_value(&key_cmd(holder, "TYPE", key, &NO_ARGS)?)?;
+        let kind = match kind.as_str() {
+            "none" => return Err(client_err(format!("no such key {}", lossy(key)))),
+            "string" => {
+                let magic: Vec<u8> =
+                    redis::from_redis_value(&key_cmd(holder, "GETRANGE", key, &["0", "3"])?)?;
+                if magic == b"HYLL" {
+                    ValueKind::HyperLogLog
+                } else {
+                    ValueKind::String
+                }
+            }
+            "list" => ValueKind::List,
+            "set" => ValueKind::Set,
+            "zset" => ValueKind::ZSet,
+            "hash" => ValueKind::Hash,
+            "stream" => ValueKind::Stream,
+            "ReJSON-RL" => ValueKind::Json,
+            other => ValueKind::Module(other.to_string()),
+        };
+        Self::open_as(holder, key, kind)
+    }
+
+    /// view a key a given way, eg a string as a bitmap
+    pub fn open_as(
+        holder: &mut ConnectionHolder,
+        key: &[u8],
+        kind: ValueKind,
+    ) -> RedisResult<ValueViewer> {
+        let mut viewer = ValueViewer {
+            key: key.to_vec(),
+            kind,
+            ttl: -1,
+            count: None,
+            page_size: 100,
+            elements: Vec::new(),
+            cursor: PageCursor::Done,
+            seen: HashSet::new(),
+        };
+        viewer.reload(holder)?;
+        Ok(viewer)
+    }
+
+    /// a viewer over elements already in hand, eg read from a dump file
+    pub fn from_elements(key: &[u8], kind: ValueKind, elements: Vec<Element>) -> ValueViewer {
+        ValueViewer {
+            key: key.to_vec(),
+            kind,
+            ttl: -1,
+            count: Some(elements.len() as u64),
+            page_size: 100,
+            elements,
+            cursor: PageCursor::Done,
+            seen: HashSet::new(),
+        }
+    }
+
+    fn start_cursor(&self) -> PageCursor {
+        match self.kind {
+            ValueKind::Set | ValueKind::Hash => PageCursor::Scan(0),
+            ValueKind::Stream => PageCursor::After(None),
+            _ => PageCursor::Index(0),
+        }
+    }
+
+    fn count_command(&self) -> Option<&'static str> {
+        match self.kind {
+            ValueKind::String => Some("STRLEN"),
+            ValueKind::Bitmap => Some("BITCOUNT"),
+            ValueKind::HyperLogLog => Some("PFCOUNT"),
+            ValueKind::List => Some("LLEN"),
+            ValueKind::Set => Some("SCARD"),
+            ValueKind::ZSet => Some("ZCARD"),
+            ValueKind::Hash => Some("HLEN"),
+            ValueKind::Stream => Some("XLEN"),
+            ValueKind::Json | ValueKind::Module(_) => None,
+        }
+    }
+
+    /// fetch ttl and count again and as many elements as were loaded before
+    pub fn reload(&mut self, holder: &mut ConnectionHolder) -> RedisResult<()> {
+        let loaded = self.elements.len().max(1);
+        self.ttl = redis::from_redis_value(&key_cmd(holder, "TTL", &self.key, &NO_ARGS)?)?;
+        self.count = match self.count_command() {
+            Some(cmd) => Some(redis::from_redis_value(&key_cmd(
+                holder, cmd, &self.key, &NO_ARGS,
+            )?)?),
+            None => None,
+        };
+        self.elements.clear();
+        self.seen.clear();
+        self.cursor = self.start_cursor();
+        while self.has_more() && self.elements.len() < loaded {
+            self.next_page(holder)?;
+        }
+        Ok(())
+    }
+
+    pub fn has_more(&self) -> bool {
+        self.cursor != PageCursor::Done
+    }
+
+    /// load the next page onto elements, returns how many were added
+    pub fn next_page(&mut self, holder: &mut ConnectionHolder) -> RedisResult<usize> {
+        let size = self.page_size.max(1) as u64;
+        let before = self.elements.len();
+        let key = self.key.clone();
+        let (page, next) = match (&self.kind, &self.cursor) {
+            (_, PageCursor::Done) => return Ok(0),
+            (ValueKind::String, _) => {
+                let raw: Vec<u8> =
+                    redis::from_redis_value(&key_cmd(holder, "GET", &key, &NO_ARGS)?)?;
+                (vec![Element::Item(raw)], PageCursor::Done)
+            }
+            (ValueKind::Json, _) => {
+                let raw: Vec<u8> =
+                    redis::from_redis_value(&key_cmd(holder, "JSON.GET", &key, &NO_ARGS)?)?;
+                (vec![Element::Item(raw)], PageCursor::Done)
+            }
+            //the count says it all, or there is no generic way to look inside
+            (ValueKind::HyperLogLog, _) | (ValueKind::Module(_), _) => (vec![], PageCursor::Done),
+            (ValueKind::Bitmap, PageCursor::Index(start)) => {
+                let end = (start + size - 1).to_string();
+                let bytes: Vec<u8> = redis::from_redis_value(&key_cmd(
+                    holder,
+                    "GETRANGE",
+                    &key,
+                    &[start.to_string(), end],
+                )?)?;
+                let next = if (bytes.len() as u64) < size {
+                    PageCursor::Done
+                } else {
+                    PageCursor::Index(start + size)
+                };
+                let bits = set_bits(*start, &bytes)
+                    .into_iter()
+                    .map(Element::Bit)
+                    .collect();
+                (bits, next)
+            }
+            (ValueKind::List, PageCursor::Index(start)) => {
+                let end = (start + size - 1).to_string();
+                let items: Vec<Vec<u8>> = redis::from_redis_value(&key_cmd(
+                    holder,
+                    "LRANGE",
+                    &key,
+                    &[start.to_string(), end],
+                )?)?;
+                let next = if (items.len() as u64) < size {
+                    PageCursor::Done
+                } else {
+                    PageCursor::Index(start + size)
+                };
+                (items.into_iter().map(Element::Item).collect(), next)
+            }
+            (ValueKind::ZSet, PageCursor::Index(start)) => {
+                let end = (start + size - 1).to_string();
+                let items: Vec<(Vec<u8>, f64)> = redis::from_redis_value(&key_cmd(
+                    holder,
+                    "ZRANGE",
+                    &key,
+                    &[start.to_string(), end, "WITHSCORES".to_string()],
+                )?)?;
+                let next = if (items.len() as u64) < size {
+                    PageCursor::Done
+                } else {
+                    PageCursor::Index(start + size)
+                };
+                let items = items
+                    .into_iter()
+                    .map(|(member, score)| Element::Scored(member, score))
+                    .collect();
+                (items, next)
+            }
+            (ValueKind::Set, PageCursor::Scan(cursor)) => {
+                let (next, members): (u64, Vec<Vec<u8>>) = redis::from_redis_value(&key_cmd(
+                    holder,
+                    "SSCAN",
+                    &key,
+                    &[cursor.to_string(), "COUNT".to_string(), size.to_string()],
+                )?)?;
+                let members = members
+                    .into_iter()
+                    .filter(|m| self.seen.insert(m.clone()))
+                    .map(Element::Item)
+                    .collect();
+                (
+                    members,
+                    if next == 0 {
+                        PageCursor::Done
+                    } else {
+                        PageCursor::Scan(next)
+                    },
+                )
+            }
+            (ValueKind::Hash, PageCursor::Scan(cursor)) => {
+                let (next, fields): (u64, Pairs) = redis::from_redis_value(&key_cmd(
+                    holder,
+                    "HSCAN",
+                    &key,
+                    &[cursor.to_string(), "COUNT".to_string(), size.to_string()],
+                )?)?;
+                let fields = fields
+                    .into_iter()
+                    .filter(|(f, _)| self.seen.insert(f.clone()))
+                    .map(|(f, v)| Element::Field(f, v))
+                    .collect();
+                (
+                    fields,
+                    if next == 0 {
+                        PageCursor::Done
+                    } else {
+                        PageCursor::Scan(next)
+                    },
+                )
+            }
+            (ValueKind::Stream, PageCursor::After(last)) => {
+                let start = last.as_deref().map_or("-".to_string(), next_stream_id);
+                let entries = parse_entries(&key_cmd(
+                    holder,
+                    "XRANGE",
+                    &key,
+                    &[
+                        start,
+                        "+".to_string(),
+                        "COUNT".to_string(),
+                        size.to_string(),
+                    ],
+                )?)?;
+                let next = match entries.last() {
+                    Some(Element::Entry(id, _)) if entries.len() as u64 == size => {
+                        PageCursor::After(Some(id.clone()))
+                    }
+                    _ => PageCursor::Done,
+                };
+                (entries, next)
+            }
+            (kind, cursor) => {
+                return Err(client_err(format!("{} can't page from {:?}", kind, cursor)))
+            }
+        };
+        self.elements.extend(page);
+        self.cursor = next;
+        Ok(self.elements.len() - before)
+    }
+
+    fn element(&self, index: usize) -> RedisResult<&Element> {
+        self.elements
+            .get(index)
+            .ok_or_else(|| client_err(format!("no element {}", index)))
+    }
+
+    //pipeline checks safe mode, an edit or delete on a read-only connection is refused
+    fn atomic(&self, holder: &mut ConnectionHolder, pipe: &redis::Pipeline) -> RedisResult<()> {
+        holder.pipeline::<()>(pipe)
+    }
+
+    /// RPUSH, SADD, ZADD, HSET, XADD or SETBIT 1, the view follows along
+    pub fn add(&mut self, holder: &mut ConnectionHolder, element: Element) -> RedisResult<()> {
+        let key = self.key.clone();
+        match (&self.kind, &element) {
+            (ValueKind::List, Element::Item(val)) => {
+                key_cmd(holder, "RPUSH", &key, &[val])?;
+            }
+            (ValueKind::Set, Element::Item(val)) => {
+                key_cmd(holder, "SADD", &key, &[val])?;
+            }
+            (ValueKind::ZSet, Element::Scored(member, score)) => {
+                key_cmd(
+                    holder,
+                    "ZADD",
+                    &key,
+                    &[score.to_string().as_bytes(), member],
+                )?;
+            }
+            (ValueKind::Hash, Element::Field(field, val)) => {
+                key_cmd(holder, "HSET", &key, &[field, val])?;
+            }
+            (ValueKind::Stream, Element::Entry(id, fields)) => {
+                let mut args = vec![id.as_bytes()];
+                for (field, val) in fields {
+                    args.push(field);
+                    args.push(val);
+                }
+                key_cmd(holder, "XADD", &key, &args)?;
+            }
+            (ValueKind::Bitmap, Element::Bit(offset)) => {
+                key_cmd(
+                    holder,
+                    "SETBIT",
+                    &key,
+                    &[offset.to_string(), "1".to_string()],
+                )?;
+            }
+            (kind, _) => return Err(client_err(format!("can't add {} to a {}", element, kind))),
+        }
+        self.reload(holder)
+    }
+
+    /// replace the element at index, renamed members go in one MULTI
+    pub fn update(
+        &mut self,
+        holder: &mut ConnectionHolder,
+        index: usize,
+        element: Element,
+    ) -> RedisResult<()> {
+        let key = self.key.clone();
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        match (&self.kind, self.element(index)?, &element) {
+            (ValueKind::String, _, Element::Item(val)) => {
+                //SET drops the expire, put it back inside the same MULTI
+                let pttl: i64 = redis::from_redis_value(&key_cmd(holder, "PTTL", &key, &NO_ARGS)?)?;
+                pipe.cmd("SET").arg(&key[..]).arg(&val[..]);
+                if pttl > 0 {
+                    pipe.cmd("PEXPIRE").arg(&key[..]).arg(pttl);
+                }
+            }
+            (ValueKind::Json, _, Element::Item(val)) => {
+                pipe.cmd("JSON.SET").arg(&key[..]).arg(".").arg(&val[..]);
+            }
+            (ValueKind::List, _, Element::Item(val)) => {
+                pipe.cmd("LSET").arg(&key[..]).arg(index).arg(&val[..]);
+            }
+            (ValueKind::Set, Element::Item(old), Element::Item(val)) => {
+                pipe.cmd("SREM").arg(&key[..]).arg(&old[..]);
+                pipe.cmd("SADD").arg(&key[..]).arg(&val[..]);
+            }
+            (ValueKind::ZSet, Element::Scored(old, _), Element::Scored(member, score)) => {
+                if old != member {
+                    pipe.cmd("ZREM").arg(&key[..]).arg(&old[..]);
+                }
+                pipe.cmd("ZADD").arg(&key[..]).arg(*score).arg(&member[..]);
+            }
+            (ValueKind::Hash, Element::Field(old, _), Element::Field(field, val)) => {
+                if old != field {
+                    pipe.cmd("HDEL").arg(&key[..]).arg(&old[..]);
+                }
+                pipe.cmd("HSET").arg(&key[..]).arg(&field[..]).arg(&val[..]);
+            }
+            (ValueKind::Stream, _, _) => {
+                return Err(client_err(
+                    "stream entries can't change, delete and add one".to_string(),
+                ))
+            }
+            (kind, _, _) => return Err(client_err(format!("can't set {} in a {}", element, kind))),
+        }
+        self.atomic(holder, &pipe)?;
+        self.reload(holder)
+    }
+
+    /// remove one member, a list element goes through LSET to a marker and LREM
+    pub fn delete(&mut self, holder: &mut ConnectionHolder, index: usize) -> RedisResult<()> {
+        let key = self.key.clone();
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        match (&self.kind, self.element(index)?) {
+            (ValueKind::List, _) => {
+                let marker = format!("__rust_a_deleted_{}__", uuid::Uuid::new_v4());
+                pipe.cmd("LSET").arg(&key[..]).arg(index).arg(&marker);
+                pipe.cmd("LREM").arg(&key[..]).arg(1).arg(&marker);
+            }
+            (ValueKind::Set, Element::Item(member)) => {
+                pipe.cmd("SREM").arg(&key[..]).arg(&member[..]);
+            }
+            (ValueKind::ZSet, Element::Scored(member, _)) => {
+                pipe.cmd("ZREM").arg(&key[..]).arg(&member[..]);
+            }
+            (ValueKind::Hash, Element::Field(field, _)) => {
+                pipe.cmd("HDEL").arg(&key[..]).arg(&field[..]);
+            }
+            (ValueKind::Stream, Element::Entry(id, _)) => {
+                pipe.cmd("XDEL").arg(&key[..]).arg(id);
+            }
+            (ValueKind::Bitmap, Element::Bit(offset)) => {
+                pipe.cmd("SETBIT").arg(&key[..]).arg(*offset).arg(0);
+            }
+            (kind, _) => {
+                return Err(client_err(format!(
+                    "a {} has no members, delete the key instead",
+                    kind
+                )))
+            }
+        }
+        self.atomic(holder, &pipe)?;
+        self.reload(holder)
+    }
+
+    /// one numbered line per element for the console and the dashboard
+    pub fn lines(&self) -> Vec<String> {
+        self.elements
+            .iter()
+            .enumerate()
+            .map(|(i, e)| format!("{}) {}", i + 1, e))
+            .collect()
+    }
+
+    /// lines with the values decoded, eg hessian hash fields as objects
+    pub fn decoded_lines(&self, chain: &DecoderChain) -> Vec<String> {
+        self.elements
+            .iter()
+            .enumerate()
+            .map(|(i, e)| format!("{}) {}", i + 1, e.decoded(&self.key, chain)))
+            .collect()
+    }
+}
+
+#[test]
+fn test_element_edit_roundtrip() {
+    let cases = vec![
+        (ValueKind::List, Element::Item(b"plain".to_vec())),
+        (ValueKind::Set, Element::Item("啧 'q'".as_bytes().to_vec())),
+        (ValueKind::Set, Element::Item(vec![0xff, 0x00, b'"'])),
+        (
+            ValueKind::Hash,
+            Element::Field(b"full name".to_vec(), b"a\nb".to_vec()),
+        ),
+        (ValueKind::ZSet, Element::Scored(b"m".to_vec(), -1.5)),
+        (
+            ValueKind::Stream,
+            Element::Entry("*".to_string(), vec![(b"f".to_vec(), b"v 1".to_vec())]),
+        ),
+        (ValueKind::Bitmap, Element::Bit(17)),
+        (ValueKind::Json, Element::Item(br#"{"a": "b c"}"#.to_vec())),
+        (ValueKind::String, Element::Item(b"line\nnext".to_vec())),
+        (ValueKind::String, Element::Item(vec![0xac, 0xed, 0x00, 0x05])),
+        (ValueKind::Json, Element::Item(br#""quoted""#.to_vec())),
+    ];
+    for (kind, element) in cases {
+        let text = element.edit_text(&kind);
+        assert_eq!(Ok(element), Element::parse(&kind, &text), "{}", text);
+    }
+    assert!(Element::parse(&ValueKind::Hash, "lonely").is_err());
+    assert!(Element::parse(&ValueKind::ZSet, "x member").is_err());
+    assert!(Element::parse(&ValueKind::Stream, "* f").is_err());
+    assert!(Element::parse(&ValueKind::HyperLogLog, "1").is_err());
+    assert!(Element::parse(&ValueKind::String, "\"a\" b").is_err());
+}
+
+#[test]
+fn test_bits_and_stream_ids() {
+    assert_eq!(vec![0, 7, 9], set_bits(0, &[0b1000_0001, 0b0100_0000]));
+    assert_eq!(vec![17], set_bits(2, &[0b0100_0000]));
+    assert_eq!("1526919030474-56", next_stream_id("1526919030474-55"));
+    assert_eq!(
+        "1526919030475-0",
+        next_stream_id(&format!("1526919030474-{}", u64::MAX))
+    );
+    assert_eq!("(-1.5) m", Element::Scored(b"m".to_vec(), -1.5).to_string());
+}
+
+#[test]
+fn test_view_and_edit_types() -> RedisResult<()> {
+    let mut holder = ConnectionHolder::new();
+    holder.put("redis://127.0.0.1:6379/0")?;
+    holder.execute(&["DEL", "view:list", "view:zset", "view:hll", "view:stream"])?;
+    for i in 0..250 {
+        holder.execute(&["RPUSH", "view:list", &i.to_string()])?;
+    }
+    let mut list = ValueViewer::open(&mut holder, b"view:list")?;
+    assert_eq!(ValueKind::List, list.kind);
+    assert_eq!(Some(250), list.count);
+    assert_eq!(100, list.elements.len());
+    while list.has_more() {
+        list.next_page(&mut holder)?;
+    }
+    assert_eq!(250, list.elements.len());
+    list.update(&mut holder, 1, Element::Item(b"one".to_vec()))?;
+    list.delete(&mut holder, 0)?;
+    assert_eq!(Some(249), list.count);
+    assert_eq!(Element::Item(b"one".to_vec()), list.elements[0]);
+
+    holder.execute(&["ZADD", "view:zset", "1", "a", "2", "b"])?;
+    let mut zset = ValueViewer::open(&mut holder, b"view:zset")?;
+    zset.update(&mut holder, 0, Element::Scored(b"c".to_vec(), 3.0))?;
+    assert_eq!(
+        vec![
+            Element::Scored(b"b".to_vec(), 2.0),
+            Element::Scored(b"c".to_vec(), 3.0)
+        ],
+        zset.elements
+    );
+
+    holder.execute(&["PFADD", "view:hll", "a", "b", "c"])?;
+    let hll = ValueViewer::open(&mut holder, b"view:hll")?;
+    assert_eq!(ValueKind::HyperLogLog, hll.kind);
+    assert_eq!(Some(3), hll.count);
+
+    let mut stream = ValueViewer::open_as(&mut holder, b"view:stream", ValueKind::Stream)?;
+    stream.page_size = 2;
+    for _ in 0..3 {
+        stream.add(
+            &mut holder,
+            Element::parse(&ValueKind::Stream, "* field value").unwrap(),
+        )?;
+    }
+    assert_eq!(Some(3), stream.count);
+    assert_eq!(2, stream.elements.len());
+    stream.next_page(&mut holder)?;
+    assert_eq!(3, stream.elements.len());
+    assert!(!stream.has_more());
+
+    holder.execute(&["DEL", "view:list", "view:zset", "view:hll", "view:stream"])?;
+    Ok(())
+}
@@ -0,0 +1,38 @@
+import java.io.*;
+import java.math.BigDecimal;
+import java.util.*;
+
+// Writes the JDK serialization fixtures used by the javaser tests.
+public class GenSer {
+    static void write(String name, Object obj) throws IOException {
+        try (ObjectOutputStream out = new ObjectOutputStream(new FileOutputStream(name))) {
+            out.writeObject(obj);
+        }
+    }
+
+    public static void main(String[] args) throws IOException {
+        HashMap<String, Object> map = new HashMap<>();
+        map.put("name", "张三丰");
+        map.put("age", 100);
+        map.put("id", 10086L);
+        write("hashmap.ser", map);
+
+        LinkedHashMap<String, Integer> linked = new LinkedHashMap<>();
+        linked.put("b", 2);
+        linked.put("a", 1);
+        write("linkedhashmap.ser", linked);
+
+        ArrayList<Object> list = new ArrayList<>();
+        list.add("10086");
+        list.add("10010");
+        list.add(new Date(1617181920000L));
+        list.add(new BigDecimal("-12345678901234567890.125"));
+        list.add("10086");
+        write("arraylist.ser", list);
+
+        write("hashset.ser", new HashSet<>(Arrays.asList(1, 2, 3)));
+        write("date.ser", new Date(1617181920000L));
+        write("bigdecimal.ser", new BigDecimal("3.1415"));
+        write("long.ser", Long.valueOf(-42L));
+    }
+}
@@ -0,0 +1,71 @@
+import java.io.Serializable;
+import java.util.ArrayList;
+import java.util.List;
+import java.util.function.Supplier;
+
+// Class file fixture for the classfile/javap/jar tests.
+public class Sample<T extends Comparable<T>> implements Serializable, Cloneable {
+    public static final long BIG = 1234567890123L;
+    public static final double PI = 3.14159;
+    private static final String NAME = "sample";
+    protected transient int count;
+    private List<T> items = new ArrayList<>();
+    volatile float ratio = 0.5f;
+
+    public Sample() {
+    }
+
+    public synchronized void add(T item) {
+        items.add(item);
+        count++;
+    }
+
+    public int classify(int code) {
+        switch (code) {
+            case 1: return 10;
+            case 2: return 20;
+            case 3: return 30;
+            default:
+                break;
+        }
+        switch (code) {
+            case 100: return -1;
+            case 1000: return -2;
+            default: return 0;
+        }
+    }
+
+    public long sum(int[] values, long start) {
+        long total = start;
+        for (int i = 0; i < values.length; i++) {
+            total += values[i];
+        }
+        return total;
+    }
+
+    public String describe(Object o) {
+        try {
+            Supplier<String> s = () -> NAME + ":" + o;
+            return s.get();
+        } catch (RuntimeException e) {
+            return null;
+        } finally {
+            count = 0;
+        }
+    }
+
+    static int[][] grid(int n) {
+        int[][] g = new int[n][n];
+        Object o = g;
+        if (o instanceof int[][] && n > 128) {
+            return (int[][]) o;
+        }
+        return g;
+    }
+
+    private native void ping();
+
+    protected abstract static class Inner {
+        abstract double area();
+    }
+}
@@ -0,0 +1,10 @@
+// Writes account_v1.ser from the v1 Account; demo.jar holds the v2 Account and Sample classes.
+import java.io.*;
+
+public class Dump {
+    public static void main(String[] args) throws Exception {
+        try (ObjectOutputStream out = new ObjectOutputStream(new FileOutputStream(args[0]))) {
+            out.writeObject(new demo.Account());
+        }
+    }
+}
@@ -0,0 +1,15 @@
+package demo;
+
+import java.io.Serializable;
+
+public class Account implements Serializable {
+    private static final long serialVersionUID = 1L;
+    private String owner = "alex";
+    private long balance = 100;
+    private int level = 3;
+    private Entry last = new Entry();
+
+    static class Entry implements Serializable {
+        int amount = 7;
+    }
+}
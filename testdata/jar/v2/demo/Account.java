@@ -0,0 +1,18 @@
+package demo;
+
+import java.io.Serializable;
+
+// Newer version of the account: uid bumped, balance narrowed, level dropped, email added.
+public class Account implements Serializable {
+    private static final long serialVersionUID = 2L;
+    private String owner;
+    private int balance;
+    private String email;
+    private transient int cache;
+    private Entry last;
+
+    private static class Entry implements Serializable {
+        int amount;
+        String memo;
+    }
+}